@@ -3,6 +3,11 @@ use std::collections::{HashMap, HashSet};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use serde::Serialize;
 
+use crate::domain::Phase;
+use crate::hg::{HgAction, SyncScope};
+
+pub const CONFIRMATION_POLICIES: &[&str] = &["never", "always", "typed"];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ActionId {
@@ -20,19 +25,60 @@ pub enum ActionId {
     Commit,
     CommitInteractive,
     Bookmark,
+    BookmarkDelete,
+    BookmarkRename,
+    BookmarkMove,
+    BookmarkDeactivate,
+    BranchCreate,
+    ToggleCloseBranch,
+    JumpToRevision,
+    TagCreate,
+    TagCreateLocal,
+    TagRemove,
     Shelve,
     Push,
+    PushRevision,
+    PushBookmark,
+    PushBranch,
     Pull,
+    PullRevision,
+    PullBookmark,
+    CyclePullMode,
     Incoming,
     Outgoing,
     UpdateSelected,
     UnshelveSelected,
+    ShelveDelete,
+    ToggleShelveKeep,
     ResolveMark,
     ResolveUnmark,
     RebaseSelected,
     RebaseContinue,
     RebaseAbort,
     HisteditSelected,
+    HisteditContinue,
+    HisteditAbort,
+    BackoutSelected,
+    PhaseSecret,
+    PhaseDraft,
+    PhasePublic,
+    EvolvePrev,
+    EvolveNext,
+    Evolve,
+    EvolveAll,
+    PruneSelected,
+    ObslogSelected,
+    TopicCreate,
+    TopicSwitch,
+    TopicClear,
+    TopicsList,
+    ToggleLogFailures,
+    ExportLog,
+    CancelJob,
+    Undo,
+    ShowBackups,
+    RestoreBackup,
+    DryRunNext,
     HardRefresh,
 }
 
@@ -53,19 +99,60 @@ impl ActionId {
             Self::Commit => "commit",
             Self::CommitInteractive => "commit_interactive",
             Self::Bookmark => "bookmark",
+            Self::BookmarkDelete => "bookmark_delete",
+            Self::BookmarkRename => "bookmark_rename",
+            Self::BookmarkMove => "bookmark_move",
+            Self::BookmarkDeactivate => "bookmark_deactivate",
+            Self::BranchCreate => "branch_create",
+            Self::ToggleCloseBranch => "toggle_close_branch",
+            Self::JumpToRevision => "jump_to_revision",
+            Self::TagCreate => "tag_create",
+            Self::TagCreateLocal => "tag_create_local",
+            Self::TagRemove => "tag_remove",
             Self::Shelve => "shelve",
             Self::Push => "push",
+            Self::PushRevision => "push_revision",
+            Self::PushBookmark => "push_bookmark",
+            Self::PushBranch => "push_branch",
             Self::Pull => "pull",
+            Self::PullRevision => "pull_revision",
+            Self::PullBookmark => "pull_bookmark",
+            Self::CyclePullMode => "cycle_pull_mode",
             Self::Incoming => "incoming",
             Self::Outgoing => "outgoing",
             Self::UpdateSelected => "update_selected",
             Self::UnshelveSelected => "unshelve_selected",
+            Self::ShelveDelete => "shelve_delete",
+            Self::ToggleShelveKeep => "toggle_shelve_keep",
             Self::ResolveMark => "resolve_mark",
             Self::ResolveUnmark => "resolve_unmark",
             Self::RebaseSelected => "rebase_selected",
             Self::RebaseContinue => "rebase_continue",
             Self::RebaseAbort => "rebase_abort",
             Self::HisteditSelected => "histedit_selected",
+            Self::HisteditContinue => "histedit_continue",
+            Self::HisteditAbort => "histedit_abort",
+            Self::BackoutSelected => "backout_selected",
+            Self::PhaseSecret => "phase_secret",
+            Self::PhaseDraft => "phase_draft",
+            Self::PhasePublic => "phase_public",
+            Self::EvolvePrev => "evolve_prev",
+            Self::EvolveNext => "evolve_next",
+            Self::Evolve => "evolve",
+            Self::EvolveAll => "evolve_all",
+            Self::PruneSelected => "prune_selected",
+            Self::ObslogSelected => "obslog_selected",
+            Self::TopicCreate => "topic_create",
+            Self::TopicSwitch => "topic_switch",
+            Self::TopicClear => "topic_clear",
+            Self::TopicsList => "topics_list",
+            Self::ToggleLogFailures => "toggle_log_failures",
+            Self::ExportLog => "export_log",
+            Self::CancelJob => "cancel_job",
+            Self::Undo => "undo",
+            Self::ShowBackups => "show_backups",
+            Self::RestoreBackup => "restore_backup",
+            Self::DryRunNext => "dry_run_next",
             Self::HardRefresh => "hard_refresh",
         }
     }
@@ -86,19 +173,60 @@ impl ActionId {
             "commit" => Some(Self::Commit),
             "commit_interactive" => Some(Self::CommitInteractive),
             "bookmark" => Some(Self::Bookmark),
+            "bookmark_delete" => Some(Self::BookmarkDelete),
+            "bookmark_rename" => Some(Self::BookmarkRename),
+            "bookmark_move" => Some(Self::BookmarkMove),
+            "bookmark_deactivate" => Some(Self::BookmarkDeactivate),
+            "branch_create" => Some(Self::BranchCreate),
+            "toggle_close_branch" => Some(Self::ToggleCloseBranch),
+            "jump_to_revision" => Some(Self::JumpToRevision),
+            "tag_create" => Some(Self::TagCreate),
+            "tag_create_local" => Some(Self::TagCreateLocal),
+            "tag_remove" => Some(Self::TagRemove),
             "shelve" => Some(Self::Shelve),
             "push" => Some(Self::Push),
+            "push_revision" => Some(Self::PushRevision),
+            "push_bookmark" => Some(Self::PushBookmark),
+            "push_branch" => Some(Self::PushBranch),
             "pull" => Some(Self::Pull),
+            "pull_revision" => Some(Self::PullRevision),
+            "pull_bookmark" => Some(Self::PullBookmark),
+            "cycle_pull_mode" => Some(Self::CyclePullMode),
             "incoming" => Some(Self::Incoming),
             "outgoing" => Some(Self::Outgoing),
             "update_selected" => Some(Self::UpdateSelected),
             "unshelve_selected" => Some(Self::UnshelveSelected),
+            "shelve_delete" => Some(Self::ShelveDelete),
+            "toggle_shelve_keep" => Some(Self::ToggleShelveKeep),
             "resolve_mark" => Some(Self::ResolveMark),
             "resolve_unmark" => Some(Self::ResolveUnmark),
             "rebase_selected" => Some(Self::RebaseSelected),
             "rebase_continue" => Some(Self::RebaseContinue),
             "rebase_abort" => Some(Self::RebaseAbort),
             "histedit_selected" => Some(Self::HisteditSelected),
+            "histedit_continue" => Some(Self::HisteditContinue),
+            "histedit_abort" => Some(Self::HisteditAbort),
+            "backout_selected" => Some(Self::BackoutSelected),
+            "phase_secret" => Some(Self::PhaseSecret),
+            "phase_draft" => Some(Self::PhaseDraft),
+            "phase_public" => Some(Self::PhasePublic),
+            "evolve_prev" => Some(Self::EvolvePrev),
+            "evolve_next" => Some(Self::EvolveNext),
+            "evolve" => Some(Self::Evolve),
+            "evolve_all" => Some(Self::EvolveAll),
+            "prune_selected" => Some(Self::PruneSelected),
+            "obslog_selected" => Some(Self::ObslogSelected),
+            "topic_create" => Some(Self::TopicCreate),
+            "topic_switch" => Some(Self::TopicSwitch),
+            "topic_clear" => Some(Self::TopicClear),
+            "topics_list" => Some(Self::TopicsList),
+            "toggle_log_failures" => Some(Self::ToggleLogFailures),
+            "export_log" => Some(Self::ExportLog),
+            "cancel_job" => Some(Self::CancelJob),
+            "undo" => Some(Self::Undo),
+            "show_backups" => Some(Self::ShowBackups),
+            "restore_backup" => Some(Self::RestoreBackup),
+            "dry_run_next" => Some(Self::DryRunNext),
             "hard_refresh" => Some(Self::HardRefresh),
             _ => None,
         }
@@ -120,22 +248,227 @@ impl ActionId {
             Self::Commit,
             Self::CommitInteractive,
             Self::Bookmark,
+            Self::BookmarkDelete,
+            Self::BookmarkRename,
+            Self::BookmarkMove,
+            Self::BookmarkDeactivate,
+            Self::BranchCreate,
+            Self::ToggleCloseBranch,
+            Self::JumpToRevision,
+            Self::TagCreate,
+            Self::TagCreateLocal,
+            Self::TagRemove,
             Self::Shelve,
             Self::Push,
+            Self::PushRevision,
+            Self::PushBookmark,
+            Self::PushBranch,
             Self::Pull,
+            Self::PullRevision,
+            Self::PullBookmark,
+            Self::CyclePullMode,
             Self::Incoming,
             Self::Outgoing,
             Self::UpdateSelected,
             Self::UnshelveSelected,
+            Self::ShelveDelete,
+            Self::ToggleShelveKeep,
             Self::ResolveMark,
             Self::ResolveUnmark,
             Self::RebaseSelected,
             Self::RebaseContinue,
             Self::RebaseAbort,
             Self::HisteditSelected,
+            Self::HisteditContinue,
+            Self::HisteditAbort,
+            Self::BackoutSelected,
+            Self::PhaseSecret,
+            Self::PhaseDraft,
+            Self::PhasePublic,
+            Self::EvolvePrev,
+            Self::EvolveNext,
+            Self::Evolve,
+            Self::EvolveAll,
+            Self::PruneSelected,
+            Self::ObslogSelected,
+            Self::TopicCreate,
+            Self::TopicSwitch,
+            Self::TopicClear,
+            Self::TopicsList,
+            Self::ToggleLogFailures,
+            Self::ExportLog,
+            Self::CancelJob,
+            Self::Undo,
+            Self::ShowBackups,
+            Self::RestoreBackup,
+            Self::DryRunNext,
             Self::HardRefresh,
         ]
     }
+
+    // The action whose `[confirmations]` entry governs running `action`.
+    // Actions easyHg issues on its own (lock breaking, safety bundles) have none.
+    pub fn for_hg_action(action: &HgAction) -> Option<Self> {
+        let id = match action {
+            HgAction::Commit { .. } => Self::Commit,
+            HgAction::Pull { scope, .. } => match scope {
+                SyncScope::Rev(_) => Self::PullRevision,
+                SyncScope::Bookmark(_) => Self::PullBookmark,
+                SyncScope::All | SyncScope::Branch(_) => Self::Pull,
+            },
+            HgAction::Push { scope, .. } => match scope {
+                SyncScope::All => Self::Push,
+                SyncScope::Rev(_) => Self::PushRevision,
+                SyncScope::Bookmark(_) => Self::PushBookmark,
+                SyncScope::Branch(_) => Self::PushBranch,
+            },
+            HgAction::BookmarkCreate { .. } => Self::Bookmark,
+            HgAction::BookmarkDelete { .. } => Self::BookmarkDelete,
+            HgAction::BookmarkRename { .. } => Self::BookmarkRename,
+            HgAction::BookmarkMove { .. } => Self::BookmarkMove,
+            HgAction::BookmarkDeactivate => Self::BookmarkDeactivate,
+            HgAction::UpdateToRevision { .. }
+            | HgAction::UpdateToBookmark { .. }
+            | HgAction::UpdateToBranch { .. } => Self::UpdateSelected,
+            HgAction::BranchCreate { .. } => Self::BranchCreate,
+            HgAction::TagCreate { local: false, .. } => Self::TagCreate,
+            HgAction::TagCreate { local: true, .. } => Self::TagCreateLocal,
+            HgAction::TagRemove { .. } => Self::TagRemove,
+            HgAction::ShelveCreate { .. } => Self::Shelve,
+            HgAction::Unshelve { .. } => Self::UnshelveSelected,
+            HgAction::ShelveDelete { .. } => Self::ShelveDelete,
+            HgAction::ResolveMark { .. } => Self::ResolveMark,
+            HgAction::ResolveUnmark { .. } => Self::ResolveUnmark,
+            HgAction::RebaseSourceDest { .. } => Self::RebaseSelected,
+            HgAction::RebaseContinue => Self::RebaseContinue,
+            HgAction::RebaseAbort => Self::RebaseAbort,
+            HgAction::HisteditContinue => Self::HisteditContinue,
+            HgAction::HisteditAbort => Self::HisteditAbort,
+            HgAction::HisteditPlan { .. } => Self::HisteditSelected,
+            HgAction::Backout { .. } => Self::BackoutSelected,
+            HgAction::Prev => Self::EvolvePrev,
+            HgAction::Next => Self::EvolveNext,
+            HgAction::Evolve { all: false } => Self::Evolve,
+            HgAction::Evolve { all: true } => Self::EvolveAll,
+            HgAction::Prune { .. } => Self::PruneSelected,
+            HgAction::SetPhase { phase, .. } => match phase {
+                Phase::Secret => Self::PhaseSecret,
+                Phase::Draft => Self::PhaseDraft,
+                Phase::Public => Self::PhasePublic,
+            },
+            HgAction::TopicSet { .. } => Self::TopicCreate,
+            HgAction::UpdateToTopic { .. } => Self::TopicSwitch,
+            HgAction::TopicClear => Self::TopicClear,
+            HgAction::Undo { .. } => Self::Undo,
+            HgAction::Unbundle { .. } => Self::RestoreBackup,
+            HgAction::Outgoing { .. }
+            | HgAction::BreakLock { .. }
+            | HgAction::BackupBundle { .. }
+            | HgAction::DryRun { .. } => {
+                return None;
+            }
+        };
+        Some(id)
+    }
+
+    // Whether `for_hg_action` can return this action, i.e. whether a
+    // `[confirmations]` entry for it has any effect.
+    pub fn runs_repo_command(self) -> bool {
+        !matches!(
+            self,
+            Self::Quit
+                | Self::Help
+                | Self::FocusNext
+                | Self::FocusPrev
+                | Self::MoveDown
+                | Self::MoveUp
+                | Self::RefreshSnapshot
+                | Self::RefreshDetails
+                | Self::OpenCustomCommands
+                | Self::ToggleFileForCommit
+                | Self::ClearFileSelection
+                | Self::CommitInteractive
+                | Self::ToggleCloseBranch
+                | Self::JumpToRevision
+                | Self::CyclePullMode
+                | Self::Incoming
+                | Self::Outgoing
+                | Self::ToggleShelveKeep
+                | Self::ObslogSelected
+                | Self::TopicsList
+                | Self::ToggleLogFailures
+                | Self::ExportLog
+                | Self::CancelJob
+                | Self::ShowBackups
+                | Self::DryRunNext
+                | Self::HardRefresh
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationPolicy {
+    Never,
+    Always,
+    Typed,
+}
+
+impl ConfirmationPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "never" => Some(Self::Never),
+            "always" => Some(Self::Always),
+            "typed" => Some(Self::Typed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Never => "never",
+            Self::Always => "always",
+            Self::Typed => "typed",
+        }
+    }
+}
+
+// Valid `[confirmations]` entries; invalid ones are reported by
+// `validate_confirmation_overrides` and otherwise ignored.
+pub fn confirmation_policies(
+    overrides: &HashMap<String, String>,
+) -> HashMap<ActionId, ConfirmationPolicy> {
+    overrides
+        .iter()
+        .filter_map(|(name, policy)| {
+            let action = ActionId::from_str(name.trim()).filter(|id| id.runs_repo_command())?;
+            Some((action, ConfirmationPolicy::from_name(policy.trim())?))
+        })
+        .collect()
+}
+
+pub fn validate_confirmation_overrides(overrides: &HashMap<String, String>) -> Vec<String> {
+    let mut entries = overrides.iter().collect::<Vec<_>>();
+    entries.sort();
+    let mut issues = Vec::new();
+    for (name, policy) in entries {
+        match ActionId::from_str(name.trim()) {
+            None => issues.push(format!("unknown confirmations action '{}'", name.trim())),
+            Some(action) if !action.runs_repo_command() => issues.push(format!(
+                "confirmations.{}: action does not run a repository command",
+                action.as_str()
+            )),
+            Some(_) => {}
+        }
+        if ConfirmationPolicy::from_name(policy.trim()).is_none() {
+            issues.push(format!(
+                "invalid confirmation policy '{}' for {} (expected: {})",
+                policy.trim(),
+                name.trim(),
+                CONFIRMATION_POLICIES.join(", ")
+            ));
+        }
+    }
+    issues
 }
 
 pub const DEFAULT_BINDINGS: &[(ActionId, &str)] = &[
@@ -156,19 +489,60 @@ pub const DEFAULT_BINDINGS: &[(ActionId, &str)] = &[
     (ActionId::Commit, "c"),
     (ActionId::CommitInteractive, "I"),
     (ActionId::Bookmark, "b"),
+    (ActionId::BookmarkDelete, "x"),
+    (ActionId::BookmarkRename, "R"),
+    (ActionId::BookmarkMove, "F"),
+    (ActionId::BookmarkDeactivate, "ctrl+b"),
+    (ActionId::BranchCreate, "n"),
+    (ActionId::ToggleCloseBranch, "N"),
+    (ActionId::JumpToRevision, "g"),
+    (ActionId::TagCreate, "y"),
+    (ActionId::TagCreateLocal, "Y"),
+    (ActionId::TagRemove, "ctrl+y"),
     (ActionId::Shelve, "s"),
     (ActionId::Push, "p"),
+    (ActionId::PushRevision, "alt+r"),
+    (ActionId::PushBookmark, "alt+m"),
+    (ActionId::PushBranch, "alt+b"),
     (ActionId::Pull, "P"),
+    (ActionId::PullRevision, "alt+g"),
+    (ActionId::PullBookmark, "alt+k"),
+    (ActionId::CyclePullMode, "alt+u"),
     (ActionId::Incoming, "i"),
     (ActionId::Outgoing, "o"),
     (ActionId::UpdateSelected, "u"),
     (ActionId::UnshelveSelected, "U"),
+    (ActionId::ShelveDelete, "alt+x"),
+    (ActionId::ToggleShelveKeep, "K"),
     (ActionId::ResolveMark, "m"),
     (ActionId::ResolveUnmark, "M"),
     (ActionId::RebaseSelected, "r"),
     (ActionId::RebaseContinue, "C"),
     (ActionId::RebaseAbort, "A"),
     (ActionId::HisteditSelected, "H"),
+    (ActionId::HisteditContinue, "alt+c"),
+    (ActionId::HisteditAbort, "alt+a"),
+    (ActionId::BackoutSelected, "B"),
+    (ActionId::PhaseSecret, "S"),
+    (ActionId::PhaseDraft, "D"),
+    (ActionId::PhasePublic, "ctrl+p"),
+    (ActionId::EvolvePrev, "["),
+    (ActionId::EvolveNext, "]"),
+    (ActionId::Evolve, "e"),
+    (ActionId::EvolveAll, "E"),
+    (ActionId::PruneSelected, "X"),
+    (ActionId::ObslogSelected, "O"),
+    (ActionId::TopicCreate, "t"),
+    (ActionId::TopicSwitch, "T"),
+    (ActionId::TopicClear, "ctrl+t"),
+    (ActionId::TopicsList, "L"),
+    (ActionId::ToggleLogFailures, "alt+f"),
+    (ActionId::ExportLog, "alt+e"),
+    (ActionId::CancelJob, "ctrl+x"),
+    (ActionId::Undo, "ctrl+z"),
+    (ActionId::ShowBackups, "alt+z"),
+    (ActionId::RestoreBackup, "alt+Z"),
+    (ActionId::DryRunNext, "alt+d"),
     (ActionId::HardRefresh, "ctrl+l"),
 ];
 
@@ -430,4 +804,169 @@ mod tests {
         assert_eq!(map.key_for_action(ActionId::RebaseContinue), Some("C"));
         assert_eq!(map.key_for_action(ActionId::RebaseAbort), Some("A"));
     }
+
+    #[test]
+    fn hg_actions_map_to_confirmable_action_ids() {
+        let push = HgAction::Push {
+            dest: None,
+            scope: SyncScope::Bookmark("main".to_string()),
+            new_branch: false,
+        };
+        assert_eq!(ActionId::for_hg_action(&push), Some(ActionId::PushBookmark));
+        let phase = HgAction::SetPhase {
+            rev: 3,
+            phase: Phase::Public,
+            force: false,
+        };
+        assert_eq!(ActionId::for_hg_action(&phase), Some(ActionId::PhasePublic));
+        assert_eq!(
+            ActionId::for_hg_action(&HgAction::HisteditAbort),
+            Some(ActionId::HisteditAbort)
+        );
+        assert_eq!(
+            ActionId::for_hg_action(&HgAction::BreakLock { wlock: true }),
+            None
+        );
+        for id in [
+            ActionId::PushBookmark,
+            ActionId::PhasePublic,
+            ActionId::RebaseAbort,
+        ] {
+            assert!(id.runs_repo_command());
+        }
+        assert!(!ActionId::MoveDown.runs_repo_command());
+
+        let policies = confirmation_policies(&HashMap::from([
+            ("push".to_string(), " typed ".to_string()),
+            ("help".to_string(), "always".to_string()),
+            ("prune_selected".to_string(), "maybe".to_string()),
+        ]));
+        assert_eq!(
+            policies,
+            HashMap::from([(ActionId::Push, ConfirmationPolicy::Typed)])
+        );
+    }
+
+    #[test]
+    fn runs_repo_command_matches_the_hg_action_mapping() {
+        let name = || "x".to_string();
+        let scopes = [
+            SyncScope::All,
+            SyncScope::Rev("1".to_string()),
+            SyncScope::Bookmark(name()),
+            SyncScope::Branch(name()),
+        ];
+        let mut actions = vec![
+            HgAction::Commit {
+                message: name(),
+                files: Vec::new(),
+                close_branch: false,
+            },
+            HgAction::BookmarkCreate { name: name() },
+            HgAction::BookmarkDelete { name: name() },
+            HgAction::BookmarkRename {
+                old: name(),
+                new: name(),
+            },
+            HgAction::BookmarkMove {
+                name: name(),
+                rev: 1,
+            },
+            HgAction::BookmarkDeactivate,
+            HgAction::UpdateToRevision { rev: 1 },
+            HgAction::UpdateToBookmark { name: name() },
+            HgAction::UpdateToBranch { name: name() },
+            HgAction::BranchCreate { name: name() },
+            HgAction::TagRemove {
+                name: name(),
+                local: false,
+            },
+            HgAction::ShelveCreate {
+                name: name(),
+                files: Vec::new(),
+                keep: false,
+            },
+            HgAction::Unshelve {
+                name: name(),
+                keep: false,
+            },
+            HgAction::ShelveDelete { name: name() },
+            HgAction::ResolveMark {
+                path: "a.txt".into(),
+            },
+            HgAction::ResolveUnmark {
+                path: "a.txt".into(),
+            },
+            HgAction::RebaseSourceDest {
+                source_rev: 1,
+                dest_rev: 2,
+            },
+            HgAction::RebaseContinue,
+            HgAction::RebaseAbort,
+            HgAction::HisteditPlan {
+                base_rev: 1,
+                steps: Vec::new(),
+            },
+            HgAction::HisteditContinue,
+            HgAction::HisteditAbort,
+            HgAction::Backout {
+                rev: 1,
+                message: name(),
+                merge: false,
+            },
+            HgAction::Prev,
+            HgAction::Next,
+            HgAction::Prune { rev: 1 },
+            HgAction::TopicSet { name: name() },
+            HgAction::TopicClear,
+            HgAction::UpdateToTopic { name: name() },
+            HgAction::Undo { steps: Vec::new() },
+            HgAction::Unbundle {
+                file: "b.hg".into(),
+            },
+            HgAction::BreakLock { wlock: true },
+        ];
+        for scope in scopes {
+            actions.push(HgAction::Pull {
+                source: None,
+                mode: crate::hg::PullMode::Update,
+                scope: scope.clone(),
+            });
+            actions.push(HgAction::Push {
+                dest: None,
+                scope,
+                new_branch: false,
+            });
+        }
+        for local in [false, true] {
+            actions.push(HgAction::TagCreate {
+                name: name(),
+                rev: 1,
+                local,
+            });
+        }
+        for all in [false, true] {
+            actions.push(HgAction::Evolve { all });
+        }
+        for phase in [Phase::Secret, Phase::Draft, Phase::Public] {
+            actions.push(HgAction::SetPhase {
+                rev: 1,
+                phase,
+                force: false,
+            });
+        }
+
+        let mapped = actions
+            .iter()
+            .filter_map(ActionId::for_hg_action)
+            .collect::<HashSet<_>>();
+        for id in ActionId::all() {
+            assert_eq!(
+                id.runs_repo_command(),
+                mapped.contains(id),
+                "runs_repo_command disagrees with for_hg_action for {}",
+                id.as_str()
+            );
+        }
+    }
 }
@@ -1,10 +1,11 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{DateTime, Local};
 use crossterm::event::{
     DisableMouseCapture, EnableMouseCapture, Event as CEvent, EventStream, KeyCode, KeyEvent,
     KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
@@ -16,14 +17,21 @@ use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
 use tokio::sync::mpsc;
 
-use crate::actions::{ActionId, ActionKeyMap};
+use crate::actions::{self, ActionId, ActionKeyMap, ConfirmationPolicy};
+use crate::backups::{self, BackupEntry, SafetyBackup};
 use crate::config::{AppConfig, CommandContext, CustomCommand};
 use crate::custom_commands::{parse_command_parts, render_template, unresolved_template_vars};
-use crate::domain::{RepoSnapshot, Revision};
+use crate::domain::{HgPath, HisteditAction, Phase, RepoPath, RepoSnapshot, Revision};
 use crate::hg::{
-    CliHgClient, CommandResult, CustomInvocation, HgAction, HgClient, SnapshotOptions,
+    CliHgClient, CommandClass, CommandResult, CustomInvocation, HgAction, HgClient,
+    HisteditPlanStep, LockWait, OutputChunk, OutputSink, PullMode, SnapshotOptions, SyncScope,
+    parse_lock_wait, remote_command, summarize_pull_output, write_histedit_plan_file,
 };
+use crate::jobs::{Job, JobKind, JobManager};
+use crate::protection::{ProtectionHit, ProtectionMode, ProtectionPolicy};
+use crate::remotes::{RemoteDefaultsStore, RemoteDirection, default_remote_defaults_path};
 use crate::ui;
+use crate::undo::{RepoState, UNDO_STACK_LIMIT, UndoCapture, UndoEntry, UndoKind};
 
 const LOG_LIMIT: usize = 200;
 const MAX_LOG_LINES: usize = 300;
@@ -34,19 +42,25 @@ pub enum FocusPanel {
     Files,
     Revisions,
     Bookmarks,
+    Branches,
+    Tags,
     Shelves,
     Conflicts,
+    Remote,
     Log,
 }
 
 impl FocusPanel {
-    pub fn all() -> [Self; 6] {
+    pub fn all() -> [Self; 9] {
         [
             Self::Files,
             Self::Revisions,
             Self::Bookmarks,
+            Self::Branches,
+            Self::Tags,
             Self::Shelves,
             Self::Conflicts,
+            Self::Remote,
             Self::Log,
         ]
     }
@@ -57,7 +71,18 @@ pub enum InputPurpose {
     CommitMessage,
     CommitMessageInteractive,
     BookmarkName,
+    BranchName,
+    ExportLogPath,
+    // Re-asked when the file exists; submitting `path` unchanged overwrites it.
+    ExportLogOverwrite { path: String },
+    PullRevision,
+    PullBookmark,
+    TagName { rev: i64, local: bool },
+    BookmarkRename { old: String },
     ShelveName,
+    TopicName,
+    TopicSwitchName,
+    BackoutMessage { rev: i64, merge: bool },
 }
 
 #[derive(Debug, Clone)]
@@ -71,6 +96,16 @@ pub struct InputState {
 pub struct PendingConfirmation {
     pub message: String,
     pub action: PendingRunAction,
+    pub follow_up: Option<String>,
+    pub typed: Option<TypedConfirmation>,
+}
+
+// Confirmation that only accepts the exact protected name (or action id for
+// `typed` confirmation policies), not y/Enter.
+#[derive(Debug, Clone)]
+pub struct TypedConfirmation {
+    pub expected: String,
+    pub value: String,
 }
 
 #[derive(Debug, Clone)]
@@ -78,16 +113,168 @@ pub struct CommandPaletteState {
     pub selected: usize,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub text: String,
+    pub record: Option<CommandRecord>,
+}
+
+impl LogEntry {
+    pub fn is_failure(&self) -> bool {
+        self.record.as_ref().is_some_and(|record| !record.success)
+    }
+
+    fn render(&self) -> String {
+        match &self.record {
+            Some(record) => format!("{}\n\n{}", self.text, record.render()),
+            None => self.text.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandRecord {
+    pub command: String,
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandRecord {
+    fn from_result(out: &CommandResult, started_at: DateTime<Local>) -> Self {
+        Self {
+            command: out.command_preview.clone(),
+            started_at,
+            finished_at: Local::now(),
+            exit_code: out.exit_code,
+            success: out.success,
+            stdout: out.stdout.clone(),
+            stderr: out.stderr.clone(),
+        }
+    }
+
+    fn render(&self) -> String {
+        let elapsed = (self.finished_at - self.started_at).num_milliseconds();
+        let status = match (self.exit_code, self.success) {
+            (Some(code), true) => format!("{code} (ok)"),
+            (Some(code), false) => format!("{code} (failed)"),
+            (None, _) => "none (did not run to completion)".to_string(),
+        };
+        format!(
+            "Command: {}\nStarted: {}\nFinished: {} ({:.1}s)\nExit status: {status}\n\nstdout:\n{}\n\nstderr:\n{}",
+            self.command,
+            self.started_at.format("%Y-%m-%d %H:%M:%S%.3f"),
+            self.finished_at.format("%Y-%m-%d %H:%M:%S%.3f"),
+            elapsed as f64 / 1000.0,
+            self.stdout.trim_end(),
+            self.stderr.trim_end(),
+        )
+    }
+}
+
+// Asked before killing a running write, which can stop hg mid-transaction.
+#[derive(Debug, Clone)]
+pub struct CancelJobPrompt {
+    pub job_id: u64,
+    pub label: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LockWaitPrompt {
+    pub job_id: u64,
+    pub label: String,
+    pub wait: LockWait,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupPickerState {
+    pub entries: Vec<BackupEntry>,
+    pub selected: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct PathPickerState {
+    pub direction: RemoteDirection,
+    pub scope: SyncScope,
+    pub selected: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct HisteditPlanState {
+    pub base_rev: i64,
+    pub rows: Vec<HisteditPlanStep>,
+    pub selected: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct InteractiveCommitRequest {
     pub message: String,
-    pub files: Vec<String>,
+    pub files: Vec<RepoPath>,
+    pub close_branch: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct InteractiveHisteditRequest {
+    pub base_rev: i64,
+    pub steps: Vec<HisteditPlanStep>,
+    // Written by the run loop before hg starts; histedit does not run if it fails.
+    pub backup: Option<SafetyBackup>,
+}
+
+// Runs in the suspended terminal (hg's prompts or the editor) instead of as a
+// background job.
+#[derive(Debug, Clone)]
+pub enum InteractiveRun {
+    Commit(InteractiveCommitRequest),
+    Histedit(InteractiveHisteditRequest),
+}
+
+impl InteractiveRun {
+    // The job-run action with the same effect on the repository.
+    fn equivalent_action(&self) -> HgAction {
+        match self {
+            Self::Commit(request) => HgAction::Commit {
+                message: request.message.clone(),
+                files: request.files.clone(),
+                close_branch: request.close_branch,
+            },
+            Self::Histedit(request) => HgAction::HisteditPlan {
+                base_rev: request.base_rev,
+                steps: request.steps.clone(),
+            },
+        }
+    }
+
+    fn command_preview(&self) -> String {
+        match self {
+            Self::Commit(request) => {
+                let close = if request.close_branch {
+                    " --close-branch"
+                } else {
+                    ""
+                };
+                if request.files.is_empty() {
+                    format!("hg commit -i{close} -m <message>")
+                } else {
+                    format!(
+                        "hg commit -i{close} -m <message> <{} files>",
+                        request.files.len()
+                    )
+                }
+            }
+            Self::Histedit(_) => self.equivalent_action().command_preview(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum PendingRunAction {
     Hg(HgAction),
     Custom(CustomRunAction),
+    Interactive(InteractiveRun),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -97,6 +284,14 @@ pub enum ActionOutcomeKind {
     RebaseAbort,
     ResolveMark,
     ResolveUnmark,
+    HisteditStart,
+    HisteditContinue,
+    HisteditAbort,
+    Backout,
+    Push,
+    Pull,
+    Undo,
+    DryRun,
     Other,
 }
 
@@ -105,13 +300,25 @@ impl PendingRunAction {
         match self {
             Self::Hg(action) => action.command_preview(),
             Self::Custom(action) => action.invocation.command_preview(),
+            Self::Interactive(run) => run.command_preview(),
         }
     }
 
     fn show_output(&self) -> bool {
         match self {
-            Self::Hg(_) => false,
+            Self::Hg(action) => matches!(action, HgAction::DryRun { .. }),
             Self::Custom(action) => action.show_output,
+            Self::Interactive(_) => false,
+        }
+    }
+
+    fn job_kind(&self) -> JobKind {
+        match self {
+            Self::Hg(action) if action.is_read_only() => JobKind::Read,
+            Self::Hg(action) if action.command_class() == CommandClass::Network => JobKind::Network,
+            // Breaking a lock must not queue behind the write that is stuck on it.
+            Self::Hg(HgAction::BreakLock { .. }) => JobKind::Read,
+            _ => JobKind::Write,
         }
     }
 
@@ -126,6 +333,14 @@ impl PendingRunAction {
             Self::Hg(HgAction::RebaseAbort) => ActionOutcomeKind::RebaseAbort,
             Self::Hg(HgAction::ResolveMark { .. }) => ActionOutcomeKind::ResolveMark,
             Self::Hg(HgAction::ResolveUnmark { .. }) => ActionOutcomeKind::ResolveUnmark,
+            Self::Hg(HgAction::HisteditPlan { .. }) => ActionOutcomeKind::HisteditStart,
+            Self::Hg(HgAction::HisteditContinue) => ActionOutcomeKind::HisteditContinue,
+            Self::Hg(HgAction::HisteditAbort) => ActionOutcomeKind::HisteditAbort,
+            Self::Hg(HgAction::Backout { .. }) => ActionOutcomeKind::Backout,
+            Self::Hg(HgAction::Push { .. }) => ActionOutcomeKind::Push,
+            Self::Hg(HgAction::Pull { .. }) => ActionOutcomeKind::Pull,
+            Self::Hg(HgAction::Undo { .. }) => ActionOutcomeKind::Undo,
+            Self::Hg(HgAction::DryRun { .. }) => ActionOutcomeKind::DryRun,
             _ => ActionOutcomeKind::Other,
         }
     }
@@ -143,19 +358,53 @@ pub enum AppEvent {
     SnapshotLoaded {
         preserve_details: bool,
         include_revisions: bool,
-        result: Result<RepoSnapshot, String>,
+        result: Result<Box<RepoSnapshot>, String>,
+    },
+    RemotePatchLoaded {
+        request_id: u64,
+        node: String,
+        result: Result<String, String>,
     },
     DetailLoaded {
         request_id: u64,
         result: Result<String, String>,
     },
+    HisteditPlanLoaded {
+        base_rev: i64,
+        result: Result<Vec<Revision>, String>,
+    },
+    RemoteChangesLoaded {
+        direction: RemoteDirection,
+        path: Option<String>,
+        result: Result<Vec<Revision>, String>,
+    },
+    PushPreviewLoaded {
+        push: HgAction,
+        target: String,
+        result: Result<CommandResult, String>,
+        // Changesets an unscoped push would send; None when not listed.
+        outgoing: Option<Vec<Revision>>,
+    },
     ActionFinished {
         action_kind: ActionOutcomeKind,
         action_preview: String,
         show_output: bool,
         clear_commit_selection: bool,
+        started_at: DateTime<Local>,
+        job_id: u64,
+        undo: Option<UndoCapture>,
         result: Result<CommandResult, String>,
     },
+    JobStarted {
+        id: u64,
+    },
+    CommandOutput {
+        job_id: u64,
+        chunk: OutputChunk,
+    },
+    JobFinished {
+        id: u64,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -168,8 +417,13 @@ struct LastMouseClick {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum DetailTarget {
-    File(String),
+    File(RepoPath),
     Revision(i64),
+    RemoteChangeset(String),
+    Shelf(String),
+    Bookmark(i64),
+    Conflict(RepoPath),
+    LogEntry(usize),
     None,
 }
 
@@ -179,25 +433,55 @@ pub struct App {
     pub snapshot: RepoSnapshot,
     pub detail_text: String,
     pub details_scroll: usize,
-    pub log_lines: Vec<String>,
+    pub log_entries: Vec<LogEntry>,
+    pub log_failures_only: bool,
     pub status_line: String,
     pub input: Option<InputState>,
     pub confirmation: Option<PendingConfirmation>,
     pub command_palette: Option<CommandPaletteState>,
-    pub commit_file_selection: BTreeSet<String>,
+    pub histedit_plan: Option<HisteditPlanState>,
+    pub path_picker: Option<PathPickerState>,
+    pub backup_picker: Option<BackupPickerState>,
+    pub lock_wait: Option<LockWaitPrompt>,
+    pub cancel_prompt: Option<CancelJobPrompt>,
+    pub remote_defaults: RemoteDefaultsStore,
+    pub incoming_changes: Option<Vec<Revision>>,
+    pub outgoing_changes: Option<Vec<Revision>>,
+    // Patches of remote changesets by node, so moving the cursor back does
+    // not contact the remote again.
+    remote_patches: HashMap<String, String>,
+    pub remote_view: RemoteDirection,
+    remote_view_path: Option<String>,
+    last_push: Option<HgAction>,
+    pub jobs: JobManager,
+    pub undo_stack: Vec<UndoEntry>,
+    // Keyed by job id so captures finishing before the next refresh are all kept.
+    pending_undo: BTreeMap<u64, UndoCapture>,
+    pub commit_file_selection: BTreeSet<RepoPath>,
     pub interactive_commit_request: Option<InteractiveCommitRequest>,
+    pub close_branch_on_commit: bool,
+    pub dry_run_next: bool,
+    pub shelve_keep: bool,
+    pub pull_mode: PullMode,
+    pub interactive_histedit_request: Option<InteractiveHisteditRequest>,
     pub should_quit: bool,
     pub files_idx: usize,
     pub rev_idx: usize,
     pub bookmarks_idx: usize,
+    pub branches_idx: usize,
+    pub tags_idx: usize,
     pub shelves_idx: usize,
     pub conflicts_idx: usize,
+    pub remote_idx: usize,
     pub log_idx: usize,
     pub files_offset: usize,
     pub rev_offset: usize,
     pub bookmarks_offset: usize,
+    pub branches_offset: usize,
+    pub tags_offset: usize,
     pub shelves_offset: usize,
     pub conflicts_offset: usize,
+    pub remote_offset: usize,
     pub ui_rects: ui::UiRects,
     last_refresh: Instant,
     detail_request_id: u64,
@@ -210,6 +494,8 @@ pub struct App {
     event_rx: mpsc::UnboundedReceiver<AppEvent>,
     hg: Arc<dyn HgClient>,
     keymap: ActionKeyMap,
+    protection: ProtectionPolicy,
+    confirmations: HashMap<ActionId, ConfirmationPolicy>,
 }
 
 impl App {
@@ -230,11 +516,17 @@ impl App {
             Ok(map) => map,
             Err(issues) => {
                 keymap_issues = issues;
-                ActionKeyMap::from_overrides(&std::collections::HashMap::new())
-                    .expect("default keymap builds")
+                ActionKeyMap::from_overrides(&HashMap::new()).expect("default keymap builds")
             }
         };
-        let hg = Arc::new(CliHgClient::new(cwd)) as Arc<dyn HgClient>;
+        let pull_mode = PullMode::from_name(config.pull_mode.trim()).unwrap_or(PullMode::Update);
+        let protection = ProtectionPolicy::from_config(&config.protected);
+        let confirmations = actions::confirmation_policies(&config.confirmations);
+        let hg = Arc::new(
+            CliHgClient::new(cwd)
+                .with_timeouts(config.timeouts.command_timeouts())
+                .with_plain_except(config.hgplain_except.clone()),
+        ) as Arc<dyn HgClient>;
         let (event_tx, event_rx) = mpsc::unbounded_channel();
 
         let mut app = Self {
@@ -243,25 +535,52 @@ impl App {
             snapshot: RepoSnapshot::default(),
             detail_text: "Loading…".to_string(),
             details_scroll: 0,
-            log_lines: Vec::new(),
+            log_entries: Vec::new(),
+            log_failures_only: false,
             status_line,
             input: None,
             confirmation: None,
             command_palette: None,
+            histedit_plan: None,
+            path_picker: None,
+            backup_picker: None,
+            lock_wait: None,
+            cancel_prompt: None,
+            remote_defaults: RemoteDefaultsStore::default(),
+            incoming_changes: None,
+            outgoing_changes: None,
+            remote_patches: HashMap::new(),
+            remote_view: RemoteDirection::Pull,
+            remote_view_path: None,
+            last_push: None,
+            jobs: JobManager::default(),
+            undo_stack: Vec::new(),
+            pending_undo: BTreeMap::new(),
             commit_file_selection: BTreeSet::new(),
             interactive_commit_request: None,
+            close_branch_on_commit: false,
+            dry_run_next: false,
+            shelve_keep: false,
+            pull_mode,
+            interactive_histedit_request: None,
             should_quit: false,
             files_idx: 0,
             rev_idx: 0,
             bookmarks_idx: 0,
+            branches_idx: 0,
+            tags_idx: 0,
             shelves_idx: 0,
             conflicts_idx: 0,
+            remote_idx: 0,
             log_idx: 0,
             files_offset: 0,
             rev_offset: 0,
             bookmarks_offset: 0,
+            branches_offset: 0,
+            tags_offset: 0,
             shelves_offset: 0,
             conflicts_offset: 0,
+            remote_offset: 0,
             ui_rects: ui::UiRects::default(),
             last_refresh: Instant::now() - Duration::from_secs(10),
             detail_request_id: 0,
@@ -274,6 +593,8 @@ impl App {
             event_rx,
             hg,
             keymap,
+            protection,
+            confirmations,
         };
 
         for issue in startup_issues {
@@ -345,6 +666,18 @@ impl App {
                     self.refresh_snapshot(false);
                 }
             }
+            if let Some(request) = self.interactive_histedit_request.take()
+                && self.write_foreground_backup(request.backup.as_ref()).await
+            {
+                if let Err(err) = self.run_interactive_histedit(&mut terminal, request) {
+                    self.status_line = "Histedit failed.".to_string();
+                    self.append_log(format!("Histedit error: {err}"));
+                    self.set_detail_text(format!("Histedit error:\n{err}"));
+                    let _ = self.resume_terminal(&mut terminal);
+                } else {
+                    self.refresh_snapshot(false);
+                }
+            }
 
             if let Err(err) = terminal.draw(|f| {
                 let rects = ui::compute_ui_rects(f.area());
@@ -382,6 +715,29 @@ impl App {
         run_result
     }
 
+    // Same rule as for jobs: history is only rewritten once the bundle exists.
+    async fn write_foreground_backup(&mut self, backup: Option<&SafetyBackup>) -> bool {
+        let Some(backup) = backup else {
+            return true;
+        };
+        let failure = match backup.write(self.hg.as_ref()).await {
+            Ok(out) if out.success => return true,
+            Ok(out) => format!("{}\n{}", out.stdout.trim(), out.stderr.trim()),
+            Err(err) => err,
+        };
+        self.status_line = format!(
+            "Safety backup failed, so `{}` was not run.",
+            backup.entry.operation
+        );
+        self.append_log(format!("Safety backup failed: {}", failure.trim()));
+        self.set_detail_text(format!(
+            "Safety backup failed, so `{}` was not run:\n{}",
+            backup.entry.operation,
+            failure.trim()
+        ));
+        false
+    }
+
     fn suspend_terminal(
         &self,
         terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
@@ -419,11 +775,7 @@ impl App {
         request: InteractiveCommitRequest,
     ) -> Result<()> {
         self.suspend_terminal(terminal)?;
-        let preview = if request.files.is_empty() {
-            "hg commit -i -m <message>".to_string()
-        } else {
-            format!("hg commit -i -m <message> <{} files>", request.files.len())
-        };
+        let preview = InteractiveRun::Commit(request.clone()).command_preview();
         self.append_log(format!("Running interactively: {preview}"));
         println!();
         println!(
@@ -431,12 +783,12 @@ impl App {
             request.message
         );
         let mut command = std::process::Command::new("hg");
-        command
-            .arg("commit")
-            .arg("-i")
-            .arg("-m")
-            .arg(&request.message);
-        command.args(&request.files);
+        command.arg("commit").arg("-i");
+        if request.close_branch {
+            command.arg("--close-branch");
+        }
+        command.arg("-m").arg(&request.message);
+        command.args(request.files.iter().map(RepoPath::hg_pattern));
         command.stdin(std::process::Stdio::inherit());
         command.stdout(std::process::Stdio::inherit());
         command.stderr(std::process::Stdio::inherit());
@@ -449,6 +801,8 @@ impl App {
             self.status_line = "Interactive commit completed.".to_string();
             self.append_log("OK: hg commit -i");
             self.commit_file_selection.clear();
+            self.close_branch_on_commit = false;
+            self.clear_remote_changes();
         } else {
             self.status_line = "Interactive commit exited with error.".to_string();
             self.append_log(format!("FAILED: interactive commit exit status {status}"));
@@ -456,6 +810,54 @@ impl App {
         Ok(())
     }
 
+    fn run_interactive_histedit(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+        request: InteractiveHisteditRequest,
+    ) -> Result<()> {
+        let plan_path = write_histedit_plan_file(&request.steps)?;
+        self.suspend_terminal(terminal)?;
+        self.append_log(format!(
+            "Running interactively: {}",
+            HgAction::HisteditPlan {
+                base_rev: request.base_rev,
+                steps: request.steps.clone(),
+            }
+            .command_preview()
+        ));
+        println!();
+        println!(
+            "easyHg histedit started from revision {}. Edit messages in your editor to continue.",
+            request.base_rev
+        );
+        let status = std::process::Command::new("hg")
+            .arg("histedit")
+            .arg("--commands")
+            .arg(&plan_path)
+            .arg(request.base_rev.to_string())
+            .stdin(std::process::Stdio::inherit())
+            .stdout(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::inherit())
+            .status();
+        std::fs::remove_file(&plan_path).ok();
+        let status = status.context("failed to execute interactive mercurial histedit")?;
+
+        self.resume_terminal(terminal)?;
+        if status.success() {
+            self.status_line = "Histedit completed.".to_string();
+            self.append_log("OK: hg histedit --commands");
+            self.clear_remote_changes();
+        } else {
+            self.status_line = format!(
+                "Histedit stopped. Resolve conflicts, then press {} to continue or {} to abort.",
+                self.key_for_action(ActionId::HisteditContinue),
+                self.key_for_action(ActionId::HisteditAbort)
+            );
+            self.append_log(format!("FAILED: histedit exit status {status}"));
+        }
+        Ok(())
+    }
+
     fn restore_terminal(&self, mut terminal: Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
         disable_raw_mode().ok();
         terminal
@@ -490,6 +892,7 @@ impl App {
             let result = hg
                 .refresh_snapshot(options)
                 .await
+                .map(Box::new)
                 .map_err(|err| err.to_string());
             let _ = tx.send(AppEvent::SnapshotLoaded {
                 preserve_details,
@@ -530,12 +933,91 @@ impl App {
                     });
                 }
             }
+            FocusPanel::Bookmarks => {
+                if let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) {
+                    let name = bookmark.name.clone();
+                    let rev = bookmark.rev;
+                    tokio::spawn(async move {
+                        let revset = format!("ancestor({rev}, .)::{rev}");
+                        let (log, patch) =
+                            tokio::join!(hg.log_summary(&revset), hg.revision_patch(rev));
+                        let result = match (log, patch) {
+                            (Ok(log), Ok(patch)) => Ok(format!(
+                                "Bookmark '{name}' @ {rev}\n\nLog since common ancestor with . ({revset}):\n{}\n\n{patch}",
+                                log.trim_end()
+                            )),
+                            (Err(err), _) | (_, Err(err)) => Err(err.to_string()),
+                        };
+                        let _ = tx.send(AppEvent::DetailLoaded { request_id, result });
+                    });
+                } else {
+                    self.set_detail_text("No bookmarks.");
+                }
+            }
+            FocusPanel::Conflicts => {
+                let text = match self.snapshot.conflicts.get(self.conflicts_idx) {
+                    Some(conflict) => self.conflict_file_text(&conflict.path),
+                    None => "No merge conflicts.".to_string(),
+                };
+                self.set_detail_text(text);
+            }
+            FocusPanel::Log => {
+                let text = match self.visible_log_entries().get(self.log_idx) {
+                    Some(entry) => entry.render(),
+                    None if self.log_failures_only => "No failed commands logged.".to_string(),
+                    None => "Command log is empty.".to_string(),
+                };
+                self.set_detail_text(text);
+            }
+            FocusPanel::Shelves => {
+                if let Some(shelf) = self.snapshot.shelves.get(self.shelves_idx) {
+                    let name = shelf.name.clone();
+                    tokio::spawn(async move {
+                        let result = hg.shelve_patch(&name).await.map_err(|err| err.to_string());
+                        let _ = tx.send(AppEvent::DetailLoaded { request_id, result });
+                    });
+                } else {
+                    self.set_detail_text("No shelves.");
+                }
+            }
+            FocusPanel::Remote => {
+                if let Some(rev) = self.remote_revisions().get(self.remote_idx) {
+                    let node = rev.node.clone();
+                    if let Some(patch) = self.remote_patches.get(&node) {
+                        self.set_detail_text(patch.clone());
+                        return;
+                    }
+                    let direction = self.remote_view;
+                    let path = self.remote_view_path.clone();
+                    tokio::spawn(async move {
+                        let result = hg
+                            .remote_changeset_patch(direction, path.as_deref(), &node)
+                            .await
+                            .map_err(|err| err.to_string());
+                        let _ = tx.send(AppEvent::RemotePatchLoaded {
+                            request_id,
+                            node,
+                            result,
+                        });
+                    });
+                }
+            }
             _ => {
                 self.set_detail_text("Select a file or revision to view details.");
             }
         }
     }
 
+    fn conflict_file_text(&self, path: &RepoPath) -> String {
+        let Some(repo_root) = self.snapshot.repo_root.as_deref() else {
+            return format!("{path}\n\nRepository root unknown.");
+        };
+        match std::fs::read(std::path::Path::new(repo_root).join(path.to_path_buf())) {
+            Ok(bytes) => format!("{path}\n\n{}", String::from_utf8_lossy(&bytes)),
+            Err(err) => format!("{path}\n\nFailed reading file: {err}"),
+        }
+    }
+
     fn detail_target(&self) -> DetailTarget {
         match self.focus {
             FocusPanel::Files => self
@@ -550,6 +1032,32 @@ impl App {
                 .get(self.rev_idx)
                 .map(|rev| DetailTarget::Revision(rev.rev))
                 .unwrap_or(DetailTarget::None),
+            FocusPanel::Shelves => self
+                .snapshot
+                .shelves
+                .get(self.shelves_idx)
+                .map(|shelf| DetailTarget::Shelf(shelf.name.clone()))
+                .unwrap_or(DetailTarget::None),
+            FocusPanel::Bookmarks => self
+                .snapshot
+                .bookmarks
+                .get(self.bookmarks_idx)
+                .map(|bookmark| DetailTarget::Bookmark(bookmark.rev))
+                .unwrap_or(DetailTarget::None),
+            FocusPanel::Conflicts => self
+                .snapshot
+                .conflicts
+                .get(self.conflicts_idx)
+                .map(|conflict| DetailTarget::Conflict(conflict.path.clone()))
+                .unwrap_or(DetailTarget::None),
+            FocusPanel::Log if self.log_idx < self.visible_log_entries().len() => {
+                DetailTarget::LogEntry(self.log_idx)
+            }
+            FocusPanel::Remote => self
+                .remote_revisions()
+                .get(self.remote_idx)
+                .map(|rev| DetailTarget::RemoteChangeset(rev.node.clone()))
+                .unwrap_or(DetailTarget::None),
             _ => DetailTarget::None,
         }
     }
@@ -569,12 +1077,25 @@ impl App {
     }
 
     fn rebase_status_hint_from_snapshot(&self) -> Option<String> {
-        if !self.snapshot.capabilities.has_rebase || !self.snapshot.rebase.in_progress {
-            return None;
-        }
         let continue_key = self.key_for_action(ActionId::RebaseContinue);
         let abort_key = self.key_for_action(ActionId::RebaseAbort);
         let unresolved = self.snapshot.rebase.unresolved_conflicts;
+        if self.snapshot.histedit.in_progress {
+            let continue_key = self.key_for_action(ActionId::HisteditContinue);
+            let abort_key = self.key_for_action(ActionId::HisteditAbort);
+            return if unresolved > 0 {
+                Some(format!(
+                    "Histedit in progress: {unresolved} unresolved conflict(s). Resolve conflicts, then press {continue_key} to continue or {abort_key} to abort."
+                ))
+            } else {
+                Some(format!(
+                    "Histedit in progress: press {continue_key} to continue or {abort_key} to abort."
+                ))
+            };
+        }
+        if !self.snapshot.capabilities.has_rebase || !self.snapshot.rebase.in_progress {
+            return None;
+        }
         if unresolved > 0 {
             Some(format!(
                 "Rebase in progress: {unresolved} unresolved conflict(s). Resolve conflicts, then press {continue_key} to continue or {abort_key} to abort."
@@ -590,8 +1111,12 @@ impl App {
         let hint = self.rebase_status_hint_from_snapshot();
         if let Some(line) = &hint {
             self.status_line = line.clone();
-        } else if self.last_rebase_hint.is_some() {
-            let line = "Rebase is no longer in progress.".to_string();
+        } else if let Some(last) = &self.last_rebase_hint {
+            let line = if last.starts_with("Histedit") {
+                "Histedit is no longer in progress.".to_string()
+            } else {
+                "Rebase is no longer in progress.".to_string()
+            };
             self.status_line = line.clone();
             self.append_log(line);
         }
@@ -615,8 +1140,20 @@ impl App {
                 "Rebase continue ran. Refreshing state to verify progress…".to_string()
             }
             ActionOutcomeKind::RebaseAbort => "Rebase abort ran. Refreshing state…".to_string(),
+            ActionOutcomeKind::HisteditStart => {
+                "Histedit ran. Refreshing state to determine next step…".to_string()
+            }
+            ActionOutcomeKind::HisteditContinue => {
+                "Histedit continue ran. Refreshing state to verify progress…".to_string()
+            }
+            ActionOutcomeKind::HisteditAbort => "Histedit abort ran. Refreshing state…".to_string(),
             ActionOutcomeKind::ResolveMark | ActionOutcomeKind::ResolveUnmark => {
-                if self.snapshot.rebase.in_progress {
+                if self.snapshot.rebase.in_progress || self.snapshot.histedit.in_progress {
+                    let operation = if self.snapshot.histedit.in_progress {
+                        "histedit"
+                    } else {
+                        "rebase"
+                    };
                     let unresolved = match action_kind {
                         ActionOutcomeKind::ResolveMark => {
                             self.snapshot.rebase.unresolved_conflicts.saturating_sub(1)
@@ -628,7 +1165,7 @@ impl App {
                     };
                     if unresolved == 0 {
                         format!(
-                            "All conflicts appear resolved. Press {} to continue rebase.",
+                            "All conflicts appear resolved. Press {} to continue {operation}.",
                             self.key_for_action(ActionId::RebaseContinue)
                         )
                     } else {
@@ -640,7 +1177,32 @@ impl App {
                     format!("Completed: {}", out.command_preview)
                 }
             }
-            ActionOutcomeKind::Other => format!("Completed: {}", out.command_preview),
+            ActionOutcomeKind::Backout => {
+                if out.stdout.contains("don't forget to commit") {
+                    format!(
+                        "Backout committed and merged into the working directory. Press {} to commit the merge.",
+                        self.key_for_action(ActionId::Commit)
+                    )
+                } else {
+                    format!("Completed: {}", out.command_preview)
+                }
+            }
+            ActionOutcomeKind::Pull => {
+                let summary = summarize_pull_output(out);
+                self.append_log(summary.clone());
+                summary
+            }
+            ActionOutcomeKind::Undo => match self.undo_stack.pop() {
+                Some(entry) => format!("Undid: {}", entry.label),
+                None => format!("Completed: {}", out.command_preview),
+            },
+            ActionOutcomeKind::DryRun => format!(
+                "Dry run finished; nothing was changed. Preview of `{}` in Details.",
+                out.command_preview.trim_start_matches("dry run: ")
+            ),
+            ActionOutcomeKind::Push | ActionOutcomeKind::Other => {
+                format!("Completed: {}", out.command_preview)
+            }
         };
         self.status_line = hint;
     }
@@ -671,12 +1233,83 @@ impl App {
                     out.command_preview
                 );
             }
+            ActionOutcomeKind::HisteditStart | ActionOutcomeKind::HisteditContinue => {
+                self.status_line = format!(
+                    "Histedit stopped: {}. Resolve conflicts then press {}, or abort with {}.",
+                    out.command_preview,
+                    self.key_for_action(ActionId::HisteditContinue),
+                    self.key_for_action(ActionId::HisteditAbort)
+                );
+            }
+            ActionOutcomeKind::HisteditAbort => {
+                self.status_line = format!(
+                    "Histedit abort failed: {}. Check details for recovery steps.",
+                    out.command_preview
+                );
+            }
             ActionOutcomeKind::ResolveMark | ActionOutcomeKind::ResolveUnmark => {
                 self.status_line = format!(
                     "Conflict resolution command failed: {}. Check details and retry.",
                     out.command_preview
                 );
             }
+            ActionOutcomeKind::Backout => {
+                let output = format!("{}\n{}", out.stdout, out.stderr);
+                self.status_line = if output.contains("hg resolve") {
+                    format!(
+                        "Backout stopped on merge conflicts. Resolve them in the Conflicts panel ({} marks resolved), then press {} to commit.",
+                        self.key_for_action(ActionId::ResolveMark),
+                        self.key_for_action(ActionId::Commit)
+                    )
+                } else {
+                    format!("Backout failed: {}", out.command_preview)
+                };
+            }
+            ActionOutcomeKind::Push => {
+                let output = format!("{}\n{}", out.stdout, out.stderr);
+                let retry = self.last_push.take().and_then(|push| match push {
+                    HgAction::Push {
+                        dest,
+                        scope,
+                        new_branch: false,
+                    } if output.contains("push creates new remote branches") => {
+                        Some(HgAction::Push {
+                            dest,
+                            scope,
+                            new_branch: true,
+                        })
+                    }
+                    _ => None,
+                });
+                match retry {
+                    Some(action) => {
+                        self.status_line =
+                            "Push refused: it would create new remote branches.".to_string();
+                        // Always asked: creating a remote branch is a separate
+                        // decision from the push the policy covered.
+                        self.ask_confirmation(
+                            PendingRunAction::Hg(action),
+                            "The remote does not have this branch yet, so hg refused to push. Push again with --new-branch to create it?".to_string(),
+                            None,
+                        );
+                    }
+                    None => {
+                        self.status_line = format!("Push failed: {}", out.command_preview);
+                    }
+                }
+            }
+            ActionOutcomeKind::Pull => {
+                self.status_line = format!("Pull failed: {}", out.command_preview);
+            }
+            ActionOutcomeKind::Undo => {
+                self.status_line = format!(
+                    "Undo stopped at `{}`; later steps were not run. See Details.",
+                    out.command_preview
+                );
+            }
+            ActionOutcomeKind::DryRun => {
+                self.status_line = format!("Dry run failed: {}", out.command_preview);
+            }
             ActionOutcomeKind::Other => {
                 self.status_line = format!("Command failed: {}", out.command_preview);
             }
@@ -684,59 +1317,576 @@ impl App {
     }
 
     fn run_pending_action(&mut self, action: PendingRunAction) {
-        let tx = self.event_tx.clone();
-        let hg = Arc::clone(&self.hg);
-        let action_preview = action.command_preview();
-        let show_output = action.show_output();
-        let clear_commit_selection = action.clears_commit_selection_on_success();
-        let action_kind = action.outcome_kind();
-        self.status_line = format!("Running: {action_preview}");
-        tokio::spawn(async move {
-            let result = match action {
-                PendingRunAction::Hg(hg_action) => hg
-                    .run_action(&hg_action)
-                    .await
-                    .map_err(|err| err.to_string()),
-                PendingRunAction::Custom(custom_action) => hg
-                    .run_custom_command(&custom_action.invocation)
-                    .await
-                    .map_err(|err| err.to_string()),
-            };
-            let _ = tx.send(AppEvent::ActionFinished {
-                action_kind,
-                action_preview,
-                show_output,
-                clear_commit_selection,
-                result,
-            });
-        });
-    }
-
-    fn run_hg_action(&mut self, action: HgAction) {
-        self.run_pending_action(PendingRunAction::Hg(action));
-    }
-
-    fn confirm_action(&mut self, action: PendingRunAction, message: impl Into<String>) {
-        self.confirmation = Some(PendingConfirmation {
-            action,
-            message: message.into(),
-        });
+        let Some(action) = self.intercept_dry_run(action) else {
+            return;
+        };
+        if let Some(hit) = self.protection_hit(&action) {
+            self.guard_protected(action, hit, None, None);
+            return;
+        }
+        match self.confirmation_policy(&action) {
+            Some((_, ConfirmationPolicy::Always)) => {
+                let message = format!("Run `{}`?", action.command_preview());
+                self.ask_confirmation(action, message, None);
+            }
+            Some((id, ConfirmationPolicy::Typed)) => {
+                let message = format!("Run `{}`?", action.command_preview());
+                self.ask_typed_confirmation(action, message, id.as_str().to_string(), None);
+            }
+            _ => self.start_pending_action(action),
+        }
     }
 
-    fn open_input(&mut self, purpose: InputPurpose, title: impl Into<String>) {
-        self.input = Some(InputState {
-            title: title.into(),
-            value: String::new(),
-            purpose,
-        });
+    // With dry run armed, the next action shows its preview in Details instead
+    // of running or asking first. Returns the action when it should go ahead.
+    fn intercept_dry_run(&mut self, action: PendingRunAction) -> Option<PendingRunAction> {
+        if !self.dry_run_next {
+            return Some(action);
+        }
+        self.dry_run_next = false;
+        match action {
+            PendingRunAction::Hg(action) => {
+                self.start_pending_action(PendingRunAction::Hg(HgAction::DryRun {
+                    action: Box::new(action),
+                }));
+            }
+            PendingRunAction::Custom(custom) => {
+                self.status_line =
+                    "Dry run: custom commands cannot be previewed; nothing was run.".to_string();
+                self.set_detail_text(format!(
+                    "Dry run: would run `{}`.",
+                    custom.invocation.command_preview()
+                ));
+            }
+            // Hunks are only chosen in hg's prompts, so an interactive commit
+            // previews every candidate change.
+            PendingRunAction::Interactive(run) => {
+                self.start_pending_action(PendingRunAction::Hg(HgAction::DryRun {
+                    action: Box::new(run.equivalent_action()),
+                }));
+            }
+        }
+        None
     }
 
-    fn selected_revision(&self) -> Option<&Revision> {
-        self.snapshot.revisions.get(self.rev_idx)
+    // The `[confirmations]` entry for the action, overriding whether easyHg
+    // asks before running it.
+    fn confirmation_policy(
+        &self,
+        action: &PendingRunAction,
+    ) -> Option<(ActionId, ConfirmationPolicy)> {
+        let PendingRunAction::Hg(action) = action else {
+            return None;
+        };
+        let id = ActionId::for_hg_action(action)?;
+        self.confirmations.get(&id).map(|policy| (id, *policy))
     }
 
-    pub fn is_file_selected_for_commit(&self, path: &str) -> bool {
-        self.commit_file_selection.contains(path)
+    fn protection_hit(&self, action: &PendingRunAction) -> Option<ProtectionHit> {
+        match action {
+            PendingRunAction::Hg(action) => self.protection.check(action, &self.snapshot),
+            PendingRunAction::Interactive(run) => self
+                .protection
+                .check(&run.equivalent_action(), &self.snapshot),
+            PendingRunAction::Custom(_) => None,
+        }
+    }
+
+    // Refuses the action or turns its confirmation into a typed one,
+    // depending on protected.mode.
+    fn guard_protected(
+        &mut self,
+        action: PendingRunAction,
+        hit: ProtectionHit,
+        message: Option<String>,
+        follow_up: Option<String>,
+    ) {
+        match self.protection.mode {
+            ProtectionMode::Refuse => {
+                self.confirmation = None;
+                self.status_line = format!("Refused: {}.", hit.reason);
+                self.set_detail_text(format!(
+                    "{} is not allowed (protected.mode = \"refuse\" in config).\n\nCommand: {}",
+                    hit.reason,
+                    action.command_preview()
+                ));
+            }
+            ProtectionMode::Confirm => {
+                let prompt = format!("{}. Type `{}` to confirm.", hit.reason, hit.target);
+                let message = match message {
+                    Some(message) => format!("{message}\n\n{prompt}"),
+                    None => prompt,
+                };
+                self.status_line = format!("{}: type `{}` to confirm.", hit.reason, hit.target);
+                self.ask_typed_confirmation(action, message, hit.target, follow_up);
+            }
+        }
+    }
+
+    fn start_pending_action(&mut self, action: PendingRunAction) {
+        let action = match action {
+            PendingRunAction::Interactive(run) => {
+                self.start_interactive_run(run);
+                return;
+            }
+            action => action,
+        };
+        let tx = self.event_tx.clone();
+        let hg = Arc::clone(&self.hg);
+        let action_preview = action.command_preview();
+        let show_output = action.show_output();
+        let clear_commit_selection = action.clears_commit_selection_on_success();
+        let action_kind = action.outcome_kind();
+        if let PendingRunAction::Hg(push @ HgAction::Push { .. }) = &action {
+            self.last_push = Some(push.clone());
+        }
+        let kind = action.job_kind();
+        let label = action_preview.clone();
+        let undo_kind = match &action {
+            PendingRunAction::Hg(hg_action) => UndoKind::for_action(hg_action),
+            PendingRunAction::Custom(_) | PendingRunAction::Interactive(_) => None,
+        };
+        let undo_label = label.clone();
+        let repo_root = self.snapshot.repo_root.clone();
+        let backup = match &action {
+            PendingRunAction::Hg(hg_action) => self.safety_backup(hg_action, &label),
+            _ => None,
+        };
+        self.jobs
+            .spawn(label.clone(), kind, tx.clone(), |job_id| async move {
+                let output = output_sink(tx.clone(), job_id);
+                let started_at = Local::now();
+                // A failed backup stops the action: it is only allowed to
+                // rewrite history once the bundle exists.
+                let backup_failure = match &backup {
+                    Some(backup) => match backup.write(hg.as_ref()).await {
+                        Ok(out) if out.success => None,
+                        Ok(out) => Some(format!("{}\n{}", out.stdout.trim(), out.stderr.trim())),
+                        Err(err) => Some(err),
+                    },
+                    None => None,
+                };
+                // Captured once the job holds the write lock, so actions queued
+                // behind another write start from the state that write left.
+                let undo = match (undo_kind, repo_root) {
+                    (Some(kind), Some(root)) if backup_failure.is_none() => {
+                        hg.repo_state(LOG_LIMIT).await.ok().map(|state| {
+                            UndoCapture::from_state(undo_label, kind, Path::new(&root), state)
+                        })
+                    }
+                    _ => None,
+                };
+                let result = match (backup_failure, action) {
+                    (Some(failure), _) => Err(format!(
+                        "Safety backup failed, so `{action_preview}` was not run:\n{}",
+                        failure.trim()
+                    )),
+                    (None, PendingRunAction::Hg(hg_action)) => hg
+                        .run_action(&hg_action, Some(&output))
+                        .await
+                        .map_err(|err| err.to_string()),
+                    (None, PendingRunAction::Custom(custom_action)) => hg
+                        .run_custom_command(&custom_action.invocation, Some(&output))
+                        .await
+                        .map_err(|err| err.to_string()),
+                    (None, PendingRunAction::Interactive(_)) => {
+                        unreachable!("interactive runs are handed to the run loop above")
+                    }
+                };
+                let _ = tx.send(AppEvent::ActionFinished {
+                    action_kind,
+                    action_preview,
+                    show_output,
+                    clear_commit_selection,
+                    started_at,
+                    job_id,
+                    undo,
+                    result,
+                });
+            });
+        let queued = self.jobs.jobs().last().is_some_and(|job| job.is_queued());
+        self.status_line = if queued {
+            format!("Queued: {label} (waiting for running write)")
+        } else {
+            format!("Running: {label}")
+        };
+    }
+
+    fn open_backup_picker(&mut self) {
+        let Some(repo_root) = self.snapshot.repo_root.as_deref() else {
+            self.status_line = "Repository root unknown.".to_string();
+            return;
+        };
+        let (entries, warning) = backups::list(Path::new(repo_root));
+        if let Some(warning) = &warning {
+            self.append_log(format!("Backups: {warning}"));
+        }
+        if entries.is_empty() {
+            self.status_line = match warning {
+                Some(warning) => format!("No backup bundles listed: {warning}"),
+                None => "No backup bundles yet.".to_string(),
+            };
+            return;
+        }
+        self.status_line = format!(
+            "{} backup bundle(s){}: Enter restore (hg unbundle) | Esc close.",
+            entries.len(),
+            if warning.is_some() {
+                " (backup index unreadable, see log)"
+            } else {
+                ""
+            }
+        );
+        self.backup_picker = Some(BackupPickerState {
+            entries,
+            selected: 0,
+        });
+    }
+
+    fn handle_backup_picker_key(&mut self, key: KeyEvent) -> bool {
+        let restore = key.code == KeyCode::Enter
+            || self.keymap.action_for_event(key) == Some(ActionId::RestoreBackup);
+        let Some(picker) = self.backup_picker.as_mut() else {
+            return false;
+        };
+        match key.code {
+            _ if restore => {
+                let Some(entry) = picker.entries.get(picker.selected).cloned() else {
+                    return true;
+                };
+                self.backup_picker = None;
+                self.confirm_action(
+                    PendingRunAction::Hg(HgAction::Unbundle {
+                        file: entry.file.clone(),
+                    }),
+                    format!(
+                        "Restore the changesets saved before `{}` ({})? hg unbundle adds them back; changesets already in the repository are left alone.",
+                        entry.operation, entry.created_at
+                    ),
+                );
+            }
+            KeyCode::Esc => {
+                self.backup_picker = None;
+                self.status_line = "Backups closed.".to_string();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                picker.selected = (picker.selected + 1).min(picker.entries.len().saturating_sub(1));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                picker.selected = picker.selected.saturating_sub(1);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    // Writes run one at a time, so each capture ends where the next one
+    // started; only the last is compared against the refreshed snapshot.
+    fn record_pending_undo(&mut self) {
+        let mut captures = std::mem::take(&mut self.pending_undo)
+            .into_values()
+            .peekable();
+        while let Some(capture) = captures.next() {
+            let entry = match captures.peek() {
+                Some(next) => capture.finish_before(next),
+                None => capture.finish(&self.snapshot),
+            };
+            let Some(entry) = entry else {
+                continue;
+            };
+            if self.undo_stack.len() == UNDO_STACK_LIMIT {
+                self.undo_stack.remove(0);
+            }
+            self.undo_stack.push(entry);
+        }
+    }
+
+    fn undo_last_action(&mut self) {
+        let Some(entry) = self.undo_stack.last() else {
+            self.status_line = "Nothing to undo.".to_string();
+            return;
+        };
+        let current = RepoState::capture(&self.snapshot);
+        match entry.plan(&current, self.snapshot.capabilities.has_undo) {
+            Ok(steps) => {
+                let preview = entry.preview(&steps);
+                self.set_detail_text(preview.clone());
+                self.confirm_action(
+                    PendingRunAction::Hg(HgAction::Undo { steps }),
+                    format!("{preview}\n\nRun these steps?"),
+                );
+            }
+            Err(reason) => {
+                self.status_line = format!("Cannot undo {}.", entry.label);
+                let text = format!("Cannot undo '{}': {reason}", entry.label);
+                self.set_detail_text(text);
+            }
+        }
+    }
+
+    fn cancel_latest_job(&mut self) {
+        let Some(job) = self.jobs.latest_active() else {
+            self.status_line = "No running jobs.".to_string();
+            return;
+        };
+        if job.is_running_write() {
+            self.status_line = format!("Kill `{}`? Confirm to proceed.", job.label);
+            self.cancel_prompt = Some(CancelJobPrompt {
+                job_id: job.id,
+                label: job.label.clone(),
+            });
+            return;
+        }
+        let job = self.jobs.cancel(job.id);
+        self.finish_cancelled_job(job);
+    }
+
+    fn handle_cancel_prompt_key(&mut self, key: KeyEvent) -> bool {
+        let Some(prompt) = self.cancel_prompt.as_ref() else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => {
+                let prompt = self.cancel_prompt.take().expect("cancel prompt");
+                match self.jobs.cancel(prompt.job_id) {
+                    Some(job) => self.finish_cancelled_job(Some(job)),
+                    None => {
+                        self.status_line = format!("{} already finished.", prompt.label);
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('n') => {
+                self.status_line = format!("Still running: {}", prompt.label);
+                self.cancel_prompt = None;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn finish_cancelled_job(&mut self, job: Option<Job>) {
+        let Some(job) = job else {
+            self.status_line = "No running jobs.".to_string();
+            return;
+        };
+        self.pending_undo.remove(&job.id);
+        // A job still waiting for the lock has not opened a transaction yet.
+        let waiting_for_lock = self
+            .lock_wait
+            .as_ref()
+            .is_some_and(|prompt| prompt.job_id == job.id);
+        if waiting_for_lock {
+            self.lock_wait = None;
+        }
+        self.append_log(format!(
+            "CANCELLED: {} after {}s",
+            job.label,
+            job.elapsed().as_secs()
+        ));
+        if job.kind != JobKind::Read && !job.is_queued() {
+            self.refresh_snapshot(true);
+        }
+        let state = if job.is_queued() { "queued" } else { "running" };
+        self.status_line = format!("Cancelled {state} job: {}", job.label);
+        if job.is_running_write() && !waiting_for_lock {
+            self.status_line.push_str(
+                ". If hg reports an interrupted transaction run `hg recover`; finish an interrupted rebase/histedit with its abort.",
+            );
+            self.set_detail_text(killed_write_help_text(&job.label));
+        }
+    }
+
+    fn open_lock_wait(&mut self, job_id: u64, line: &str) {
+        let (Some(label), Some(wait)) = (
+            self.jobs.get(job_id).map(|job| job.label.clone()),
+            parse_lock_wait(line),
+        ) else {
+            return;
+        };
+        self.append_log(format!("{label}: {}", wait.message));
+        self.status_line = format!("{label} is waiting for a lock.");
+        self.lock_wait = Some(LockWaitPrompt {
+            job_id,
+            label,
+            wait,
+        });
+    }
+
+    fn handle_lock_wait_key(&mut self, key: KeyEvent) -> bool {
+        let Some(prompt) = self.lock_wait.as_ref() else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('w') => {
+                self.status_line = format!("Still waiting for the lock: {}", prompt.label);
+                self.lock_wait = None;
+            }
+            KeyCode::Esc | KeyCode::Char('c') => {
+                let job = self.jobs.cancel(prompt.job_id);
+                self.finish_cancelled_job(job);
+            }
+            KeyCode::Char('b') => {
+                let wlock = prompt.wait.wlock;
+                let what = if wlock {
+                    "working directory lock (wlock)"
+                } else {
+                    "repository store lock"
+                };
+                let holder = prompt
+                    .wait
+                    .holder
+                    .clone()
+                    .unwrap_or_else(|| "an unknown process".to_string());
+                self.lock_wait = None;
+                self.confirm_action_twice(
+                    PendingRunAction::Hg(HgAction::BreakLock { wlock }),
+                    format!(
+                        "Break the {what} held by {holder}? Only do this if that process is no longer running."
+                    ),
+                    "Really break the lock? If the holder is still alive this can corrupt the repository.",
+                );
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn safety_backup(&mut self, action: &HgAction, label: &str) -> Option<SafetyBackup> {
+        if !self.config.backup_bundles {
+            return None;
+        }
+        let repo_root = self.snapshot.repo_root.as_deref()?;
+        let backup = SafetyBackup::new(Path::new(repo_root), label, action.backup_revset()?);
+        self.append_log(format!(
+            "Safety backup of {} before {label}: {}",
+            backup.entry.revset,
+            backup.entry.file.display()
+        ));
+        Some(backup)
+    }
+
+    // The run loop picks the request up and suspends the terminal for it.
+    fn start_interactive_run(&mut self, run: InteractiveRun) {
+        let backup = self.safety_backup(&run.equivalent_action(), &run.command_preview());
+        match run {
+            InteractiveRun::Commit(request) => {
+                self.interactive_commit_request = Some(request);
+                self.status_line =
+                    "Launching interactive commit; complete prompts in terminal.".to_string();
+            }
+            InteractiveRun::Histedit(request) => {
+                self.interactive_histedit_request =
+                    Some(InteractiveHisteditRequest { backup, ..request });
+                self.status_line =
+                    "Launching histedit; edit commit messages in your editor.".to_string();
+            }
+        }
+    }
+
+    fn run_hg_action(&mut self, action: HgAction) {
+        self.run_pending_action(PendingRunAction::Hg(action));
+    }
+
+    fn confirm_action(&mut self, action: PendingRunAction, message: impl Into<String>) {
+        self.confirm_with_policy(action, message.into(), None);
+    }
+
+    fn confirm_action_twice(
+        &mut self,
+        action: PendingRunAction,
+        message: impl Into<String>,
+        follow_up: impl Into<String>,
+    ) {
+        self.confirm_with_policy(action, message.into(), Some(follow_up.into()));
+    }
+
+    // Protection outranks the configured policy: `never` does not skip the
+    // typed confirmation of a protected target.
+    fn confirm_with_policy(
+        &mut self,
+        action: PendingRunAction,
+        message: String,
+        follow_up: Option<String>,
+    ) {
+        let Some(action) = self.intercept_dry_run(action) else {
+            return;
+        };
+        if let Some(hit) = self.protection_hit(&action) {
+            self.guard_protected(action, hit, Some(message), follow_up);
+            return;
+        }
+        match self.confirmation_policy(&action) {
+            // "never" skips the routine prompt but not the risk warning.
+            Some((_, ConfirmationPolicy::Never)) => match follow_up {
+                Some(follow_up) => {
+                    self.status_line = "Confirm to proceed.".to_string();
+                    self.ask_confirmation(action, follow_up, None);
+                }
+                None => self.start_pending_action(action),
+            },
+            Some((id, ConfirmationPolicy::Typed)) => {
+                self.status_line = format!("Type `{}` to confirm.", id.as_str());
+                self.ask_typed_confirmation(action, message, id.as_str().to_string(), follow_up);
+            }
+            _ => self.ask_confirmation(action, message, follow_up),
+        }
+    }
+
+    fn ask_confirmation(
+        &mut self,
+        action: PendingRunAction,
+        message: String,
+        follow_up: Option<String>,
+    ) {
+        self.confirmation = Some(PendingConfirmation {
+            action,
+            message,
+            follow_up,
+            typed: None,
+        });
+    }
+
+    fn ask_typed_confirmation(
+        &mut self,
+        action: PendingRunAction,
+        message: String,
+        expected: String,
+        follow_up: Option<String>,
+    ) {
+        self.confirmation = Some(PendingConfirmation {
+            action,
+            message,
+            follow_up,
+            typed: Some(TypedConfirmation {
+                expected,
+                value: String::new(),
+            }),
+        });
+    }
+
+    fn open_input(&mut self, purpose: InputPurpose, title: impl Into<String>) {
+        self.open_input_with_value(purpose, title, String::new());
+    }
+
+    fn open_input_with_value(
+        &mut self,
+        purpose: InputPurpose,
+        title: impl Into<String>,
+        value: impl Into<String>,
+    ) {
+        self.input = Some(InputState {
+            title: title.into(),
+            value: value.into(),
+            purpose,
+        });
+    }
+
+    fn selected_revision(&self) -> Option<&Revision> {
+        self.snapshot.revisions.get(self.rev_idx)
+    }
+
+    pub fn is_file_selected_for_commit(&self, path: &RepoPath) -> bool {
+        self.commit_file_selection.contains(path)
     }
 
     pub fn selected_file_commit_count(&self) -> usize {
@@ -744,11 +1894,80 @@ impl App {
     }
 
     fn append_log(&mut self, line: impl Into<String>) {
+        self.push_log_entry(line.into(), None);
+    }
+
+    pub fn visible_log_entries(&self) -> Vec<&LogEntry> {
+        self.log_entries
+            .iter()
+            .filter(|entry| !self.log_failures_only || entry.is_failure())
+            .collect()
+    }
+
+    fn toggle_log_failures_only(&mut self) {
+        self.log_failures_only = !self.log_failures_only;
+        self.log_idx = 0;
+        self.status_line = if self.log_failures_only {
+            format!(
+                "Command log: showing {} failed command(s).",
+                self.visible_log_entries().len()
+            )
+        } else {
+            "Command log: showing all entries.".to_string()
+        };
+        if self.focus == FocusPanel::Log {
+            self.refresh_detail_for_focus();
+        }
+    }
+
+    fn export_log(&mut self, target: &str, overwrite: bool) {
+        let path = match self.snapshot.repo_root.as_deref() {
+            Some(root) if std::path::Path::new(target).is_relative() => {
+                std::path::Path::new(root).join(target)
+            }
+            _ => std::path::PathBuf::from(target),
+        };
+        if path.exists() && !overwrite {
+            self.status_line = format!("{} already exists.", path.display());
+            self.open_input_with_value(
+                InputPurpose::ExportLogOverwrite {
+                    path: target.to_string(),
+                },
+                "File exists: Enter overwrites it, or edit the path",
+                target,
+            );
+            return;
+        }
+        let entries = self.visible_log_entries();
+        let count = entries.len();
+        let body = entries
+            .iter()
+            .map(|entry| entry.render())
+            .collect::<Vec<_>>()
+            .join("\n\n----\n\n");
+        match std::fs::write(&path, format!("{body}\n")) {
+            Ok(()) => {
+                self.status_line = format!("Exported {count} log entries to {}.", path.display());
+            }
+            Err(err) => {
+                self.status_line = format!("Log export failed: {err}");
+            }
+        }
+    }
+
+    fn append_command_log(&mut self, line: impl Into<String>, record: CommandRecord) {
+        self.push_log_entry(line.into(), Some(record));
+    }
+
+    fn push_log_entry(&mut self, line: String, record: Option<CommandRecord>) {
         let now = Local::now().format("%H:%M:%S");
-        self.log_lines.push(format!("[{now}] {}", line.into()));
-        if self.log_lines.len() > MAX_LOG_LINES {
-            let extra = self.log_lines.len() - MAX_LOG_LINES;
-            self.log_lines.drain(0..extra);
+        self.log_entries.push(LogEntry {
+            text: format!("[{now}] {line}"),
+            record,
+        });
+        if self.log_entries.len() > MAX_LOG_LINES {
+            let extra = self.log_entries.len() - MAX_LOG_LINES;
+            self.log_entries.drain(0..extra);
         }
     }
 
@@ -762,14 +1981,25 @@ impl App {
         if self.bookmarks_idx >= self.snapshot.bookmarks.len() {
             self.bookmarks_idx = self.snapshot.bookmarks.len().saturating_sub(1);
         }
+        if self.branches_idx >= self.snapshot.branches.len() {
+            self.branches_idx = self.snapshot.branches.len().saturating_sub(1);
+        }
+        let remote_len = self.remote_revisions().len();
+        if self.remote_idx >= remote_len {
+            self.remote_idx = remote_len.saturating_sub(1);
+        }
+        if self.tags_idx >= self.snapshot.tags.len() {
+            self.tags_idx = self.snapshot.tags.len().saturating_sub(1);
+        }
         if self.shelves_idx >= self.snapshot.shelves.len() {
             self.shelves_idx = self.snapshot.shelves.len().saturating_sub(1);
         }
         if self.conflicts_idx >= self.snapshot.conflicts.len() {
             self.conflicts_idx = self.snapshot.conflicts.len().saturating_sub(1);
         }
-        if self.log_idx >= self.log_lines.len() {
-            self.log_idx = self.log_lines.len().saturating_sub(1);
+        let log_len = self.visible_log_entries().len();
+        if self.log_idx >= log_len {
+            self.log_idx = log_len.saturating_sub(1);
         }
         let current_paths = self
             .snapshot
@@ -782,6 +2012,9 @@ impl App {
         self.ensure_visible(FocusPanel::Files);
         self.ensure_visible(FocusPanel::Revisions);
         self.ensure_visible(FocusPanel::Bookmarks);
+        self.ensure_visible(FocusPanel::Branches);
+        self.ensure_visible(FocusPanel::Tags);
+        self.ensure_visible(FocusPanel::Remote);
         self.ensure_visible(FocusPanel::Shelves);
         self.ensure_visible(FocusPanel::Conflicts);
     }
@@ -791,9 +2024,12 @@ impl App {
             FocusPanel::Files => self.snapshot.files.len(),
             FocusPanel::Revisions => self.snapshot.revisions.len(),
             FocusPanel::Bookmarks => self.snapshot.bookmarks.len(),
+            FocusPanel::Branches => self.snapshot.branches.len(),
+            FocusPanel::Tags => self.snapshot.tags.len(),
             FocusPanel::Shelves => self.snapshot.shelves.len(),
             FocusPanel::Conflicts => self.snapshot.conflicts.len(),
-            FocusPanel::Log => self.log_lines.len(),
+            FocusPanel::Remote => self.remote_revisions().len(),
+            FocusPanel::Log => self.visible_log_entries().len(),
         }
     }
 
@@ -802,8 +2038,11 @@ impl App {
             FocusPanel::Files => self.files_idx,
             FocusPanel::Revisions => self.rev_idx,
             FocusPanel::Bookmarks => self.bookmarks_idx,
+            FocusPanel::Branches => self.branches_idx,
+            FocusPanel::Tags => self.tags_idx,
             FocusPanel::Shelves => self.shelves_idx,
             FocusPanel::Conflicts => self.conflicts_idx,
+            FocusPanel::Remote => self.remote_idx,
             FocusPanel::Log => self.log_idx,
         }
     }
@@ -813,8 +2052,11 @@ impl App {
             FocusPanel::Files => self.files_idx = index,
             FocusPanel::Revisions => self.rev_idx = index,
             FocusPanel::Bookmarks => self.bookmarks_idx = index,
+            FocusPanel::Branches => self.branches_idx = index,
+            FocusPanel::Tags => self.tags_idx = index,
             FocusPanel::Shelves => self.shelves_idx = index,
             FocusPanel::Conflicts => self.conflicts_idx = index,
+            FocusPanel::Remote => self.remote_idx = index,
             FocusPanel::Log => self.log_idx = index,
         }
     }
@@ -824,8 +2066,11 @@ impl App {
             FocusPanel::Files => self.files_offset,
             FocusPanel::Revisions => self.rev_offset,
             FocusPanel::Bookmarks => self.bookmarks_offset,
+            FocusPanel::Branches => self.branches_offset,
+            FocusPanel::Tags => self.tags_offset,
             FocusPanel::Shelves => self.shelves_offset,
             FocusPanel::Conflicts => self.conflicts_offset,
+            FocusPanel::Remote => self.remote_offset,
             FocusPanel::Log => self.log_idx,
         }
     }
@@ -835,8 +2080,11 @@ impl App {
             FocusPanel::Files => self.files_offset = offset,
             FocusPanel::Revisions => self.rev_offset = offset,
             FocusPanel::Bookmarks => self.bookmarks_offset = offset,
+            FocusPanel::Branches => self.branches_offset = offset,
+            FocusPanel::Tags => self.tags_offset = offset,
             FocusPanel::Shelves => self.shelves_offset = offset,
             FocusPanel::Conflicts => self.conflicts_offset = offset,
+            FocusPanel::Remote => self.remote_offset = offset,
             FocusPanel::Log => self.log_idx = offset,
         }
     }
@@ -950,7 +2198,7 @@ impl App {
                     if !include_revisions {
                         snapshot.revisions = self.snapshot.revisions.clone();
                     }
-                    self.snapshot = snapshot;
+                    self.snapshot = *snapshot;
                     self.adjust_indexes();
                     if include_revisions {
                         let has_graph_rows = self.snapshot.revisions.iter().any(|rev| {
@@ -1002,6 +2250,9 @@ impl App {
                         self.refresh_detail_for_focus();
                     }
                     self.refresh_rebase_status_hint_from_snapshot();
+                    if include_revisions {
+                        self.record_pending_undo();
+                    }
                     self.append_log("Snapshot refreshed");
                 }
                 Err(err) => {
@@ -1010,6 +2261,16 @@ impl App {
                     self.append_log(format!("Refresh failed: {err}"));
                 }
             },
+            AppEvent::RemotePatchLoaded {
+                request_id,
+                node,
+                result,
+            } => {
+                if let Ok(patch) = &result {
+                    self.remote_patches.insert(node, patch.clone());
+                }
+                self.handle_app_event(AppEvent::DetailLoaded { request_id, result });
+            }
             AppEvent::DetailLoaded { request_id, result } => {
                 if request_id == self.detail_request_id {
                     match result {
@@ -1027,23 +2288,112 @@ impl App {
                     }
                 }
             }
+            AppEvent::HisteditPlanLoaded { base_rev, result } => match result {
+                Ok(revisions) if revisions.is_empty() => {
+                    self.status_line = format!(
+                        "Revision {base_rev} is not an ancestor of the working directory parent."
+                    );
+                }
+                Ok(revisions) => {
+                    let rows = revisions
+                        .into_iter()
+                        .map(|rev| HisteditPlanStep {
+                            action: HisteditAction::Pick,
+                            rev: rev.rev,
+                            node: rev.node,
+                            desc: rev.desc,
+                        })
+                        .collect::<Vec<_>>();
+                    self.status_line = format!(
+                        "Histedit plan: {} revision(s) from {base_rev}. Enter runs, Esc cancels.",
+                        rows.len()
+                    );
+                    self.histedit_plan = Some(HisteditPlanState {
+                        base_rev,
+                        rows,
+                        selected: 0,
+                    });
+                }
+                Err(err) => {
+                    self.status_line = "Failed loading histedit plan.".to_string();
+                    self.append_log(format!("Histedit plan error: {}", err.trim()));
+                    self.set_detail_text(err);
+                }
+            },
+            AppEvent::RemoteChangesLoaded {
+                direction,
+                path,
+                result,
+            } => self.apply_remote_changes(direction, path, result),
+            AppEvent::PushPreviewLoaded {
+                push,
+                target,
+                result,
+                outgoing,
+            } => self.confirm_push_with_preview(push, target, result, outgoing),
+            AppEvent::JobStarted { id } => {
+                self.jobs.mark_started(id);
+                if let Some(job) = self.jobs.jobs().iter().find(|job| job.id == id) {
+                    self.status_line = format!("Running: {}", job.label);
+                }
+            }
+            AppEvent::JobFinished { id } => {
+                self.jobs.finish(id);
+                if self
+                    .cancel_prompt
+                    .as_ref()
+                    .is_some_and(|prompt| prompt.job_id == id)
+                {
+                    self.cancel_prompt = None;
+                }
+            }
+            AppEvent::CommandOutput { job_id, chunk } => match chunk {
+                OutputChunk::Progress(progress) => self.jobs.set_progress(job_id, progress),
+                OutputChunk::Stderr(line) if parse_lock_wait(&line).is_some() => {
+                    self.open_lock_wait(job_id, &line)
+                }
+                OutputChunk::Stdout(line) | OutputChunk::Stderr(line) => {
+                    let line = line.trim();
+                    if let Some(job) = self.jobs.get(job_id).filter(|_| !line.is_empty()) {
+                        self.status_line = format!("{}: {line}", job.label);
+                    }
+                }
+            },
             AppEvent::ActionFinished {
                 action_kind,
                 action_preview,
                 show_output,
                 clear_commit_selection,
+                started_at,
+                job_id,
+                undo,
                 result,
             } => match result {
                 Ok(out) => {
                     let mut preserve_status_after_refresh = None;
                     if out.success {
+                        // Finished once the refresh below shows the new state.
+                        if let Some(undo) = undo {
+                            self.pending_undo.insert(job_id, undo);
+                        }
+                        if self
+                            .jobs
+                            .get(job_id)
+                            .is_some_and(|job| job.kind != JobKind::Read)
+                        {
+                            self.clear_remote_changes();
+                        }
                         self.handle_rebase_action_success_hint(action_kind, &out);
                         if action_kind != ActionOutcomeKind::Other {
                             preserve_status_after_refresh = Some(self.status_line.clone());
                         }
-                        self.append_log(format!("OK: {}", out.command_preview));
+                        self.append_command_log(
+                            format!("OK: {}", out.command_preview),
+                            CommandRecord::from_result(&out, started_at),
+                        );
                         if clear_commit_selection {
                             self.commit_file_selection.clear();
+                            self.close_branch_on_commit = false;
                         }
                         if show_output {
                             let text = collect_command_output(&out);
@@ -1059,7 +2409,10 @@ impl App {
                             out.stdout.trim(),
                             out.stderr.trim()
                         );
-                        self.append_log(format!("FAILED: {}", detail.trim()));
+                        self.append_command_log(
+                            format!("FAILED: {}", out.command_preview),
+                            CommandRecord::from_result(&out, started_at),
+                        );
                         self.set_detail_text(detail);
                         if action_kind != ActionOutcomeKind::Other {
                             preserve_status_after_refresh = Some(self.status_line.clone());
@@ -1072,7 +2425,18 @@ impl App {
                 }
                 Err(err) => {
                     self.status_line = format!("Command error: {action_preview}");
-                    self.append_log(format!("ERROR: {}", err.trim()));
+                    self.append_command_log(
+                        format!("ERROR: {}", err.trim()),
+                        CommandRecord {
+                            command: action_preview,
+                            started_at,
+                            finished_at: Local::now(),
+                            exit_code: None,
+                            success: false,
+                            stdout: String::new(),
+                            stderr: err.clone(),
+                        },
+                    );
                     self.set_detail_text(err);
                 }
             },
@@ -1083,6 +2447,11 @@ impl App {
         if self.handle_confirmation_key(key)
             || self.handle_input_key(key)
             || self.handle_command_palette_key(key)
+            || self.handle_histedit_plan_key(key)
+            || self.handle_path_picker_key(key)
+            || self.handle_backup_picker_key(key)
+            || self.handle_lock_wait_key(key)
+            || self.handle_cancel_prompt_key(key)
         {
             return;
         }
@@ -1113,7 +2482,7 @@ impl App {
             ActionId::ToggleFileForCommit => self.toggle_selected_file_for_commit(),
             ActionId::ClearFileSelection => self.clear_file_selection(),
             ActionId::Commit => {
-                let title = if self.selected_file_commit_count() == 0 {
+                let mut title = if self.selected_file_commit_count() == 0 {
                     "Commit message (all tracked changes)".to_string()
                 } else {
                     format!(
@@ -1126,6 +2495,9 @@ impl App {
                         }
                     )
                 };
+                if self.close_branch_on_commit {
+                    title.push_str(" [--close-branch]");
+                }
                 self.open_input(InputPurpose::CommitMessage, title);
             }
             ActionId::CommitInteractive => {
@@ -1145,28 +2517,163 @@ impl App {
                 self.open_input(InputPurpose::CommitMessageInteractive, title);
             }
             ActionId::Bookmark => self.open_input(InputPurpose::BookmarkName, "New bookmark"),
+            ActionId::BranchCreate => {
+                self.open_input(InputPurpose::BranchName, "New branch (hg branch)")
+            }
+            ActionId::ToggleCloseBranch => {
+                self.close_branch_on_commit = !self.close_branch_on_commit;
+                let branch = self.snapshot.branch.as_deref().unwrap_or("current branch");
+                self.status_line = if self.close_branch_on_commit {
+                    format!("Next commit will close branch '{branch}' (--close-branch).")
+                } else {
+                    "Next commit will not close the branch.".to_string()
+                };
+            }
+            ActionId::DryRunNext => {
+                self.dry_run_next = !self.dry_run_next;
+                self.status_line = if self.dry_run_next {
+                    "Dry run armed: the next action shows what it would do instead of running."
+                        .to_string()
+                } else {
+                    "Dry run disarmed.".to_string()
+                };
+            }
+            ActionId::CancelJob => self.cancel_latest_job(),
+            ActionId::Undo => self.undo_last_action(),
+            // A bundle has to be picked first; inside the view this key restores it.
+            ActionId::ShowBackups | ActionId::RestoreBackup => self.open_backup_picker(),
+            ActionId::ToggleLogFailures => self.toggle_log_failures_only(),
+            ActionId::ExportLog => self.open_input_with_value(
+                InputPurpose::ExportLogPath,
+                "Export command log to file (relative to repo root)",
+                // Under .hg/ so the export never shows up as an untracked file.
+                ".hg/easyhg-command-log.txt",
+            ),
+            ActionId::JumpToRevision => self.jump_to_selected_ref_revision(),
+            ActionId::TagCreate => self.create_tag_on_selected_revision(false),
+            ActionId::TagCreateLocal => self.create_tag_on_selected_revision(true),
+            ActionId::TagRemove => self.remove_selected_tag(),
+            ActionId::BookmarkDelete => self.delete_selected_bookmark(),
+            ActionId::BookmarkRename => self.rename_selected_bookmark(),
+            ActionId::BookmarkMove => self.move_selected_bookmark(),
+            ActionId::BookmarkDeactivate => {
+                if self
+                    .snapshot
+                    .bookmarks
+                    .iter()
+                    .any(|bookmark| bookmark.active)
+                {
+                    self.run_hg_action(HgAction::BookmarkDeactivate);
+                } else {
+                    self.status_line = "No active bookmark to deactivate.".to_string();
+                }
+            }
             ActionId::Shelve => {
                 if self.snapshot.capabilities.has_shelve {
-                    self.open_input(InputPurpose::ShelveName, "Shelve name");
+                    let selected = self.commit_file_selection.len();
+                    let title = if selected == 0 {
+                        "Shelve name (all changes)".to_string()
+                    } else {
+                        format!("Shelve name ({selected} selected file(s))")
+                    };
+                    self.open_input(InputPurpose::ShelveName, title);
                 } else {
                     self.status_line = "Shelve extension/command unavailable.".to_string();
                 }
             }
-            ActionId::Push => self.confirm_action(
-                PendingRunAction::Hg(HgAction::Push),
-                "Push current changes?",
-            ),
-            ActionId::Pull => self.run_hg_action(HgAction::Pull),
-            ActionId::Incoming => self.run_hg_action(HgAction::Incoming),
-            ActionId::Outgoing => self.run_hg_action(HgAction::Outgoing),
+            ActionId::Push => self.open_path_picker(RemoteDirection::Push, SyncScope::All),
+            ActionId::PushRevision => self.push_selected_revision(),
+            ActionId::PushBookmark => self.push_selected_bookmark(),
+            ActionId::PushBranch => self.push_branch(),
+            ActionId::Pull => self.open_path_picker(RemoteDirection::Pull, SyncScope::All),
+            ActionId::PullRevision => {
+                self.open_input(InputPurpose::PullRevision, "Revision to pull (hg pull -r)")
+            }
+            ActionId::PullBookmark => {
+                self.open_input(InputPurpose::PullBookmark, "Bookmark to pull (hg pull -B)")
+            }
+            ActionId::CyclePullMode => self.cycle_pull_mode(),
+            ActionId::Incoming => self.load_remote_changes(RemoteDirection::Pull),
+            ActionId::Outgoing => self.load_remote_changes(RemoteDirection::Push),
             ActionId::UpdateSelected => self.update_action_for_selection(),
             ActionId::UnshelveSelected => self.unshelve_selected(),
+            ActionId::ShelveDelete => self.delete_selected_shelf(),
+            ActionId::ToggleShelveKeep => {
+                self.shelve_keep = !self.shelve_keep;
+                self.status_line = if self.shelve_keep {
+                    "Shelve/unshelve will keep changes (--keep).".to_string()
+                } else {
+                    "Shelve/unshelve will not use --keep.".to_string()
+                };
+            }
             ActionId::ResolveMark => self.mark_selected_conflict(true),
             ActionId::ResolveUnmark => self.mark_selected_conflict(false),
             ActionId::RebaseSelected => self.start_or_confirm_rebase(),
             ActionId::RebaseContinue => self.continue_rebase(),
             ActionId::RebaseAbort => self.abort_rebase(),
             ActionId::HisteditSelected => self.maybe_histedit(),
+            ActionId::HisteditContinue => self.continue_histedit(),
+            ActionId::HisteditAbort => self.abort_histedit(),
+            ActionId::BackoutSelected => self.backout_selected(),
+            ActionId::PhaseSecret => self.set_selected_phase(Phase::Secret),
+            ActionId::PhaseDraft => self.set_selected_phase(Phase::Draft),
+            ActionId::PhasePublic => self.set_selected_phase(Phase::Public),
+            ActionId::EvolvePrev => {
+                if self.require_evolve() {
+                    self.run_hg_action(HgAction::Prev);
+                }
+            }
+            ActionId::EvolveNext => {
+                if self.require_evolve() {
+                    self.run_hg_action(HgAction::Next);
+                }
+            }
+            ActionId::Evolve => {
+                if self.require_evolve() {
+                    self.run_hg_action(HgAction::Evolve { all: false });
+                }
+            }
+            ActionId::EvolveAll => {
+                if self.require_evolve() {
+                    self.confirm_action(
+                        PendingRunAction::Hg(HgAction::Evolve { all: true }),
+                        "Evolve all unstable changesets? This rewrites every orphan and divergent revision.",
+                    );
+                }
+            }
+            ActionId::TopicCreate => {
+                if self.require_topic() {
+                    self.open_input(InputPurpose::TopicName, "New topic (hg topic)");
+                }
+            }
+            ActionId::TopicSwitch => {
+                if self.require_topic() {
+                    let names = self
+                        .snapshot
+                        .topics
+                        .iter()
+                        .map(|topic| topic.name.as_str())
+                        .collect::<Vec<_>>();
+                    let title = if names.is_empty() {
+                        "Switch to topic (no topics yet)".to_string()
+                    } else {
+                        format!("Switch to topic ({})", names.join(", "))
+                    };
+                    self.open_input(InputPurpose::TopicSwitchName, title);
+                }
+            }
+            ActionId::TopicClear => {
+                if self.require_topic() {
+                    if self.snapshot.active_topic().is_some() {
+                        self.run_hg_action(HgAction::TopicClear);
+                    } else {
+                        self.status_line = "No active topic to clear.".to_string();
+                    }
+                }
+            }
+            ActionId::TopicsList => self.show_topics_list(),
+            ActionId::PruneSelected => self.prune_selected(),
+            ActionId::ObslogSelected => self.show_obslog_for_selection(),
             ActionId::HardRefresh => {
                 self.refresh_snapshot(false);
                 self.refresh_detail_for_focus();
@@ -1175,7 +2682,14 @@ impl App {
     }
 
     fn handle_mouse(&mut self, mouse: MouseEvent) {
-        if self.confirmation.is_some() || self.input.is_some() || self.command_palette.is_some() {
+        if self.confirmation.is_some()
+            || self.input.is_some()
+            || self.command_palette.is_some()
+            || self.histedit_plan.is_some()
+            || self.path_picker.is_some()
+            || self.backup_picker.is_some()
+            || self.lock_wait.is_some()
+        {
             return;
         }
 
@@ -1198,7 +2712,17 @@ impl App {
                         self.ensure_visible(panel);
                     }
 
-                    if is_double && matches!(panel, FocusPanel::Files | FocusPanel::Revisions) {
+                    if is_double
+                        && matches!(
+                            panel,
+                            FocusPanel::Files
+                                | FocusPanel::Revisions
+                                | FocusPanel::Bookmarks
+                                | FocusPanel::Shelves
+                                | FocusPanel::Conflicts
+                                | FocusPanel::Remote
+                        )
+                    {
                         self.refresh_detail_for_focus();
                     }
                 }
@@ -1241,7 +2765,7 @@ impl App {
     fn scroll_panel(&mut self, panel: FocusPanel, delta: isize) {
         self.focus = panel;
         if panel == FocusPanel::Log {
-            let len = self.log_lines.len();
+            let len = self.visible_log_entries().len();
             if len == 0 {
                 self.log_idx = 0;
                 return;
@@ -1249,6 +2773,7 @@ impl App {
             let current = self.log_idx as isize;
             let next = (current + delta).clamp(0, (len - 1) as isize);
             self.log_idx = next as usize;
+            self.refresh_detail_for_focus();
             return;
         }
 
@@ -1263,7 +2788,15 @@ impl App {
         let next = (current + delta).clamp(0, (len - 1) as isize) as usize;
         self.set_panel_index(panel, next);
         self.ensure_visible(panel);
-        if matches!(panel, FocusPanel::Files | FocusPanel::Revisions) {
+        if matches!(
+            panel,
+            FocusPanel::Files
+                | FocusPanel::Revisions
+                | FocusPanel::Bookmarks
+                | FocusPanel::Shelves
+                | FocusPanel::Conflicts
+                | FocusPanel::Remote
+        ) {
             self.refresh_detail_for_focus();
         }
     }
@@ -1307,6 +2840,9 @@ impl App {
             return;
         }
 
+        if self.public_revision_guard(selected_rev, "rebase") {
+            return;
+        }
         self.pending_rebase_source = Some(selected_rev);
         self.status_line = format!(
             "Rebase step 1/2: source {selected_rev} selected. Choose destination and press {} again (Esc cancels).",
@@ -1378,1273 +2914,3662 @@ impl App {
             self.status_line = "Histedit extension not enabled.".to_string();
             return;
         }
-        if let Some(rev) = self.selected_revision() {
-            self.confirm_action(
-                PendingRunAction::Hg(HgAction::HisteditBase { base_rev: rev.rev }),
-                format!("Start histedit from revision {}?", rev.rev),
+        if self.snapshot.histedit.in_progress {
+            self.status_line = format!(
+                "Histedit already in progress. Press {} to continue or {} to abort.",
+                self.key_for_action(ActionId::HisteditContinue),
+                self.key_for_action(ActionId::HisteditAbort)
             );
-        } else {
+            return;
+        }
+        let Some(base_rev) = self.selected_revision().map(|rev| rev.rev) else {
             self.status_line = "No revision selected for histedit.".to_string();
+            return;
+        };
+        if self.public_revision_guard(base_rev, "histedit") {
+            return;
+        }
+        self.status_line = format!("Loading histedit plan from revision {base_rev}…");
+        let tx = self.event_tx.clone();
+        let hg = Arc::clone(&self.hg);
+        tokio::spawn(async move {
+            let result = hg
+                .log_revset(&format!("{base_rev}::."))
+                .await
+                .map_err(|err| err.to_string());
+            let _ = tx.send(AppEvent::HisteditPlanLoaded { base_rev, result });
+        });
+    }
+
+    fn continue_histedit(&mut self) {
+        if !self.histedit_in_progress() {
+            return;
+        }
+        if self.snapshot.rebase.unresolved_conflicts > 0 {
+            let unresolved = self.snapshot.rebase.unresolved_conflicts;
+            self.status_line =
+                format!("Cannot continue histedit: {unresolved} unresolved conflict(s) remain.");
+            self.set_rebase_guard_detail_text(histedit_continue_blocked_help_text(
+                unresolved,
+                self.key_for_action(ActionId::ResolveMark),
+                self.key_for_action(ActionId::HisteditContinue),
+                self.key_for_action(ActionId::HisteditAbort),
+            ));
+            return;
         }
+        self.status_line = "Histedit continue ready. Confirm to proceed.".to_string();
+        self.confirm_action(
+            PendingRunAction::Hg(HgAction::HisteditContinue),
+            "Continue in-progress histedit?",
+        );
     }
 
-    fn mark_selected_conflict(&mut self, resolved: bool) {
-        if let Some(conflict) = self.snapshot.conflicts.get(self.conflicts_idx) {
-            let action = if resolved {
-                HgAction::ResolveMark {
-                    path: conflict.path.clone(),
-                }
-            } else {
-                HgAction::ResolveUnmark {
-                    path: conflict.path.clone(),
-                }
-            };
-            self.run_hg_action(action);
-        } else {
-            self.status_line = "No conflict selected.".to_string();
+    fn abort_histedit(&mut self) {
+        if !self.histedit_in_progress() {
+            return;
         }
+        self.status_line = "Histedit abort ready. Confirm to proceed.".to_string();
+        self.confirm_action(
+            PendingRunAction::Hg(HgAction::HisteditAbort),
+            "Abort in-progress histedit? Already rewritten changesets are restored.",
+        );
     }
 
-    fn unshelve_selected(&mut self) {
-        if let Some(shelf) = self.snapshot.shelves.get(self.shelves_idx) {
-            self.confirm_action(
-                PendingRunAction::Hg(HgAction::Unshelve {
-                    name: shelf.name.clone(),
-                }),
-                format!("Unshelve '{}'? This applies shelved changes.", shelf.name),
-            );
-        } else {
-            self.status_line = "No shelf selected.".to_string();
+    fn histedit_in_progress(&mut self) -> bool {
+        if !self.snapshot.capabilities.has_histedit {
+            self.status_line = "Histedit extension not enabled.".to_string();
+            return false;
+        }
+        if !self.snapshot.histedit.in_progress {
+            self.status_line = "No histedit is currently in progress.".to_string();
+            return false;
         }
+        true
     }
 
-    fn update_action_for_selection(&mut self) {
-        match self.focus {
-            FocusPanel::Bookmarks => {
-                if let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) {
-                    self.confirm_action(
-                        PendingRunAction::Hg(HgAction::UpdateToBookmark {
-                            name: bookmark.name.clone(),
-                        }),
-                        format!("Update working directory to bookmark '{}'?", bookmark.name),
-                    );
-                } else {
-                    self.status_line = "No bookmark selected.".to_string();
-                }
+    fn handle_histedit_plan_key(&mut self, key: KeyEvent) -> bool {
+        let Some(plan) = self.histedit_plan.as_mut() else {
+            return false;
+        };
+        let last = plan.rows.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc => {
+                self.histedit_plan = None;
+                self.status_line = "Histedit cancelled.".to_string();
             }
-            _ => {
-                if let Some(rev) = self.snapshot.revisions.get(self.rev_idx) {
-                    self.confirm_action(
-                        PendingRunAction::Hg(HgAction::UpdateToRevision { rev: rev.rev }),
-                        format!("Update working directory to revision {}?", rev.rev),
-                    );
-                } else {
-                    self.status_line = "No revision selected.".to_string();
+            KeyCode::Down | KeyCode::Char('j') => {
+                plan.selected = (plan.selected + 1).min(last);
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                plan.selected = plan.selected.saturating_sub(1);
+            }
+            KeyCode::Char('J') if plan.selected < last => {
+                plan.rows.swap(plan.selected, plan.selected + 1);
+                plan.selected += 1;
+            }
+            KeyCode::Char('K') if plan.selected > 0 => {
+                plan.rows.swap(plan.selected, plan.selected - 1);
+                plan.selected -= 1;
+            }
+            KeyCode::Char('J' | 'K') => {}
+            KeyCode::Char(c) => {
+                if let Some(action) = HisteditAction::from_key(c)
+                    && let Some(row) = plan.rows.get_mut(plan.selected)
+                {
+                    row.action = action;
                 }
             }
+            KeyCode::Enter => self.submit_histedit_plan(),
+            _ => {}
         }
+        true
     }
 
-    fn cycle_focus(&mut self, forward: bool) {
-        let panels = FocusPanel::all();
-        let pos = panels
-            .iter()
-            .position(|panel| *panel == self.focus)
-            .unwrap_or(0);
-        let next = if forward {
-            (pos + 1) % panels.len()
-        } else {
-            (pos + panels.len() - 1) % panels.len()
+    fn submit_histedit_plan(&mut self) {
+        let Some(plan) = self.histedit_plan.as_ref() else {
+            return;
         };
-        self.focus = panels[next];
-        self.refresh_detail_for_focus();
-    }
-
-    fn move_selection(&mut self, delta: isize) {
-        if self.focus == FocusPanel::Log {
-            let len = self.log_lines.len();
-            if len == 0 {
-                self.log_idx = 0;
-                return;
-            }
-            let current = self.log_idx as isize;
-            let next = (current + delta).clamp(0, (len - 1) as isize);
-            self.log_idx = next as usize;
+        if let Some(first) = plan.rows.first()
+            && first.action.needs_predecessor()
+        {
+            self.status_line = format!(
+                "Cannot {} the first revision in the plan; it has nothing to fold into.",
+                first.action.as_str()
+            );
             return;
         }
-
-        let len = self.panel_len(self.focus);
-        if len == 0 {
-            self.set_panel_index(self.focus, 0);
-            self.set_panel_offset(self.focus, 0);
+        let Some(plan) = self.histedit_plan.take() else {
             return;
-        }
-
-        let current = self.panel_index(self.focus) as isize;
-        let next = (current + delta).clamp(0, (len - 1) as isize) as usize;
-        self.set_panel_index(self.focus, next);
-        self.ensure_visible(self.focus);
-        if matches!(self.focus, FocusPanel::Files | FocusPanel::Revisions) {
-            self.refresh_detail_for_focus();
+        };
+        if plan.rows.iter().any(|row| row.action.needs_editor()) {
+            self.run_pending_action(PendingRunAction::Interactive(InteractiveRun::Histedit(
+                InteractiveHisteditRequest {
+                    base_rev: plan.base_rev,
+                    steps: plan.rows,
+                    backup: None,
+                },
+            )));
+        } else {
+            self.run_hg_action(HgAction::HisteditPlan {
+                base_rev: plan.base_rev,
+                steps: plan.rows,
+            });
         }
     }
 
-    fn open_command_palette(&mut self) {
-        if self.config.custom_commands.is_empty() {
-            self.status_line = "No custom commands configured.".to_string();
+    fn backout_selected(&mut self) {
+        let Some(rev) = self.selected_revision() else {
+            self.status_line = "No revision selected for backout.".to_string();
             return;
-        }
-        self.command_palette = Some(CommandPaletteState { selected: 0 });
-        self.status_line = "Custom commands: Enter run | Esc cancel.".to_string();
+        };
+        let rev_num = rev.rev;
+        let short_node = rev.node.chars().take(12).collect::<String>();
+        let merge = self.snapshot.working_parent_rev != Some(rev_num);
+        let title = if merge {
+            format!("Backout message (hg backout --merge, revision {rev_num})")
+        } else {
+            format!("Backout message (hg backout, revision {rev_num})")
+        };
+        self.open_input_with_value(
+            InputPurpose::BackoutMessage {
+                rev: rev_num,
+                merge,
+            },
+            title,
+            format!("Backed out changeset {short_node}"),
+        );
     }
 
-    fn toggle_selected_file_for_commit(&mut self) {
-        let Some(file) = self.snapshot.files.get(self.files_idx) else {
-            self.status_line = "No file selected.".to_string();
+    fn set_selected_phase(&mut self, target: Phase) {
+        let Some(rev) = self.selected_revision() else {
+            self.status_line = "No revision selected for phase change.".to_string();
             return;
         };
-        let path = file.path.clone();
-        if self.commit_file_selection.contains(&path) {
-            self.commit_file_selection.remove(&path);
-            self.status_line = format!("Removed from commit selection: {path}");
+        let rev_num = rev.rev;
+        let current = Phase::from_name(&rev.phase);
+        if current == Some(target) {
+            self.status_line = format!("Revision {rev_num} is already {}.", target.as_str());
+            return;
+        }
+        let current_name = current.map(Phase::as_str).unwrap_or(rev.phase.as_str());
+        let force = current.is_some_and(|current| target > current);
+        let action = PendingRunAction::Hg(HgAction::SetPhase {
+            rev: rev_num,
+            phase: target,
+            force,
+        });
+        if force {
+            let message = format!(
+                "Move revision {rev_num} from {current_name} back to {}? This needs --force.",
+                target.as_str()
+            );
+            let follow_up = format!(
+                "Really force revision {rev_num} to {}? Other clones may already have it as {current_name}; rewriting it afterwards can create divergent history.",
+                target.as_str()
+            );
+            self.confirm_action_twice(action, message, follow_up);
         } else {
-            self.commit_file_selection.insert(path.clone());
-            self.status_line = format!("Selected for commit: {path}");
+            let message = format!(
+                "Move revision {rev_num} from {current_name} to {}?",
+                target.as_str()
+            );
+            self.confirm_action(action, message);
         }
     }
 
-    fn clear_file_selection(&mut self) {
-        self.commit_file_selection.clear();
-        self.status_line = "Cleared commit file selection.".to_string();
+    fn public_revision_guard(&mut self, rev: i64, operation: &str) -> bool {
+        let is_public = self
+            .snapshot
+            .revisions
+            .iter()
+            .any(|candidate| candidate.rev == rev && candidate.phase == Phase::Public.as_str());
+        if is_public {
+            self.status_line = format!(
+                "Revision {rev} is public; {operation} would rewrite published history. Move it to draft ({}) first if you really need to.",
+                self.key_for_action(ActionId::PhaseDraft)
+            );
+        }
+        is_public
     }
 
-    fn handle_command_palette_key(&mut self, key: KeyEvent) -> bool {
-        if self.command_palette.is_none() {
+    fn require_evolve(&mut self) -> bool {
+        if !self.snapshot.capabilities.has_evolve {
+            self.status_line = "Evolve extension unavailable.".to_string();
             return false;
         }
-        match key.code {
-            KeyCode::Esc => {
-                self.command_palette = None;
-                self.status_line = "Custom command selection cancelled.".to_string();
-            }
-            KeyCode::Down | KeyCode::Char('j') => {
-                let len = self.config.custom_commands.len();
-                if len > 0
-                    && let Some(palette) = self.command_palette.as_mut()
-                {
-                    palette.selected = (palette.selected + 1).min(len - 1);
-                }
-            }
-            KeyCode::Up | KeyCode::Char('k') => {
-                if let Some(palette) = self.command_palette.as_mut()
-                    && palette.selected > 0
-                {
-                    palette.selected -= 1;
-                }
-            }
-            KeyCode::Enter => self.run_selected_custom_command(),
-            _ => {}
-        }
         true
     }
 
-    fn run_selected_custom_command(&mut self) {
-        let selected = self
-            .command_palette
-            .as_ref()
-            .map(|palette| palette.selected)
-            .unwrap_or(0);
-        let Some(command) = self.config.custom_commands.get(selected).cloned() else {
-            self.status_line = "No custom command selected.".to_string();
-            self.command_palette = None;
+    fn prune_selected(&mut self) {
+        if !self.require_evolve() {
+            return;
+        }
+        let Some(rev) = self.selected_revision() else {
+            self.status_line = "No revision selected for prune.".to_string();
             return;
         };
-        self.command_palette = None;
-
-        match self.prepare_custom_run_action(&command) {
-            Ok(custom_action) => {
-                let preview = custom_action.invocation.command_preview();
-                let title = custom_action.title.clone();
-                let pending = PendingRunAction::Custom(custom_action);
-                if command.needs_confirmation {
-                    self.confirm_action(
-                        pending,
-                        format!("Run custom command '{}'?\n{}", title, preview),
-                    );
-                } else {
-                    self.run_pending_action(pending);
-                }
-            }
-            Err(err) => {
-                self.status_line = "Custom command not runnable.".to_string();
-                self.append_log(format!("Custom command '{}' failed: {err}", command.id));
-                self.set_detail_text(err);
-            }
+        let rev_num = rev.rev;
+        if self.public_revision_guard(rev_num, "prune") {
+            return;
         }
+        self.confirm_action(
+            PendingRunAction::Hg(HgAction::Prune { rev: rev_num }),
+            format!("Prune revision {rev_num}? It is marked obsolete and hidden from the log."),
+        );
     }
 
-    fn prepare_custom_run_action(
-        &self,
-        command: &CustomCommand,
-    ) -> Result<CustomRunAction, String> {
-        let template_vars = self.custom_template_vars(command)?;
-        let (program_raw, base_args_raw) = parse_command_parts(&command.command)?;
-        let mut unresolved = unresolved_template_vars(&program_raw, &template_vars);
-        for raw in base_args_raw
-            .iter()
-            .chain(command.args.iter())
-            .chain(command.env.values())
-        {
-            for name in unresolved_template_vars(raw, &template_vars) {
-                if !unresolved.contains(&name) {
-                    unresolved.push(name);
-                }
-            }
+    fn show_obslog_for_selection(&mut self) {
+        if !self.require_evolve() {
+            return;
         }
-        if !unresolved.is_empty() {
-            unresolved.sort();
-            return Err(format!(
-                "custom command '{}' requires unavailable template vars: {}",
-                command.id,
-                unresolved.join(", ")
-            ));
+        let Some(rev) = self.selected_revision() else {
+            self.status_line = "No revision selected for obslog.".to_string();
+            return;
+        };
+        let rev_num = rev.rev;
+        self.details_scroll = 0;
+        let request_id = self.detail_request_id.wrapping_add(1);
+        self.detail_request_id = request_id;
+        let tx = self.event_tx.clone();
+        let hg = Arc::clone(&self.hg);
+        tokio::spawn(async move {
+            let result = hg.obslog(rev_num).await.map_err(|err| err.to_string());
+            let _ = tx.send(AppEvent::DetailLoaded { request_id, result });
+        });
+        self.status_line = format!("Loading obsolescence history for revision {rev_num}…");
+    }
+
+    fn require_topic(&mut self) -> bool {
+        if !self.snapshot.capabilities.has_topic {
+            self.status_line = "Topic extension unavailable.".to_string();
+            return false;
         }
-        let program = render_template(&program_raw, &template_vars);
-        if program.trim().is_empty() {
-            return Err(format!(
-                "custom command '{}' resolved to empty program",
-                command.id
-            ));
+        true
+    }
+
+    fn show_topics_list(&mut self) {
+        if !self.require_topic() {
+            return;
         }
+        self.details_scroll = 0;
+        let request_id = self.detail_request_id.wrapping_add(1);
+        self.detail_request_id = request_id;
+        let tx = self.event_tx.clone();
+        let hg = Arc::clone(&self.hg);
+        tokio::spawn(async move {
+            let result = hg.topics_list().await.map_err(|err| err.to_string());
+            let _ = tx.send(AppEvent::DetailLoaded { request_id, result });
+        });
+        self.status_line = "Loading topics…".to_string();
+    }
 
-        let mut args = base_args_raw
+    fn jump_to_selected_ref_revision(&mut self) {
+        let target = match self.focus {
+            FocusPanel::Tags => self
+                .snapshot
+                .tags
+                .get(self.tags_idx)
+                .map(|tag| (format!("tag '{}'", tag.name), tag.rev)),
+            FocusPanel::Bookmarks => self
+                .snapshot
+                .bookmarks
+                .get(self.bookmarks_idx)
+                .map(|bookmark| (format!("bookmark '{}'", bookmark.name), bookmark.rev)),
+            FocusPanel::Branches => self
+                .snapshot
+                .branches
+                .get(self.branches_idx)
+                .map(|branch| (format!("branch '{}'", branch.name), branch.rev)),
+            _ => {
+                self.status_line =
+                    "Jump works from the Tags, Bookmarks or Branches panel.".to_string();
+                return;
+            }
+        };
+        let Some((label, rev)) = target else {
+            self.status_line = "Nothing selected to jump from.".to_string();
+            return;
+        };
+        let Some(idx) = self
+            .snapshot
+            .revisions
             .iter()
-            .map(|arg| render_template(arg, &template_vars))
-            .collect::<Vec<_>>();
-        args.extend(
-            command
-                .args
-                .iter()
-                .map(|arg| render_template(arg, &template_vars)),
-        );
+            .position(|candidate| candidate.rev == rev)
+        else {
+            self.status_line = format!("Revision {rev} ({label}) is not in the loaded history.");
+            return;
+        };
+        self.focus = FocusPanel::Revisions;
+        self.rev_idx = idx;
+        self.ensure_visible(FocusPanel::Revisions);
+        self.status_line = format!("Jumped to revision {rev} ({label}).");
+        self.refresh_detail_for_focus();
+    }
 
-        let env = command
-            .env
-            .iter()
-            .map(|(key, value)| (key.clone(), render_template(value, &template_vars)))
-            .collect::<Vec<_>>();
+    fn create_tag_on_selected_revision(&mut self, local: bool) {
+        let Some(rev) = self.selected_revision().map(|rev| rev.rev) else {
+            self.status_line = "No revision selected to tag.".to_string();
+            return;
+        };
+        let title = if local {
+            format!("Local tag name for revision {rev} (hg tag -l)")
+        } else {
+            format!("Tag name for revision {rev} (hg tag, commits .hgtags)")
+        };
+        self.open_input(InputPurpose::TagName { rev, local }, title);
+    }
 
-        Ok(CustomRunAction {
-            title: command.title.clone(),
-            show_output: command.show_output,
-            invocation: CustomInvocation { program, args, env },
-        })
+    fn remove_selected_tag(&mut self) {
+        let Some(tag) = self.snapshot.tags.get(self.tags_idx) else {
+            self.status_line = "No tag selected.".to_string();
+            return;
+        };
+        if tag.name == "tip" {
+            self.status_line = "'tip' is not a removable tag.".to_string();
+            return;
+        }
+        let message = if tag.local {
+            format!("Remove local tag '{}'?", tag.name)
+        } else {
+            format!(
+                "Remove tag '{}'? Regular tags live in .hgtags, so removing one creates a new commit.",
+                tag.name
+            )
+        };
+        self.confirm_action(
+            PendingRunAction::Hg(HgAction::TagRemove {
+                name: tag.name.clone(),
+                local: tag.local,
+            }),
+            message,
+        );
     }
 
-    fn custom_template_vars(
-        &self,
-        command: &CustomCommand,
-    ) -> Result<std::collections::HashMap<&'static str, String>, String> {
-        let mut vars = std::collections::HashMap::new();
-        let repo_root = self
-            .snapshot
-            .repo_root
-            .clone()
-            .ok_or_else(|| "repository root unavailable".to_string())?;
-        vars.insert("repo_root", repo_root);
-        vars.insert(
-            "branch",
-            self.snapshot
-                .branch
-                .clone()
-                .unwrap_or_else(|| "unknown".to_string()),
+    fn delete_selected_bookmark(&mut self) {
+        let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) else {
+            self.status_line = "No bookmark selected.".to_string();
+            return;
+        };
+        let name = bookmark.name.clone();
+        self.confirm_action(
+            PendingRunAction::Hg(HgAction::BookmarkDelete { name: name.clone() }),
+            format!(
+                "Delete bookmark '{name}'? The revision it points to (@{}) is kept.",
+                bookmark.rev
+            ),
         );
+    }
 
-        match command.context {
-            CommandContext::Repo => {}
-            CommandContext::File => {
-                let file = self
-                    .snapshot
-                    .files
-                    .get(self.files_idx)
-                    .ok_or_else(|| "file-context command requires selected file".to_string())?;
-                vars.insert("file", file.path.clone());
-            }
-            CommandContext::Revision => {
-                let rev = self.snapshot.revisions.get(self.rev_idx).ok_or_else(|| {
-                    "revision-context command requires selected revision".to_string()
-                })?;
-                vars.insert("rev", rev.rev.to_string());
-                vars.insert("node", rev.node.clone());
-            }
-        }
+    fn rename_selected_bookmark(&mut self) {
+        let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) else {
+            self.status_line = "No bookmark selected.".to_string();
+            return;
+        };
+        let old = bookmark.name.clone();
+        self.open_input_with_value(
+            InputPurpose::BookmarkRename { old: old.clone() },
+            format!("Rename bookmark '{old}'"),
+            old,
+        );
+    }
 
-        if let Some(file) = self.snapshot.files.get(self.files_idx) {
-            vars.entry("file").or_insert_with(|| file.path.clone());
-        }
-        if let Some(rev) = self.snapshot.revisions.get(self.rev_idx) {
-            vars.entry("rev").or_insert_with(|| rev.rev.to_string());
-            vars.entry("node").or_insert_with(|| rev.node.clone());
+    fn move_selected_bookmark(&mut self) {
+        let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) else {
+            self.status_line = "No bookmark selected.".to_string();
+            return;
+        };
+        let Some(rev) = self.snapshot.revisions.get(self.rev_idx) else {
+            self.status_line = "No revision selected to move the bookmark to.".to_string();
+            return;
+        };
+        if bookmark.rev == rev.rev {
+            self.status_line = format!(
+                "Bookmark '{}' already points to revision {}.",
+                bookmark.name, rev.rev
+            );
+            return;
         }
-
-        Ok(vars)
+        let name = bookmark.name.clone();
+        let (from, to) = (bookmark.rev, rev.rev);
+        self.confirm_action(
+            PendingRunAction::Hg(HgAction::BookmarkMove {
+                name: name.clone(),
+                rev: to,
+            }),
+            format!("Force-move bookmark '{name}' from revision {from} to revision {to}?"),
+        );
     }
 
-    fn handle_confirmation_key(&mut self, key: KeyEvent) -> bool {
-        if self.confirmation.is_none() {
-            return false;
-        }
-        match key.code {
-            KeyCode::Enter | KeyCode::Char('y') => {
-                if let Some(confirm) = self.confirmation.take() {
-                    self.run_pending_action(confirm.action);
+    fn mark_selected_conflict(&mut self, resolved: bool) {
+        if let Some(conflict) = self.snapshot.conflicts.get(self.conflicts_idx) {
+            let action = if resolved {
+                HgAction::ResolveMark {
+                    path: conflict.path.clone(),
                 }
-            }
-            KeyCode::Esc | KeyCode::Char('n') => {
-                self.confirmation = None;
-                self.status_line = "Action cancelled.".to_string();
-            }
-            _ => {}
+            } else {
+                HgAction::ResolveUnmark {
+                    path: conflict.path.clone(),
+                }
+            };
+            self.run_hg_action(action);
+        } else {
+            self.status_line = "No conflict selected.".to_string();
         }
-        true
     }
 
-    fn handle_input_key(&mut self, key: KeyEvent) -> bool {
-        if self.input.is_none() {
-            return false;
+    fn unshelve_selected(&mut self) {
+        if let Some(shelf) = self.snapshot.shelves.get(self.shelves_idx) {
+            let message = if self.shelve_keep {
+                format!(
+                    "Unshelve '{}' with --keep? This applies shelved changes and keeps the shelf.",
+                    shelf.name
+                )
+            } else {
+                format!("Unshelve '{}'? This applies shelved changes.", shelf.name)
+            };
+            self.confirm_action(
+                PendingRunAction::Hg(HgAction::Unshelve {
+                    name: shelf.name.clone(),
+                    keep: self.shelve_keep,
+                }),
+                message,
+            );
+        } else {
+            self.status_line = "No shelf selected.".to_string();
         }
+    }
 
-        let mut submit: Option<InputState> = None;
-        if let Some(input) = self.input.as_mut() {
-            match key.code {
-                KeyCode::Esc => {
-                    self.input = None;
-                    self.status_line = "Input cancelled.".to_string();
-                }
-                KeyCode::Enter => {
-                    submit = self.input.clone();
-                }
-                KeyCode::Backspace => {
-                    input.value.pop();
-                }
-                KeyCode::Char(c) => {
-                    if !key.modifiers.contains(KeyModifiers::CONTROL) {
-                        input.value.push(c);
-                    }
-                }
-                _ => {}
-            }
+    fn delete_selected_shelf(&mut self) {
+        if let Some(shelf) = self.snapshot.shelves.get(self.shelves_idx) {
+            self.confirm_action(
+                PendingRunAction::Hg(HgAction::ShelveDelete {
+                    name: shelf.name.clone(),
+                }),
+                format!(
+                    "Delete shelf '{}'? Its changes are discarded permanently.",
+                    shelf.name
+                ),
+            );
+        } else {
+            self.status_line = "No shelf selected.".to_string();
         }
+    }
 
-        if let Some(input) = submit {
-            let value = input.value.trim();
-            if value.is_empty() {
-                self.status_line = "Input cannot be empty.".to_string();
-                return true;
+    fn update_action_for_selection(&mut self) {
+        match self.focus {
+            FocusPanel::Bookmarks => {
+                if let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) {
+                    self.confirm_action(
+                        PendingRunAction::Hg(HgAction::UpdateToBookmark {
+                            name: bookmark.name.clone(),
+                        }),
+                        format!("Update working directory to bookmark '{}'?", bookmark.name),
+                    );
+                } else {
+                    self.status_line = "No bookmark selected.".to_string();
+                }
             }
-            self.input = None;
-            match input.purpose {
-                InputPurpose::CommitMessage => {
-                    let files = self
-                        .commit_file_selection
-                        .iter()
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    self.run_hg_action(HgAction::Commit {
-                        message: value.to_string(),
-                        files,
-                    });
+            FocusPanel::Branches => {
+                if let Some(branch) = self.snapshot.branches.get(self.branches_idx) {
+                    let message = if branch.closed {
+                        format!(
+                            "Update working directory to closed branch '{}' (head {})?",
+                            branch.name, branch.rev
+                        )
+                    } else {
+                        format!(
+                            "Update working directory to the head of branch '{}' (revision {})?",
+                            branch.name, branch.rev
+                        )
+                    };
+                    self.confirm_action(
+                        PendingRunAction::Hg(HgAction::UpdateToBranch {
+                            name: branch.name.clone(),
+                        }),
+                        message,
+                    );
+                } else {
+                    self.status_line = "No branch selected.".to_string();
                 }
-                InputPurpose::CommitMessageInteractive => {
-                    let files = self
-                        .commit_file_selection
-                        .iter()
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    self.interactive_commit_request = Some(InteractiveCommitRequest {
-                        message: value.to_string(),
-                        files,
-                    });
-                    self.status_line =
-                        "Launching interactive commit; complete prompts in terminal.".to_string();
+            }
+            _ => {
+                if let Some(rev) = self.snapshot.revisions.get(self.rev_idx) {
+                    self.confirm_action(
+                        PendingRunAction::Hg(HgAction::UpdateToRevision { rev: rev.rev }),
+                        format!("Update working directory to revision {}?", rev.rev),
+                    );
+                } else {
+                    self.status_line = "No revision selected.".to_string();
                 }
-                InputPurpose::BookmarkName => self.run_hg_action(HgAction::BookmarkCreate {
-                    name: value.to_string(),
-                }),
-                InputPurpose::ShelveName => self.run_hg_action(HgAction::ShelveCreate {
-                    name: value.to_string(),
-                }),
             }
         }
-        true
     }
-}
 
-fn collect_command_output(result: &CommandResult) -> String {
-    let mut sections = Vec::new();
-    if !result.stdout.trim().is_empty() {
-        sections.push(format!("stdout:\n{}", result.stdout.trim_end()));
-    }
-    if !result.stderr.trim().is_empty() {
-        sections.push(format!("stderr:\n{}", result.stderr.trim_end()));
+    fn cycle_focus(&mut self, forward: bool) {
+        let panels = FocusPanel::all();
+        let pos = panels
+            .iter()
+            .position(|panel| *panel == self.focus)
+            .unwrap_or(0);
+        let next = if forward {
+            (pos + 1) % panels.len()
+        } else {
+            (pos + panels.len() - 1) % panels.len()
+        };
+        self.focus = panels[next];
+        self.refresh_detail_for_focus();
     }
-    sections.join("\n\n")
-}
 
-fn rebase_unavailable_help_text() -> String {
-    "Rebase is unavailable in this repository.\n\nEnable the Mercurial rebase extension in your hgrc:\n[extensions]\nrebase =\n\nThen refresh the snapshot and try rebase again.".to_string()
-}
+    fn move_selection(&mut self, delta: isize) {
+        if self.focus == FocusPanel::Log {
+            let len = self.visible_log_entries().len();
+            if len == 0 {
+                self.log_idx = 0;
+                return;
+            }
+            let current = self.log_idx as isize;
+            let next = (current + delta).clamp(0, (len - 1) as isize);
+            self.log_idx = next as usize;
+            self.refresh_detail_for_focus();
+            return;
+        }
 
-fn no_rebase_in_progress_help_text() -> String {
-    "No rebase is currently in progress.\n\nStart a rebase with the rebase picker (`r`) from the Commits panel, then use continue/abort actions as needed.".to_string()
-}
+        let len = self.panel_len(self.focus);
+        if len == 0 {
+            self.set_panel_index(self.focus, 0);
+            self.set_panel_offset(self.focus, 0);
+            return;
+        }
 
-fn rebase_continue_blocked_help_text(
-    unresolved: usize,
-    resolve_mark_key: &str,
-    continue_key: &str,
-    abort_key: &str,
-) -> String {
-    format!(
-        "Rebase continue is blocked.\n\n{unresolved} unresolved conflict(s) remain.\n\nResolve conflicts in the Conflicts panel (mark resolved with `{resolve_mark_key}`), then press `{continue_key}`.\nUse `{abort_key}` to abort the rebase."
-    )
-}
+        let current = self.panel_index(self.focus) as isize;
+        let next = (current + delta).clamp(0, (len - 1) as isize) as usize;
+        self.set_panel_index(self.focus, next);
+        self.ensure_visible(self.focus);
+        if matches!(
+            self.focus,
+            FocusPanel::Files
+                | FocusPanel::Revisions
+                | FocusPanel::Bookmarks
+                | FocusPanel::Shelves
+                | FocusPanel::Conflicts
+                | FocusPanel::Remote
+        ) {
+            self.refresh_detail_for_focus();
+        }
+    }
 
-fn rect_contains(rect: ratatui::layout::Rect, x: u16, y: u16) -> bool {
-    let x_end = rect.x.saturating_add(rect.width);
-    let y_end = rect.y.saturating_add(rect.height);
-    x >= rect.x && x < x_end && y >= rect.y && y < y_end
-}
+    fn remembered_path(&self, direction: RemoteDirection) -> Option<String> {
+        let repo_root = self.snapshot.repo_root.as_deref()?;
+        let name = self.remote_defaults.get(repo_root, direction)?;
+        self.snapshot
+            .paths
+            .iter()
+            .any(|path| path.name == name)
+            .then(|| name.to_string())
+    }
 
-fn help_text(
-    keymap: &ActionKeyMap,
-    caps: &crate::domain::HgCapabilities,
-    has_custom_commands: bool,
-) -> String {
-    let key = |action: ActionId| keymap.key_for_action(action).unwrap_or("?");
-    let mut text = vec![
-        format!(
-            "Keys: {} quit | {} focus+ | {} focus- | {} down | {} up | {} refresh | {} reload diff",
-            key(ActionId::Quit),
-            key(ActionId::FocusNext),
-            key(ActionId::FocusPrev),
-            key(ActionId::MoveDown),
-            key(ActionId::MoveUp),
-            key(ActionId::RefreshSnapshot),
-            key(ActionId::RefreshDetails),
-        ),
-        format!(
-            "Actions: {} pick file | {} clear picks | {} commit | {} interactive commit | {} bookmark | {} update | {} push(confirm) | {} pull",
-            key(ActionId::ToggleFileForCommit),
-            key(ActionId::ClearFileSelection),
-            key(ActionId::Commit),
-            key(ActionId::CommitInteractive),
-            key(ActionId::Bookmark),
-            key(ActionId::UpdateSelected),
-            key(ActionId::Push),
-            key(ActionId::Pull),
-        ),
-        format!(
-            "Remote: {} incoming | {} outgoing",
-            key(ActionId::Incoming),
-            key(ActionId::Outgoing),
-        ),
-        format!(
-            "Shelves: {} create shelf | {} unshelve selected shelf",
-            key(ActionId::Shelve),
-            key(ActionId::UnshelveSelected),
-        ),
-        format!(
-            "Conflicts: {} mark resolved | {} mark unresolved",
-            key(ActionId::ResolveMark),
-            key(ActionId::ResolveUnmark),
-        ),
-        "Mouse: click focus/select | wheel scroll hovered panel or Details (fallback: focused panel) | double-click files/commits loads details".to_string(),
-    ];
-    if caps.has_rebase {
-        text.push(format!(
-            "History: {} rebase picker | {} rebase --continue (only when rebase is active and conflicts are resolved) | {} rebase --abort",
-            key(ActionId::RebaseSelected),
-            key(ActionId::RebaseContinue),
-            key(ActionId::RebaseAbort)
-        ));
+    pub fn remote_revisions(&self) -> &[Revision] {
+        let changes = match self.remote_view {
+            RemoteDirection::Pull => &self.incoming_changes,
+            RemoteDirection::Push => &self.outgoing_changes,
+        };
+        changes.as_deref().unwrap_or(&[])
     }
-    if caps.has_histedit {
-        text.push(format!(
-            "History: {} histedit from selected revision",
-            key(ActionId::HisteditSelected)
-        ));
+
+    // Commits, pulls, pushes and rewrites all change what incoming/outgoing
+    // would list, so the lists (and the header counts) are dropped until
+    // they are loaded again.
+    fn clear_remote_changes(&mut self) {
+        self.incoming_changes = None;
+        self.outgoing_changes = None;
+        self.remote_patches.clear();
     }
-    if has_custom_commands {
-        text.push(format!(
-            "Custom: {} open command palette",
-            key(ActionId::OpenCustomCommands)
-        ));
+
+    fn load_remote_changes(&mut self, direction: RemoteDirection) {
+        let path = self.remembered_path(direction);
+        let tx = self.event_tx.clone();
+        let hg = Arc::clone(&self.hg);
+        self.status_line = format!(
+            "Loading {} changesets{}…",
+            remote_direction_label(direction),
+            path.as_deref()
+                .map(|path| format!(" ({path})"))
+                .unwrap_or_default()
+        );
+        let label = format!("hg {}", remote_command(direction));
+        self.jobs
+            .spawn(label, JobKind::Read, tx.clone(), |_| async move {
+                let result = hg
+                    .remote_changesets(direction, path.as_deref())
+                    .await
+                    .map_err(|err| err.to_string());
+                let _ = tx.send(AppEvent::RemoteChangesLoaded {
+                    direction,
+                    path,
+                    result,
+                });
+            });
     }
-    text.join(" | ")
-}
 
-pub async fn run_app(config: AppConfig, startup_issues: Vec<String>) -> Result<()> {
-    let mut app = App::new_with_startup_issues(config, startup_issues)?;
-    app.run().await
-}
+    fn apply_remote_changes(
+        &mut self,
+        direction: RemoteDirection,
+        path: Option<String>,
+        result: Result<Vec<Revision>, String>,
+    ) {
+        let label = remote_direction_label(direction);
+        let revisions = match result {
+            Ok(revisions) => revisions,
+            Err(err) => {
+                self.status_line = format!("Loading {label} changesets failed.");
+                self.append_log(format!("ERROR: {}", err.trim()));
+                self.set_detail_text(err);
+                return;
+            }
+        };
+        let count = revisions.len();
+        match direction {
+            RemoteDirection::Pull => self.incoming_changes = Some(revisions),
+            RemoteDirection::Push => self.outgoing_changes = Some(revisions),
+        }
+        let source = path.as_deref().unwrap_or("default").to_string();
+        self.remote_view = direction;
+        self.remote_view_path = path;
+        self.remote_idx = 0;
+        self.remote_offset = 0;
+        self.focus = FocusPanel::Remote;
+        self.status_line = format!("{count} {label} changeset(s) ({source}).");
+        self.append_log(format!("{label}: {count} changeset(s) ({source})"));
+        if count == 0 {
+            self.set_detail_text(format!("No {label} changesets."));
+        } else {
+            self.refresh_detail_for_focus();
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::{AppConfig, CommandContext, CustomCommand};
-    use ratatui::layout::Rect;
-    use std::collections::HashMap;
-    use std::fs;
-    use std::path::{Path, PathBuf};
-    use std::process::Command;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    fn cycle_pull_mode(&mut self) {
+        self.pull_mode = match self.pull_mode {
+            PullMode::Update => PullMode::NoUpdate,
+            PullMode::NoUpdate if self.snapshot.capabilities.has_rebase => PullMode::Rebase,
+            PullMode::NoUpdate | PullMode::Rebase => PullMode::Update,
+        };
+        self.status_line = format!("Pull mode: {}", self.pull_mode.as_str());
+    }
 
-    fn make_app() -> App {
-        let mut app = App::new(AppConfig::default()).expect("app");
-        app.ui_rects = ui::UiRects {
-            header: Rect::new(0, 0, 100, 2),
-            footer: Rect::new(0, 29, 100, 1),
-            files: Rect::new(0, 2, 58, 12),
-            details: Rect::new(0, 14, 58, 15),
-            revisions: Rect::new(58, 2, 42, 10),
-            bookmarks: Rect::new(58, 12, 42, 5),
-            shelves: Rect::new(58, 17, 21, 5),
-            conflicts: Rect::new(79, 17, 21, 5),
-            log: Rect::new(58, 22, 42, 7),
+    fn push_selected_revision(&mut self) {
+        let Some(rev) = self.selected_revision() else {
+            self.status_line = "No revision selected to push.".to_string();
+            return;
         };
-        app
+        if rev.phase == Phase::Secret.as_str() {
+            self.status_line = format!(
+                "Revision {} is secret and would not be pushed. Move it to draft ({}) first.",
+                rev.rev,
+                self.key_for_action(ActionId::PhaseDraft)
+            );
+            return;
+        }
+        let scope = SyncScope::Rev(rev.rev.to_string());
+        self.open_path_picker(RemoteDirection::Push, scope);
     }
 
-    fn revision_fixture(rev: i64) -> crate::domain::Revision {
-        crate::domain::Revision {
-            rev,
-            node: format!("node-{rev}"),
-            desc: format!("desc-{rev}"),
-            user: "u".to_string(),
-            branch: "default".to_string(),
-            phase: "draft".to_string(),
-            tags: Vec::new(),
+    fn push_selected_bookmark(&mut self) {
+        let Some(bookmark) = self.snapshot.bookmarks.get(self.bookmarks_idx) else {
+            self.status_line = "No bookmark selected to push.".to_string();
+            return;
+        };
+        let scope = SyncScope::Bookmark(bookmark.name.clone());
+        self.open_path_picker(RemoteDirection::Push, scope);
+    }
+
+    fn push_branch(&mut self) {
+        let branch = if self.focus == FocusPanel::Branches {
+            self.snapshot
+                .branches
+                .get(self.branches_idx)
+                .map(|branch| branch.name.clone())
+        } else {
+            self.snapshot.branch.clone()
+        };
+        let Some(branch) = branch.filter(|name| !name.is_empty()) else {
+            self.status_line = "No branch to push.".to_string();
+            return;
+        };
+        self.open_path_picker(RemoteDirection::Push, SyncScope::Branch(branch));
+    }
+
+    fn open_path_picker(&mut self, direction: RemoteDirection, scope: SyncScope) {
+        if self.snapshot.paths.is_empty() {
+            self.run_sync_to_path(direction, scope, None);
+            return;
+        }
+        let preferred = self.remembered_path(direction).or_else(|| {
+            let fallback = match direction {
+                RemoteDirection::Pull => "default",
+                RemoteDirection::Push => "default-push",
+            };
+            self.snapshot
+                .paths
+                .iter()
+                .find(|path| path.name == fallback)
+                .or_else(|| {
+                    self.snapshot
+                        .paths
+                        .iter()
+                        .find(|path| path.name == "default")
+                })
+                .map(|path| path.name.clone())
+        });
+        let selected = preferred
+            .and_then(|name| {
+                self.snapshot
+                    .paths
+                    .iter()
+                    .position(|path| path.name == name)
+            })
+            .unwrap_or(0);
+        self.path_picker = Some(PathPickerState {
+            direction,
+            scope,
+            selected,
+        });
+        self.status_line = "Choose a path: Enter select | Esc cancel.".to_string();
+    }
+
+    fn handle_path_picker_key(&mut self, key: KeyEvent) -> bool {
+        let Some(picker) = self.path_picker.as_mut() else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.path_picker = None;
+                self.status_line = "Path selection cancelled.".to_string();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                picker.selected =
+                    (picker.selected + 1).min(self.snapshot.paths.len().saturating_sub(1));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                picker.selected = picker.selected.saturating_sub(1);
+            }
+            KeyCode::Enter => {
+                let direction = picker.direction;
+                let scope = picker.scope.clone();
+                let selected = picker.selected;
+                self.path_picker = None;
+                let Some(path) = self.snapshot.paths.get(selected).cloned() else {
+                    self.status_line = "No path selected.".to_string();
+                    return true;
+                };
+                if let Some(repo_root) = self.snapshot.repo_root.clone()
+                    && let Err(err) = self.remote_defaults.set(&repo_root, direction, &path.name)
+                {
+                    self.append_log(format!("Could not remember path: {err}"));
+                }
+                self.run_sync_to_path(direction, scope, Some(path));
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn run_sync_to_path(
+        &mut self,
+        direction: RemoteDirection,
+        scope: SyncScope,
+        path: Option<HgPath>,
+    ) {
+        match direction {
+            RemoteDirection::Pull => {
+                let mode = if self.pull_mode == PullMode::Rebase
+                    && !self.snapshot.capabilities.has_rebase
+                {
+                    self.append_log("Rebase extension unavailable; pulling with update instead.");
+                    PullMode::Update
+                } else {
+                    self.pull_mode
+                };
+                self.run_hg_action(HgAction::Pull {
+                    source: path.map(|path| path.name),
+                    mode,
+                    scope,
+                });
+            }
+            RemoteDirection::Push => {
+                let target = match &path {
+                    Some(path) => format!("'{}' ({})", path.name, path.url),
+                    None => "the default path".to_string(),
+                };
+                let dest = path.map(|path| path.name);
+                self.request_push_preview(
+                    HgAction::Push {
+                        dest: dest.clone(),
+                        scope: scope.clone(),
+                        new_branch: false,
+                    },
+                    HgAction::Outgoing { dest, scope },
+                    target,
+                );
+            }
+        }
+    }
+
+    fn request_push_preview(&mut self, push: HgAction, outgoing: HgAction, target: String) {
+        let tx = self.event_tx.clone();
+        let hg = Arc::clone(&self.hg);
+        let label = outgoing.command_preview();
+        self.status_line = format!("Checking outgoing: {label}");
+        self.jobs
+            .spawn(label, JobKind::Read, tx.clone(), |job_id| async move {
+                let output = output_sink(tx.clone(), job_id);
+                let result = hg
+                    .run_action(&outgoing, Some(&output))
+                    .await
+                    .map_err(|err| err.to_string());
+                let changesets = match &push {
+                    HgAction::Push {
+                        dest,
+                        scope: SyncScope::All,
+                        ..
+                    } => hg
+                        .remote_changesets(RemoteDirection::Push, dest.as_deref())
+                        .await
+                        .ok(),
+                    _ => None,
+                };
+                let _ = tx.send(AppEvent::PushPreviewLoaded {
+                    push,
+                    target,
+                    result,
+                    outgoing: changesets,
+                });
+            });
+    }
+
+    fn confirm_push_with_preview(
+        &mut self,
+        push: HgAction,
+        target: String,
+        result: Result<CommandResult, String>,
+        outgoing: Option<Vec<Revision>>,
+    ) {
+        let scope = match &push {
+            HgAction::Push { scope, .. } => scope.describe(),
+            _ => String::new(),
+        };
+        let preview = match result {
+            Ok(out) if out.success => out.stdout.trim().to_string(),
+            Ok(out) if format!("{}\n{}", out.stdout, out.stderr).contains("no changes found") => {
+                self.status_line = format!("Nothing to push: no outgoing changes for {scope}.");
+                return;
+            }
+            Ok(out) => format!(
+                "Outgoing preview failed: {}",
+                first_non_empty_line(&out.stderr)
+                    .or_else(|| first_non_empty_line(&out.stdout))
+                    .unwrap_or("no output")
+            ),
+            Err(err) => format!("Outgoing preview failed: {}", err.trim()),
+        };
+        let mut lines = preview.lines().collect::<Vec<_>>();
+        let hidden = lines.len().saturating_sub(PUSH_PREVIEW_MAX_LINES);
+        lines.truncate(PUSH_PREVIEW_MAX_LINES);
+        let mut message = format!("Push {scope} to {target}?\n\n{}", lines.join("\n"));
+        if hidden > 0 {
+            message.push_str(&format!("\n… {hidden} more line(s)"));
+        }
+        self.status_line = "Review outgoing changesets before pushing.".to_string();
+        let Some(action) = self.intercept_dry_run(PendingRunAction::Hg(push)) else {
+            return;
+        };
+        if let PendingRunAction::Hg(HgAction::Push {
+            scope: SyncScope::All,
+            ..
+        }) = &action
+            && let Some(hit) = self
+                .protection
+                .check_outgoing(outgoing.as_deref(), &self.snapshot)
+        {
+            self.guard_protected(action, hit, Some(message), None);
+            return;
+        }
+        self.confirm_action(action, message);
+    }
+
+    fn open_command_palette(&mut self) {
+        if self.config.custom_commands.is_empty() {
+            self.status_line = "No custom commands configured.".to_string();
+            return;
+        }
+        self.command_palette = Some(CommandPaletteState { selected: 0 });
+        self.status_line = "Custom commands: Enter run | Esc cancel.".to_string();
+    }
+
+    fn toggle_selected_file_for_commit(&mut self) {
+        let Some(file) = self.snapshot.files.get(self.files_idx) else {
+            self.status_line = "No file selected.".to_string();
+            return;
+        };
+        let path = file.path.clone();
+        if self.commit_file_selection.contains(&path) {
+            self.commit_file_selection.remove(&path);
+            self.status_line = format!("Removed from commit selection: {path}");
+        } else {
+            self.commit_file_selection.insert(path.clone());
+            self.status_line = format!("Selected for commit: {path}");
+        }
+    }
+
+    fn clear_file_selection(&mut self) {
+        self.commit_file_selection.clear();
+        self.status_line = "Cleared commit file selection.".to_string();
+    }
+
+    fn handle_command_palette_key(&mut self, key: KeyEvent) -> bool {
+        if self.command_palette.is_none() {
+            return false;
+        }
+        match key.code {
+            KeyCode::Esc => {
+                self.command_palette = None;
+                self.status_line = "Custom command selection cancelled.".to_string();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let len = self.config.custom_commands.len();
+                if len > 0
+                    && let Some(palette) = self.command_palette.as_mut()
+                {
+                    palette.selected = (palette.selected + 1).min(len - 1);
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                if let Some(palette) = self.command_palette.as_mut()
+                    && palette.selected > 0
+                {
+                    palette.selected -= 1;
+                }
+            }
+            KeyCode::Enter => self.run_selected_custom_command(),
+            _ => {}
+        }
+        true
+    }
+
+    fn run_selected_custom_command(&mut self) {
+        let selected = self
+            .command_palette
+            .as_ref()
+            .map(|palette| palette.selected)
+            .unwrap_or(0);
+        let Some(command) = self.config.custom_commands.get(selected).cloned() else {
+            self.status_line = "No custom command selected.".to_string();
+            self.command_palette = None;
+            return;
+        };
+        self.command_palette = None;
+
+        match self.prepare_custom_run_action(&command) {
+            Ok(custom_action) => {
+                let preview = custom_action.invocation.command_preview();
+                let title = custom_action.title.clone();
+                let pending = PendingRunAction::Custom(custom_action);
+                if command.needs_confirmation {
+                    self.confirm_action(
+                        pending,
+                        format!("Run custom command '{}'?\n{}", title, preview),
+                    );
+                } else {
+                    self.run_pending_action(pending);
+                }
+            }
+            Err(err) => {
+                self.status_line = "Custom command not runnable.".to_string();
+                self.append_log(format!("Custom command '{}' failed: {err}", command.id));
+                self.set_detail_text(err);
+            }
+        }
+    }
+
+    fn prepare_custom_run_action(
+        &self,
+        command: &CustomCommand,
+    ) -> Result<CustomRunAction, String> {
+        let template_vars = self.custom_template_vars(command)?;
+        let (program_raw, base_args_raw) = parse_command_parts(&command.command)?;
+        let mut unresolved = unresolved_template_vars(&program_raw, &template_vars);
+        for raw in base_args_raw
+            .iter()
+            .chain(command.args.iter())
+            .chain(command.env.values())
+        {
+            for name in unresolved_template_vars(raw, &template_vars) {
+                if !unresolved.contains(&name) {
+                    unresolved.push(name);
+                }
+            }
+        }
+        if !unresolved.is_empty() {
+            unresolved.sort();
+            return Err(format!(
+                "custom command '{}' requires unavailable template vars: {}",
+                command.id,
+                unresolved.join(", ")
+            ));
+        }
+        let program = render_template(&program_raw, &template_vars);
+        if program.trim().is_empty() {
+            return Err(format!(
+                "custom command '{}' resolved to empty program",
+                command.id
+            ));
+        }
+
+        let mut args = base_args_raw
+            .iter()
+            .map(|arg| render_template(arg, &template_vars))
+            .collect::<Vec<_>>();
+        args.extend(
+            command
+                .args
+                .iter()
+                .map(|arg| render_template(arg, &template_vars)),
+        );
+
+        let env = command
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), render_template(value, &template_vars)))
+            .collect::<Vec<_>>();
+
+        Ok(CustomRunAction {
+            title: command.title.clone(),
+            show_output: command.show_output,
+            invocation: CustomInvocation { program, args, env },
+        })
+    }
+
+    fn custom_template_vars(
+        &self,
+        command: &CustomCommand,
+    ) -> Result<HashMap<&'static str, String>, String> {
+        let mut vars = HashMap::new();
+        let repo_root = self
+            .snapshot
+            .repo_root
+            .clone()
+            .ok_or_else(|| "repository root unavailable".to_string())?;
+        vars.insert("repo_root", repo_root);
+        vars.insert(
+            "branch",
+            self.snapshot
+                .branch
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+        );
+
+        match command.context {
+            CommandContext::Repo => {}
+            CommandContext::File => {
+                let file = self
+                    .snapshot
+                    .files
+                    .get(self.files_idx)
+                    .ok_or_else(|| "file-context command requires selected file".to_string())?;
+                vars.insert("file", file.path.to_string());
+            }
+            CommandContext::Revision => {
+                let rev = self.snapshot.revisions.get(self.rev_idx).ok_or_else(|| {
+                    "revision-context command requires selected revision".to_string()
+                })?;
+                vars.insert("rev", rev.rev.to_string());
+                vars.insert("node", rev.node.clone());
+            }
+        }
+
+        if let Some(file) = self.snapshot.files.get(self.files_idx) {
+            vars.entry("file").or_insert_with(|| file.path.to_string());
+        }
+        if let Some(rev) = self.snapshot.revisions.get(self.rev_idx) {
+            vars.entry("rev").or_insert_with(|| rev.rev.to_string());
+            vars.entry("node").or_insert_with(|| rev.node.clone());
+        }
+
+        Ok(vars)
+    }
+
+    fn handle_confirmation_key(&mut self, key: KeyEvent) -> bool {
+        let Some(confirm) = self.confirmation.as_mut() else {
+            return false;
+        };
+        if let Some(typed) = confirm.typed.as_mut() {
+            match key.code {
+                KeyCode::Esc => {
+                    self.confirmation = None;
+                    self.status_line = "Action cancelled.".to_string();
+                }
+                KeyCode::Backspace => {
+                    typed.value.pop();
+                }
+                KeyCode::Char(ch) => typed.value.push(ch),
+                KeyCode::Enter if typed.value == typed.expected => {
+                    if let Some(confirm) = self.confirmation.take() {
+                        if let Some(follow_up) = confirm.follow_up {
+                            self.ask_confirmation(confirm.action, follow_up, None);
+                            self.status_line = "Confirm once more to proceed.".to_string();
+                        } else {
+                            self.start_pending_action(confirm.action);
+                        }
+                    }
+                }
+                KeyCode::Enter => {
+                    self.status_line = format!("Type `{}` exactly to confirm.", typed.expected);
+                }
+                _ => {}
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => {
+                if let Some(confirm) = self.confirmation.take() {
+                    if let Some(follow_up) = confirm.follow_up {
+                        self.ask_confirmation(confirm.action, follow_up, None);
+                        self.status_line = "Confirm once more to proceed.".to_string();
+                    } else {
+                        // confirm_action already applied the protection and
+                        // confirmation policies.
+                        self.start_pending_action(confirm.action);
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('n') => {
+                self.confirmation = None;
+                self.status_line = "Action cancelled.".to_string();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) -> bool {
+        if self.input.is_none() {
+            return false;
+        }
+
+        let mut submit: Option<InputState> = None;
+        if let Some(input) = self.input.as_mut() {
+            match key.code {
+                KeyCode::Esc => {
+                    self.input = None;
+                    self.status_line = "Input cancelled.".to_string();
+                }
+                KeyCode::Enter => {
+                    submit = self.input.clone();
+                }
+                KeyCode::Backspace => {
+                    input.value.pop();
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    input.value.push(c);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(input) = submit {
+            let value = input.value.trim();
+            if value.is_empty() {
+                self.status_line = "Input cannot be empty.".to_string();
+                return true;
+            }
+            if matches!(input.purpose, InputPurpose::TopicSwitchName)
+                && !self.snapshot.topics.iter().any(|topic| topic.name == value)
+            {
+                self.status_line = format!("Unknown topic '{value}'.");
+                return true;
+            }
+            self.input = None;
+            match input.purpose {
+                InputPurpose::CommitMessage => {
+                    let files = self
+                        .commit_file_selection
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    self.run_hg_action(HgAction::Commit {
+                        message: value.to_string(),
+                        files,
+                        close_branch: self.close_branch_on_commit,
+                    });
+                }
+                InputPurpose::CommitMessageInteractive => {
+                    let files = self
+                        .commit_file_selection
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    self.run_pending_action(PendingRunAction::Interactive(InteractiveRun::Commit(
+                        InteractiveCommitRequest {
+                            message: value.to_string(),
+                            files,
+                            close_branch: self.close_branch_on_commit,
+                        },
+                    )));
+                }
+                InputPurpose::BookmarkName => self.run_hg_action(HgAction::BookmarkCreate {
+                    name: value.to_string(),
+                }),
+                InputPurpose::BranchName => self.run_hg_action(HgAction::BranchCreate {
+                    name: value.to_string(),
+                }),
+                InputPurpose::ExportLogPath => self.export_log(value, false),
+                InputPurpose::ExportLogOverwrite { path } => self.export_log(value, value == path),
+                InputPurpose::PullRevision => {
+                    self.open_path_picker(RemoteDirection::Pull, SyncScope::Rev(value.to_string()))
+                }
+                InputPurpose::PullBookmark => self.open_path_picker(
+                    RemoteDirection::Pull,
+                    SyncScope::Bookmark(value.to_string()),
+                ),
+                InputPurpose::TagName { rev, local } => {
+                    let action = HgAction::TagCreate {
+                        name: value.to_string(),
+                        rev,
+                        local,
+                    };
+                    if local {
+                        self.run_hg_action(action);
+                    } else {
+                        self.confirm_action(
+                            PendingRunAction::Hg(action),
+                            format!(
+                                "Tag revision {rev} as '{value}'? Regular tags are recorded in .hgtags and create a new commit on the current branch."
+                            ),
+                        );
+                    }
+                }
+                InputPurpose::BookmarkRename { old } => {
+                    if old == value {
+                        self.status_line = "Bookmark name unchanged.".to_string();
+                    } else {
+                        self.run_hg_action(HgAction::BookmarkRename {
+                            old,
+                            new: value.to_string(),
+                        });
+                    }
+                }
+                InputPurpose::ShelveName => self.run_hg_action(HgAction::ShelveCreate {
+                    name: value.to_string(),
+                    files: self.commit_file_selection.iter().cloned().collect(),
+                    keep: self.shelve_keep,
+                }),
+                InputPurpose::TopicName => self.run_hg_action(HgAction::TopicSet {
+                    name: value.to_string(),
+                }),
+                InputPurpose::TopicSwitchName => self.run_hg_action(HgAction::UpdateToTopic {
+                    name: value.to_string(),
+                }),
+                InputPurpose::BackoutMessage { rev, merge } => {
+                    self.run_hg_action(HgAction::Backout {
+                        rev,
+                        message: value.to_string(),
+                        merge,
+                    })
+                }
+            }
+        }
+        true
+    }
+}
+
+const PUSH_PREVIEW_MAX_LINES: usize = 20;
+
+pub fn remote_direction_label(direction: RemoteDirection) -> &'static str {
+    match direction {
+        RemoteDirection::Pull => "incoming",
+        RemoteDirection::Push => "outgoing",
+    }
+}
+
+fn first_non_empty_line(text: &str) -> Option<&str> {
+    text.lines().map(str::trim).find(|line| !line.is_empty())
+}
+
+fn collect_command_output(result: &CommandResult) -> String {
+    let mut sections = Vec::new();
+    if !result.stdout.trim().is_empty() {
+        sections.push(format!("stdout:\n{}", result.stdout.trim_end()));
+    }
+    if !result.stderr.trim().is_empty() {
+        sections.push(format!("stderr:\n{}", result.stderr.trim_end()));
+    }
+    sections.join("\n\n")
+}
+
+fn rebase_unavailable_help_text() -> String {
+    "Rebase is unavailable in this repository.\n\nEnable the Mercurial rebase extension in your hgrc:\n[extensions]\nrebase =\n\nThen refresh the snapshot and try rebase again.".to_string()
+}
+
+fn no_rebase_in_progress_help_text() -> String {
+    "No rebase is currently in progress.\n\nStart a rebase with the rebase picker (`r`) from the Commits panel, then use continue/abort actions as needed.".to_string()
+}
+
+fn rebase_continue_blocked_help_text(
+    unresolved: usize,
+    resolve_mark_key: &str,
+    continue_key: &str,
+    abort_key: &str,
+) -> String {
+    format!(
+        "Rebase continue is blocked.\n\n{unresolved} unresolved conflict(s) remain.\n\nResolve conflicts in the Conflicts panel (mark resolved with `{resolve_mark_key}`), then press `{continue_key}`.\nUse `{abort_key}` to abort the rebase."
+    )
+}
+
+fn killed_write_help_text(label: &str) -> String {
+    format!(
+        "`{label}` was killed while it was changing the repository.\n\nIf hg reports \"abandoned transaction found\", run `hg recover`.\nIf a rebase or histedit was in progress, abort it (`hg rebase --abort` / `hg histedit --abort`) or continue it once conflicts are resolved."
+    )
+}
+
+fn histedit_continue_blocked_help_text(
+    unresolved: usize,
+    resolve_mark_key: &str,
+    continue_key: &str,
+    abort_key: &str,
+) -> String {
+    format!(
+        "Histedit continue is blocked.\n\n{unresolved} unresolved conflict(s) remain.\n\nResolve conflicts in the Conflicts panel (mark resolved with `{resolve_mark_key}`), then press `{continue_key}`.\nUse `{abort_key}` to abort the histedit."
+    )
+}
+
+fn rect_contains(rect: ratatui::layout::Rect, x: u16, y: u16) -> bool {
+    let x_end = rect.x.saturating_add(rect.width);
+    let y_end = rect.y.saturating_add(rect.height);
+    x >= rect.x && x < x_end && y >= rect.y && y < y_end
+}
+
+fn help_text(
+    keymap: &ActionKeyMap,
+    caps: &crate::domain::HgCapabilities,
+    has_custom_commands: bool,
+) -> String {
+    let key = |action: ActionId| keymap.key_for_action(action).unwrap_or("?");
+    let mut text = vec![
+        format!(
+            "Keys: {} quit | {} focus+ | {} focus- | {} down | {} up | {} refresh | {} reload diff",
+            key(ActionId::Quit),
+            key(ActionId::FocusNext),
+            key(ActionId::FocusPrev),
+            key(ActionId::MoveDown),
+            key(ActionId::MoveUp),
+            key(ActionId::RefreshSnapshot),
+            key(ActionId::RefreshDetails),
+        ),
+        format!(
+            "Actions: {} pick file | {} clear picks | {} commit | {} interactive commit | {} bookmark | {} update | {} push(confirm) | {} pull",
+            key(ActionId::ToggleFileForCommit),
+            key(ActionId::ClearFileSelection),
+            key(ActionId::Commit),
+            key(ActionId::CommitInteractive),
+            key(ActionId::Bookmark),
+            key(ActionId::UpdateSelected),
+            key(ActionId::Push),
+            key(ActionId::Pull),
+        ),
+        format!(
+            "Branches: {} update to selected branch head (Branches panel) | {} new branch | {} toggle --close-branch for next commit",
+            key(ActionId::UpdateSelected),
+            key(ActionId::BranchCreate),
+            key(ActionId::ToggleCloseBranch),
+        ),
+        format!(
+            "Tags: {} jump to tag/bookmark/branch revision | {} tag selected revision(confirm, commits) | {} local tag | {} remove selected tag(confirm)",
+            key(ActionId::JumpToRevision),
+            key(ActionId::TagCreate),
+            key(ActionId::TagCreateLocal),
+            key(ActionId::TagRemove),
+        ),
+        format!(
+            "Bookmarks: {} delete(confirm) | {} rename | {} force-move to selected revision(confirm) | {} deactivate",
+            key(ActionId::BookmarkDelete),
+            key(ActionId::BookmarkRename),
+            key(ActionId::BookmarkMove),
+            key(ActionId::BookmarkDeactivate),
+        ),
+        format!(
+            "Jobs: running/queued commands show in the footer with elapsed time; {} cancels the latest (kills the hg process); writes run one at a time, reads run alongside",
+            key(ActionId::CancelJob),
+        ),
+        format!(
+            "Undo: {} previews and reverts the last commit/shelve/update/bookmark change/rebase/histedit/pull (hg undo when the extension is enabled, otherwise backup bundles, strip and bookmark history)",
+            key(ActionId::Undo),
+        ),
+        format!(
+            "Backups: rebase/histedit/prune first bundle the affected changesets (backup_bundles in config) | {} lists easyHg and strip-backup bundles, Enter or {} restores one with hg unbundle",
+            key(ActionId::ShowBackups),
+            key(ActionId::RestoreBackup),
+        ),
+        format!(
+            "Dry run: {} arms a preview for the next action (push shows outgoing, rebase runs --dry-run, commit/shelve show files and diff, backout the reverse diff, update the files that change); nothing is changed",
+            key(ActionId::DryRunNext),
+        ),
+        format!(
+            "Log: {} show failed commands only | {} export log to file | selected entry's full record in Details",
+            key(ActionId::ToggleLogFailures),
+            key(ActionId::ExportLog),
+        ),
+        format!(
+            "Remote: {} incoming | {} outgoing (listed in the Remote panel with per-changeset patches, remembered pull/push path) | pull/push open a path picker and remember the choice per repo | {} pull a revision | {} pull a bookmark | {} cycle pull mode (update/no-update/rebase) | {} push selected revision | {} push selected bookmark | {} push branch (selected in Branches, else current); push confirmation previews exactly what goes out",
+            key(ActionId::Incoming),
+            key(ActionId::Outgoing),
+            key(ActionId::PullRevision),
+            key(ActionId::PullBookmark),
+            key(ActionId::CyclePullMode),
+            key(ActionId::PushRevision),
+            key(ActionId::PushBookmark),
+            key(ActionId::PushBranch),
+        ),
+        format!(
+            "Shelves: {} create shelf (selected files only when picked) | {} unshelve selected shelf | {} delete selected shelf(confirm) | {} toggle --keep | patch preview in Details",
+            key(ActionId::Shelve),
+            key(ActionId::UnshelveSelected),
+            key(ActionId::ShelveDelete),
+            key(ActionId::ToggleShelveKeep),
+        ),
+        format!(
+            "Conflicts: {} mark resolved | {} mark unresolved",
+            key(ActionId::ResolveMark),
+            key(ActionId::ResolveUnmark),
+        ),
+        format!(
+            "History: {} backout selected revision (--merge unless it is the working parent)",
+            key(ActionId::BackoutSelected),
+        ),
+        format!(
+            "Phases: {} secret | {} draft | {} public (moving back toward secret needs --force and two confirmations; public revisions are never rewritten)",
+            key(ActionId::PhaseSecret),
+            key(ActionId::PhaseDraft),
+            key(ActionId::PhasePublic),
+        ),
+        "Mouse: click focus/select | wheel scroll hovered panel or Details (fallback: focused panel) | double-click files/commits loads details".to_string(),
+    ];
+    if caps.has_rebase {
+        text.push(format!(
+            "History: {} rebase picker | {} rebase --continue (only when rebase is active and conflicts are resolved) | {} rebase --abort",
+            key(ActionId::RebaseSelected),
+            key(ActionId::RebaseContinue),
+            key(ActionId::RebaseAbort)
+        ));
+    }
+    if caps.has_histedit {
+        text.push(format!(
+            "History: {} histedit plan editor from selected revision (j/k move, J/K reorder, p/e/f/r/d/m/b set verb, Enter run) | {} / {} continue / abort an in-progress histedit",
+            key(ActionId::HisteditSelected),
+            key(ActionId::HisteditContinue),
+            key(ActionId::HisteditAbort)
+        ));
+    }
+    if caps.has_evolve {
+        text.push(format!(
+            "Evolve: {} prev | {} next | {} evolve | {} evolve --all(confirm) | {} prune selected(confirm) | {} obslog -p selected",
+            key(ActionId::EvolvePrev),
+            key(ActionId::EvolveNext),
+            key(ActionId::Evolve),
+            key(ActionId::EvolveAll),
+            key(ActionId::PruneSelected),
+            key(ActionId::ObslogSelected),
+        ));
+    }
+    if caps.has_topic {
+        text.push(format!(
+            "Topics: {} create/activate topic | {} switch (update) to topic | {} clear active topic | {} list topics",
+            key(ActionId::TopicCreate),
+            key(ActionId::TopicSwitch),
+            key(ActionId::TopicClear),
+            key(ActionId::TopicsList),
+        ));
+    }
+    if has_custom_commands {
+        text.push(format!(
+            "Custom: {} open command palette",
+            key(ActionId::OpenCustomCommands)
+        ));
+    }
+    text.join(" | ")
+}
+
+fn output_sink(tx: mpsc::UnboundedSender<AppEvent>, job_id: u64) -> OutputSink {
+    Arc::new(move |chunk| {
+        let _ = tx.send(AppEvent::CommandOutput { job_id, chunk });
+    })
+}
+
+pub async fn run_app(config: AppConfig, startup_issues: Vec<String>) -> Result<()> {
+    let mut app = App::new_with_startup_issues(config, startup_issues)?;
+    app.remote_defaults = RemoteDefaultsStore::load(default_remote_defaults_path());
+    app.run().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, CommandContext, CustomCommand};
+    use crate::hg::HgProgress;
+    use ratatui::layout::Rect;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_app() -> App {
+        let mut app = App::new(AppConfig::default()).expect("app");
+        app.ui_rects = ui::UiRects {
+            header: Rect::new(0, 0, 100, 2),
+            footer: Rect::new(0, 29, 100, 1),
+            files: Rect::new(0, 2, 58, 12),
+            details: Rect::new(0, 14, 58, 15),
+            revisions: Rect::new(58, 2, 42, 10),
+            bookmarks: Rect::new(58, 12, 14, 5),
+            branches: Rect::new(72, 12, 14, 5),
+            tags: Rect::new(86, 12, 14, 5),
+            shelves: Rect::new(58, 17, 14, 5),
+            conflicts: Rect::new(72, 17, 14, 5),
+            remote: Rect::new(86, 17, 14, 5),
+            log: Rect::new(58, 22, 42, 7),
+        };
+        app
+    }
+
+    fn revision_fixture(rev: i64) -> crate::domain::Revision {
+        crate::domain::Revision {
+            rev,
+            node: format!("node-{rev}"),
+            desc: format!("desc-{rev}"),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "draft".to_string(),
+            tags: Vec::new(),
+            bookmarks: Vec::new(),
+            date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
+            graph_prefix: Some("o".to_string()),
+        }
+    }
+
+    fn left_down(column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    fn scroll_down(column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind: MouseEventKind::ScrollDown,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    fn scroll_up(column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind: MouseEventKind::ScrollUp,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    fn run_hg(repo: &Path, args: &[&str]) {
+        let output = Command::new("hg")
+            .current_dir(repo)
+            .args(args)
+            .output()
+            .expect("spawn hg command");
+        assert!(
+            output.status.success(),
+            "hg {} failed\nstdout:\n{}\nstderr:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    fn temp_repo_dir() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock")
+            .as_nanos();
+        std::env::temp_dir().join(format!("easyhg-details-e2e-{}-{nanos}", std::process::id()))
+    }
+
+    #[derive(Debug)]
+    struct RecordingHgClient {
+        snapshot: RepoSnapshot,
+        calls: std::sync::Mutex<Vec<SnapshotOptions>>,
+    }
+
+    impl RecordingHgClient {
+        fn new(snapshot: RepoSnapshot) -> Self {
+            Self {
+                snapshot,
+                calls: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+
+        fn calls(&self) -> Vec<SnapshotOptions> {
+            self.calls.lock().expect("calls lock").clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl HgClient for RecordingHgClient {
+        async fn refresh_snapshot(&self, options: SnapshotOptions) -> anyhow::Result<RepoSnapshot> {
+            self.calls.lock().expect("calls lock").push(options);
+            Ok(self.snapshot.clone())
+        }
+
+        async fn file_diff(&self, _file: &RepoPath) -> anyhow::Result<String> {
+            Ok(String::new())
+        }
+
+        async fn revision_patch(&self, _rev: i64) -> anyhow::Result<String> {
+            Ok(String::new())
+        }
+
+        async fn log_revset(&self, _revset: &str) -> anyhow::Result<Vec<Revision>> {
+            Ok(self.snapshot.revisions.clone())
+        }
+
+        async fn log_summary(&self, revset: &str) -> anyhow::Result<String> {
+            Ok(format!("log {revset}"))
+        }
+
+        async fn repo_state(&self, _revision_limit: usize) -> anyhow::Result<RepoState> {
+            Ok(RepoState::capture(&self.snapshot))
+        }
+
+        async fn obslog(&self, rev: i64) -> anyhow::Result<String> {
+            Ok(format!("obslog for {rev}"))
+        }
+
+        async fn topics_list(&self) -> anyhow::Result<String> {
+            Ok("topics".to_string())
+        }
+
+        async fn shelve_patch(&self, name: &str) -> anyhow::Result<String> {
+            Ok(format!("shelf patch for {name}"))
+        }
+
+        async fn remote_changesets(
+            &self,
+            _direction: RemoteDirection,
+            _path: Option<&str>,
+        ) -> anyhow::Result<Vec<Revision>> {
+            Ok(self.snapshot.revisions.clone())
+        }
+
+        async fn remote_changeset_patch(
+            &self,
+            direction: RemoteDirection,
+            _path: Option<&str>,
+            node: &str,
+        ) -> anyhow::Result<String> {
+            Ok(format!(
+                "{} patch for {node}",
+                remote_direction_label(direction)
+            ))
+        }
+
+        async fn run_action(
+            &self,
+            _action: &HgAction,
+            _output: Option<&OutputSink>,
+        ) -> anyhow::Result<CommandResult> {
+            Ok(CommandResult {
+                command_preview: "mock".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            })
+        }
+
+        async fn run_custom_command(
+            &self,
+            _invocation: &CustomInvocation,
+            _output: Option<&OutputSink>,
+        ) -> anyhow::Result<CommandResult> {
+            Ok(CommandResult {
+                command_preview: "mock".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn panel_hit_testing() {
+        let app = make_app();
+        assert_eq!(app.panel_at(1, 3), Some(FocusPanel::Files));
+        assert_eq!(app.panel_at(80, 3), Some(FocusPanel::Revisions));
+        assert_eq!(app.panel_at(90, 26), Some(FocusPanel::Log));
+        assert_eq!(app.panel_at(20, 20), None);
+    }
+
+    #[test]
+    fn row_mapping_uses_offset() {
+        let mut app = make_app();
+        app.snapshot.files = vec![
+            crate::domain::FileChange {
+                path: "a".into(),
+                status: crate::domain::FileStatus::Modified,
+            };
+            20
+        ];
+        app.files_offset = 5;
+        assert_eq!(app.list_row_from_point(FocusPanel::Files, 2, 3), Some(5));
+        assert_eq!(app.list_row_from_point(FocusPanel::Files, 2, 4), Some(6));
+    }
+
+    #[test]
+    fn mouse_click_selects_row_and_focuses_panel() {
+        let mut app = make_app();
+        app.snapshot.bookmarks = vec![
+            crate::domain::Bookmark {
+                name: "a".to_string(),
+                rev: 1,
+                node: "a".to_string(),
+                active: false,
+            },
+            crate::domain::Bookmark {
+                name: "b".to_string(),
+                rev: 2,
+                node: "b".to_string(),
+                active: false,
+            },
+        ];
+
+        app.handle_mouse(left_down(60, 13));
+        assert_eq!(app.focus, FocusPanel::Bookmarks);
+        assert_eq!(app.bookmarks_idx, 0);
+    }
+
+    #[test]
+    fn modal_blocks_mouse() {
+        let mut app = make_app();
+        app.focus = FocusPanel::Files;
+        app.confirmation = Some(PendingConfirmation {
+            message: "Confirm".to_string(),
+            action: PendingRunAction::Hg(HgAction::Push {
+                dest: None,
+                scope: SyncScope::All,
+                new_branch: false,
+            }),
+            follow_up: None,
+            typed: None,
+        });
+        app.handle_mouse(left_down(80, 3));
+        assert_eq!(app.focus, FocusPanel::Files);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn mouse_scroll_falls_back_to_focused_panel_when_not_over_panel() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.focus = FocusPanel::Bookmarks;
+        app.snapshot.bookmarks = vec![
+            crate::domain::Bookmark {
+                name: "a".to_string(),
+                rev: 1,
+                node: "a".to_string(),
+                active: false,
+            },
+            crate::domain::Bookmark {
+                name: "b".to_string(),
+                rev: 2,
+                node: "b".to_string(),
+                active: false,
+            },
+        ];
+
+        app.handle_mouse(scroll_down(1, 1));
+        assert_eq!(app.focus, FocusPanel::Bookmarks);
+        assert_eq!(app.bookmarks_idx, 1);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn mouse_scroll_prefers_hovered_panel_over_focused_panel() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.focus = FocusPanel::Conflicts;
+        app.conflicts_idx = 1;
+        app.snapshot.bookmarks = vec![
+            crate::domain::Bookmark {
+                name: "a".to_string(),
+                rev: 1,
+                node: "a".to_string(),
+                active: false,
+            },
+            crate::domain::Bookmark {
+                name: "b".to_string(),
+                rev: 2,
+                node: "b".to_string(),
+                active: false,
+            },
+        ];
+        app.snapshot.conflicts = vec![
+            crate::domain::ConflictEntry {
+                resolved: false,
+                path: "x".into(),
+            },
+            crate::domain::ConflictEntry {
+                resolved: false,
+                path: "y".into(),
+            },
+        ];
+
+        app.handle_mouse(scroll_down(60, 13));
+        assert_eq!(app.focus, FocusPanel::Bookmarks);
+        assert_eq!(app.bookmarks_idx, 1);
+        assert_eq!(app.conflicts_idx, 1);
+    }
+
+    #[test]
+    fn mouse_scroll_on_details_uses_details_offset_without_changing_focus() {
+        let mut app = make_app();
+        app.focus = FocusPanel::Files;
+        app.files_idx = 2;
+        app.detail_text = (0..30)
+            .map(|i| format!("line-{i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        app.handle_mouse(scroll_down(2, 15));
+        assert_eq!(app.details_scroll, 1);
+        assert_eq!(app.focus, FocusPanel::Files);
+        assert_eq!(app.files_idx, 2);
+
+        app.handle_mouse(scroll_up(2, 15));
+        assert_eq!(app.details_scroll, 0);
+    }
+
+    #[test]
+    fn detail_scroll_resets_when_new_detail_arrives() {
+        let mut app = make_app();
+        app.details_scroll = 5;
+        app.detail_request_id = 99;
+
+        app.handle_app_event(AppEvent::DetailLoaded {
+            request_id: 99,
+            result: Ok("new detail text".to_string()),
+        });
+
+        assert_eq!(app.details_scroll, 0);
+    }
+
+    #[test]
+    fn periodic_snapshot_refresh_preserves_detail_scroll_for_same_target() {
+        let mut app = make_app();
+        app.focus = FocusPanel::Files;
+        app.files_idx = 0;
+        app.snapshot.files = vec![crate::domain::FileChange {
+            path: "src/main.rs".into(),
+            status: crate::domain::FileStatus::Modified,
+        }];
+        app.detail_text = (0..30)
+            .map(|i| format!("line-{i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        app.details_scroll = 7;
+
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                files: vec![crate::domain::FileChange {
+                    path: "src/main.rs".into(),
+                    status: crate::domain::FileStatus::Modified,
+                }],
+                ..RepoSnapshot::default()
+            })),
+        });
+
+        assert_eq!(app.details_scroll, 7);
+    }
+
+    #[test]
+    fn periodic_snapshot_refresh_resets_detail_scroll_when_target_changes() {
+        let mut app = make_app();
+        app.focus = FocusPanel::Files;
+        app.files_idx = 0;
+        app.snapshot.files = vec![crate::domain::FileChange {
+            path: "src/main.rs".into(),
+            status: crate::domain::FileStatus::Modified,
+        }];
+        app.details_scroll = 7;
+
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot::default())),
+        });
+
+        assert_eq!(app.details_scroll, 0);
+    }
+
+    #[test]
+    fn explicit_snapshot_refresh_resets_detail_scroll() {
+        let mut app = make_app();
+        app.focus = FocusPanel::Files;
+        app.files_idx = 0;
+        app.snapshot.files = vec![crate::domain::FileChange {
+            path: "src/main.rs".into(),
+            status: crate::domain::FileStatus::Modified,
+        }];
+        app.details_scroll = 7;
+
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: false,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot::default())),
+        });
+
+        assert_eq!(app.details_scroll, 0);
+    }
+
+    #[test]
+    fn lightweight_snapshot_refresh_preserves_existing_revisions() {
+        let mut app = make_app();
+        app.snapshot.revisions = vec![crate::domain::Revision {
+            rev: 7,
+            node: "abc".to_string(),
+            desc: "old".to_string(),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "draft".to_string(),
+            tags: Vec::new(),
             bookmarks: Vec::new(),
             date_unix_secs: 0,
-            graph_prefix: Some("o".to_string()),
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
+            graph_prefix: None,
+        }];
+
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: false,
+            result: Ok(Box::new(RepoSnapshot::default())),
+        });
+
+        assert_eq!(app.snapshot.revisions.len(), 1);
+        assert_eq!(app.snapshot.revisions[0].rev, 7);
+    }
+
+    #[test]
+    fn full_snapshot_refresh_replaces_revisions() {
+        let mut app = make_app();
+        app.snapshot.revisions = vec![crate::domain::Revision {
+            rev: 7,
+            node: "abc".to_string(),
+            desc: "old".to_string(),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "draft".to_string(),
+            tags: Vec::new(),
+            bookmarks: Vec::new(),
+            date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
+            graph_prefix: None,
+        }];
+
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                revisions: vec![crate::domain::Revision {
+                    rev: 8,
+                    node: "def".to_string(),
+                    desc: "new".to_string(),
+                    user: "u".to_string(),
+                    branch: "default".to_string(),
+                    phase: "draft".to_string(),
+                    tags: Vec::new(),
+                    bookmarks: Vec::new(),
+                    date_unix_secs: 0,
+                    obsolete: false,
+                    instabilities: Vec::new(),
+                    topic: None,
+                    graph_prefix: None,
+                }],
+                ..RepoSnapshot::default()
+            })),
+        });
+
+        assert_eq!(app.snapshot.revisions.len(), 1);
+        assert_eq!(app.snapshot.revisions[0].rev, 8);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn periodic_refresh_uses_lightweight_snapshot_mode() {
+        let mut app = make_app();
+        let client = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.hg = client.clone();
+        app.last_refresh = Instant::now() - Duration::from_secs(8);
+
+        app.periodic_refresh();
+        let snapshot_event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+            .await
+            .expect("snapshot timeout")
+            .expect("snapshot event");
+        app.handle_app_event(snapshot_event);
+
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(!calls[0].include_revisions);
+        assert_eq!(calls[0].revision_limit, LOG_LIMIT);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn manual_refresh_uses_full_snapshot_mode() {
+        let mut app = make_app();
+        let client = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.hg = client.clone();
+
+        app.refresh_snapshot(false);
+        let snapshot_event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+            .await
+            .expect("snapshot timeout")
+            .expect("snapshot event");
+        app.handle_app_event(snapshot_event);
+
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].include_revisions);
+        assert_eq!(calls[0].revision_limit, LOG_LIMIT);
+    }
+
+    #[test]
+    fn rebase_picker_requires_distinct_source_and_destination() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.revisions = vec![revision_fixture(12)];
+        app.rev_idx = 0;
+
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.pending_rebase_source, Some(12));
+        assert!(app.confirmation.is_none());
+
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.pending_rebase_source, Some(12));
+        assert!(app.confirmation.is_none());
+        assert!(app.status_line.contains("different destination"));
+    }
+
+    #[test]
+    fn rebase_picker_two_step_sets_confirmation() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.revisions = vec![revision_fixture(20), revision_fixture(18)];
+        app.rev_idx = 0;
+
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.pending_rebase_source, Some(20));
+        assert!(app.confirmation.is_none());
+        assert!(app.status_line.contains("step 1/2"));
+
+        app.rev_idx = 1;
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.pending_rebase_source, None);
+        assert!(app.status_line.contains("step 2/2"));
+
+        let confirm = app.confirmation.as_ref().expect("rebase confirmation");
+        assert!(confirm.message.contains("20"));
+        assert!(confirm.message.contains("18"));
+        match &confirm.action {
+            PendingRunAction::Hg(HgAction::RebaseSourceDest {
+                source_rev,
+                dest_rev,
+            }) => {
+                assert_eq!(*source_rev, 20);
+                assert_eq!(*dest_rev, 18);
+            }
+            other => panic!("unexpected action: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn esc_cancels_rebase_picker_source_selection() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.revisions = vec![revision_fixture(20), revision_fixture(18)];
+        app.rev_idx = 0;
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.pending_rebase_source, Some(20));
+
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(app.pending_rebase_source, None);
+        assert_eq!(app.status_line, "Rebase selection cancelled.");
+    }
+
+    #[test]
+    fn rebase_continue_blocked_without_in_progress_rebase() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.rebase.in_progress = false;
+
+        app.dispatch_action(ActionId::RebaseContinue);
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "No rebase is currently in progress.");
+        assert!(
+            app.detail_text
+                .contains("No rebase is currently in progress.")
+        );
+    }
+
+    #[test]
+    fn rebase_continue_blocked_with_unresolved_conflicts() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.rebase.in_progress = true;
+        app.snapshot.rebase.unresolved_conflicts = 2;
+
+        app.dispatch_action(ActionId::RebaseContinue);
+        assert!(app.confirmation.is_none());
+        assert!(app.status_line.contains("Cannot continue rebase"));
+        assert!(app.detail_text.contains("2 unresolved conflict"));
+    }
+
+    #[test]
+    fn rebase_continue_and_abort_open_confirmations_when_in_progress_and_clear() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.rebase.in_progress = true;
+        app.snapshot.rebase.unresolved_conflicts = 0;
+
+        app.dispatch_action(ActionId::RebaseContinue);
+        match app.confirmation.as_ref().map(|c| &c.action) {
+            Some(PendingRunAction::Hg(HgAction::RebaseContinue)) => {}
+            other => panic!("unexpected continue confirmation: {other:?}"),
         }
+
+        app.confirmation = None;
+        app.dispatch_action(ActionId::RebaseAbort);
+        match app.confirmation.as_ref().map(|c| &c.action) {
+            Some(PendingRunAction::Hg(HgAction::RebaseAbort)) => {}
+            other => panic!("unexpected abort confirmation: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rebase_abort_blocked_without_in_progress_rebase() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.rebase.in_progress = false;
+
+        app.dispatch_action(ActionId::RebaseAbort);
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "No rebase is currently in progress.");
+    }
+
+    #[test]
+    fn rebase_action_without_extension_sets_actionable_detail_help() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = false;
+        app.snapshot.revisions = vec![revision_fixture(20)];
+        app.rev_idx = 0;
+
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.status_line, "Rebase extension not enabled.");
+        assert!(app.detail_text.contains("[extensions]"));
+        assert!(app.detail_text.contains("rebase ="));
+    }
+
+    #[test]
+    fn snapshot_logs_rebase_unavailable_notice_once() {
+        let mut app = make_app();
+        let snapshot = RepoSnapshot {
+            capabilities: crate::domain::HgCapabilities {
+                has_rebase: false,
+                ..crate::domain::HgCapabilities::default()
+            },
+            ..RepoSnapshot::default()
+        };
+
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(snapshot.clone())),
+        });
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(snapshot)),
+        });
+
+        let count = app
+            .log_entries
+            .iter()
+            .filter(|line| line.text.contains("Rebase unavailable"))
+            .count();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn snapshot_rebase_hint_indicates_continue_when_conflicts_resolved() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                capabilities: crate::domain::HgCapabilities {
+                    has_rebase: true,
+                    ..crate::domain::HgCapabilities::default()
+                },
+                rebase: crate::domain::RebaseState {
+                    in_progress: true,
+                    unresolved_conflicts: 0,
+                    resolved_conflicts: 2,
+                    total_conflicts: 2,
+                },
+                ..RepoSnapshot::default()
+            })),
+        });
+        assert!(app.status_line.contains("all conflicts resolved"));
+        assert!(
+            app.log_entries
+                .iter()
+                .any(|line| line.text.contains("all conflicts resolved"))
+        );
+    }
+
+    #[test]
+    fn snapshot_rebase_hint_indicates_remaining_conflicts() {
+        let mut app = make_app();
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                capabilities: crate::domain::HgCapabilities {
+                    has_rebase: true,
+                    ..crate::domain::HgCapabilities::default()
+                },
+                rebase: crate::domain::RebaseState {
+                    in_progress: true,
+                    unresolved_conflicts: 3,
+                    resolved_conflicts: 1,
+                    total_conflicts: 4,
+                },
+                ..RepoSnapshot::default()
+            })),
+        });
+        assert!(app.status_line.contains("3 unresolved conflict"));
+    }
+
+    #[test]
+    fn snapshot_rebase_completion_emits_completion_status() {
+        let mut app = make_app();
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                capabilities: crate::domain::HgCapabilities {
+                    has_rebase: true,
+                    ..crate::domain::HgCapabilities::default()
+                },
+                rebase: crate::domain::RebaseState {
+                    in_progress: true,
+                    unresolved_conflicts: 0,
+                    resolved_conflicts: 0,
+                    total_conflicts: 0,
+                },
+                ..RepoSnapshot::default()
+            })),
+        });
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                capabilities: crate::domain::HgCapabilities {
+                    has_rebase: true,
+                    ..crate::domain::HgCapabilities::default()
+                },
+                rebase: crate::domain::RebaseState {
+                    in_progress: false,
+                    unresolved_conflicts: 0,
+                    resolved_conflicts: 0,
+                    total_conflicts: 0,
+                },
+                ..RepoSnapshot::default()
+            })),
+        });
+        assert_eq!(app.status_line, "Rebase is no longer in progress.");
+    }
+
+    #[test]
+    fn update_selected_without_target_sets_status_message() {
+        let mut app = make_app();
+        app.focus = FocusPanel::Revisions;
+        app.snapshot.revisions.clear();
+
+        app.dispatch_action(ActionId::UpdateSelected);
+        assert_eq!(app.status_line, "No revision selected.");
+
+        app.focus = FocusPanel::Bookmarks;
+        app.snapshot.bookmarks.clear();
+        app.dispatch_action(ActionId::UpdateSelected);
+        assert_eq!(app.status_line, "No bookmark selected.");
+    }
+
+    #[test]
+    fn histedit_without_selected_revision_sets_status_message() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_histedit = true;
+        app.snapshot.revisions.clear();
+
+        app.dispatch_action(ActionId::HisteditSelected);
+        assert_eq!(app.status_line, "No revision selected for histedit.");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn histedit_selected_loads_plan_rows_defaulting_to_pick() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_histedit = true;
+        app.snapshot.revisions = vec![revision_fixture(3)];
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot {
+            revisions: vec![revision_fixture(3), revision_fixture(4)],
+            ..RepoSnapshot::default()
+        }));
+
+        app.dispatch_action(ActionId::HisteditSelected);
+        let event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+            .await
+            .expect("plan timeout")
+            .expect("plan event");
+        app.handle_app_event(event);
+
+        let plan = app.histedit_plan.as_ref().expect("plan editor open");
+        assert_eq!(plan.base_rev, 3);
+        assert_eq!(
+            plan.rows.iter().map(|row| row.rev).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        assert!(
+            plan.rows
+                .iter()
+                .all(|row| row.action == HisteditAction::Pick)
+        );
+    }
+
+    #[test]
+    fn histedit_plan_keys_reorder_rows_and_set_verbs() {
+        let mut app = make_app();
+        app.histedit_plan = Some(HisteditPlanState {
+            base_rev: 3,
+            rows: [3, 4, 5]
+                .into_iter()
+                .map(|rev| HisteditPlanStep {
+                    action: HisteditAction::Pick,
+                    rev,
+                    node: format!("node-{rev}"),
+                    desc: format!("desc-{rev}"),
+                })
+                .collect(),
+            selected: 0,
+        });
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('J'), KeyModifiers::SHIFT));
+        app.handle_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE));
+
+        let plan = app.histedit_plan.as_ref().expect("plan still open");
+        assert_eq!(
+            plan.rows
+                .iter()
+                .map(|row| (row.rev, row.action))
+                .collect::<Vec<_>>(),
+            vec![
+                (4, HisteditAction::Drop),
+                (3, HisteditAction::Roll),
+                (5, HisteditAction::Pick)
+            ]
+        );
     }
 
-    fn left_down(column: u16, row: u16) -> MouseEvent {
-        MouseEvent {
-            kind: MouseEventKind::Down(MouseButton::Left),
-            column,
-            row,
-            modifiers: KeyModifiers::NONE,
-        }
-    }
+    #[test]
+    fn histedit_plan_rejects_fold_on_first_row() {
+        let mut app = make_app();
+        app.histedit_plan = Some(HisteditPlanState {
+            base_rev: 3,
+            rows: vec![HisteditPlanStep {
+                action: HisteditAction::Fold,
+                rev: 3,
+                node: "node-3".to_string(),
+                desc: "desc-3".to_string(),
+            }],
+            selected: 0,
+        });
 
-    fn scroll_down(column: u16, row: u16) -> MouseEvent {
-        MouseEvent {
-            kind: MouseEventKind::ScrollDown,
-            column,
-            row,
-            modifiers: KeyModifiers::NONE,
-        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.histedit_plan.is_some());
+        assert!(app.status_line.contains("Cannot fold the first revision"));
     }
 
-    fn scroll_up(column: u16, row: u16) -> MouseEvent {
-        MouseEvent {
-            kind: MouseEventKind::ScrollUp,
-            column,
-            row,
-            modifiers: KeyModifiers::NONE,
-        }
+    #[test]
+    fn histedit_plan_with_message_edits_runs_in_foreground() {
+        let mut app = make_app();
+        app.histedit_plan = Some(HisteditPlanState {
+            base_rev: 3,
+            rows: vec![HisteditPlanStep {
+                action: HisteditAction::Mess,
+                rev: 3,
+                node: "node-3".to_string(),
+                desc: "desc-3".to_string(),
+            }],
+            selected: 0,
+        });
+
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.histedit_plan.is_none());
+        let request = app
+            .interactive_histedit_request
+            .as_ref()
+            .expect("foreground histedit requested");
+        assert_eq!(request.base_rev, 3);
     }
 
-    fn run_hg(repo: &Path, args: &[&str]) {
-        let output = Command::new("hg")
-            .current_dir(repo)
-            .args(args)
-            .output()
-            .expect("spawn hg command");
+    #[tokio::test(flavor = "current_thread")]
+    async fn armed_dry_run_previews_editor_histedit_and_interactive_commit() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.dry_run_next = true;
+        app.histedit_plan = Some(HisteditPlanState {
+            base_rev: 3,
+            rows: vec![HisteditPlanStep {
+                action: HisteditAction::Mess,
+                rev: 3,
+                node: "node-3".to_string(),
+                desc: "desc-3".to_string(),
+            }],
+            selected: 0,
+        });
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.interactive_histedit_request.is_none());
         assert!(
-            output.status.success(),
-            "hg {} failed\nstdout:\n{}\nstderr:\n{}",
-            args.join(" "),
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
+            app.status_line
+                .starts_with("Running: dry run: hg histedit --commands")
         );
-    }
 
-    fn temp_repo_dir() -> PathBuf {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("clock")
-            .as_nanos();
-        std::env::temp_dir().join(format!("easyhg-details-e2e-{}-{nanos}", std::process::id()))
+        app.dry_run_next = true;
+        app.input = Some(InputState {
+            title: "Interactive".to_string(),
+            value: "msg".to_string(),
+            purpose: InputPurpose::CommitMessageInteractive,
+        });
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.interactive_commit_request.is_none());
+        assert!(app.status_line.contains("dry run: hg commit"));
     }
 
-    #[derive(Debug)]
-    struct RecordingHgClient {
-        snapshot: RepoSnapshot,
-        calls: std::sync::Mutex<Vec<SnapshotOptions>>,
-    }
+    #[test]
+    fn histedit_continue_and_abort_have_their_own_actions() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.capabilities.has_histedit = true;
+        app.dispatch_action(ActionId::HisteditContinue);
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "No histedit is currently in progress.");
 
-    impl RecordingHgClient {
-        fn new(snapshot: RepoSnapshot) -> Self {
-            Self {
-                snapshot,
-                calls: std::sync::Mutex::new(Vec::new()),
-            }
+        app.snapshot.histedit.in_progress = true;
+        app.dispatch_action(ActionId::RebaseContinue);
+        assert!(app.confirmation.is_none());
+
+        app.dispatch_action(ActionId::HisteditContinue);
+        match app.confirmation.as_ref().map(|c| &c.action) {
+            Some(PendingRunAction::Hg(HgAction::HisteditContinue)) => {}
+            other => panic!("unexpected continue confirmation: {other:?}"),
         }
 
-        fn calls(&self) -> Vec<SnapshotOptions> {
-            self.calls.lock().expect("calls lock").clone()
+        app.confirmation = None;
+        app.dispatch_action(ActionId::HisteditAbort);
+        match app.confirmation.as_ref().map(|c| &c.action) {
+            Some(PendingRunAction::Hg(HgAction::HisteditAbort)) => {}
+            other => panic!("unexpected abort confirmation: {other:?}"),
         }
     }
 
-    #[async_trait::async_trait]
-    impl HgClient for RecordingHgClient {
-        async fn refresh_snapshot(&self, options: SnapshotOptions) -> anyhow::Result<RepoSnapshot> {
-            self.calls.lock().expect("calls lock").push(options);
-            Ok(self.snapshot.clone())
-        }
+    #[test]
+    fn snapshot_histedit_hint_reports_in_progress_state() {
+        let mut app = make_app();
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                histedit: crate::domain::HisteditState { in_progress: true },
+                ..RepoSnapshot::default()
+            })),
+        });
+        assert!(app.status_line.starts_with("Histedit in progress"));
 
-        async fn file_diff(&self, _file: &str) -> anyhow::Result<String> {
-            Ok(String::new())
-        }
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: true,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot::default())),
+        });
+        assert_eq!(app.status_line, "Histedit is no longer in progress.");
+    }
 
-        async fn revision_patch(&self, _rev: i64) -> anyhow::Result<String> {
-            Ok(String::new())
-        }
+    #[test]
+    fn evolve_actions_require_evolve_extension() {
+        let mut app = make_app();
+        app.snapshot.revisions = vec![revision_fixture(5)];
 
-        async fn run_action(&self, _action: &HgAction) -> anyhow::Result<CommandResult> {
-            Ok(CommandResult {
-                command_preview: "mock".to_string(),
-                success: true,
-                stdout: String::new(),
-                stderr: String::new(),
-            })
+        app.dispatch_action(ActionId::PruneSelected);
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "Evolve extension unavailable.");
+
+        app.snapshot.capabilities.has_evolve = true;
+        app.dispatch_action(ActionId::PruneSelected);
+        match app.confirmation.as_ref().map(|c| &c.action) {
+            Some(PendingRunAction::Hg(HgAction::Prune { rev: 5 })) => {}
+            other => panic!("unexpected prune confirmation: {other:?}"),
         }
 
-        async fn run_custom_command(
-            &self,
-            _invocation: &CustomInvocation,
-        ) -> anyhow::Result<CommandResult> {
-            Ok(CommandResult {
-                command_preview: "mock".to_string(),
-                success: true,
-                stdout: String::new(),
-                stderr: String::new(),
-            })
+        app.confirmation = None;
+        app.dispatch_action(ActionId::EvolveAll);
+        match app.confirmation.as_ref().map(|c| &c.action) {
+            Some(PendingRunAction::Hg(HgAction::Evolve { all: true })) => {}
+            other => panic!("unexpected evolve confirmation: {other:?}"),
         }
     }
 
-    #[test]
-    fn panel_hit_testing() {
-        let app = make_app();
-        assert_eq!(app.panel_at(1, 3), Some(FocusPanel::Files));
-        assert_eq!(app.panel_at(80, 3), Some(FocusPanel::Revisions));
-        assert_eq!(app.panel_at(90, 26), Some(FocusPanel::Log));
-        assert_eq!(app.panel_at(20, 20), None);
+    #[tokio::test(flavor = "current_thread")]
+    async fn obslog_selected_loads_into_details() {
+        let mut app = make_app();
+        app.snapshot.capabilities.has_evolve = true;
+        app.snapshot.revisions = vec![revision_fixture(5)];
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+
+        app.dispatch_action(ActionId::ObslogSelected);
+        let event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+            .await
+            .expect("obslog timeout")
+            .expect("obslog event");
+        app.handle_app_event(event);
+
+        assert_eq!(app.detail_text, "obslog for 5");
     }
 
-    #[test]
-    fn row_mapping_uses_offset() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn topic_switch_rejects_unknown_topic_and_updates_to_known_one() {
         let mut app = make_app();
-        app.snapshot.files = vec![
-            crate::domain::FileChange {
-                path: "a".to_string(),
-                status: crate::domain::FileStatus::Modified,
-            };
-            20
-        ];
-        app.files_offset = 5;
-        assert_eq!(app.list_row_from_point(FocusPanel::Files, 2, 3), Some(5));
-        assert_eq!(app.list_row_from_point(FocusPanel::Files, 2, 4), Some(6));
+        app.snapshot.capabilities.has_topic = true;
+        app.snapshot.topics = vec![crate::domain::Topic {
+            name: "feature-x".to_string(),
+            active: false,
+            changesets: Some(2),
+        }];
+
+        app.dispatch_action(ActionId::TopicSwitch);
+        app.input.as_mut().expect("switch input").value = "nope".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.input.is_some());
+        assert_eq!(app.status_line, "Unknown topic 'nope'.");
+
+        app.input.as_mut().expect("switch input").value = "feature-x".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.input.is_none());
+        assert_eq!(app.status_line, "Running: hg update feature-x");
     }
 
     #[test]
-    fn mouse_click_selects_row_and_focuses_panel() {
+    fn topic_clear_requires_active_topic() {
         let mut app = make_app();
-        app.snapshot.bookmarks = vec![
-            crate::domain::Bookmark {
-                name: "a".to_string(),
-                rev: 1,
-                node: "a".to_string(),
-                active: false,
-            },
-            crate::domain::Bookmark {
-                name: "b".to_string(),
-                rev: 2,
-                node: "b".to_string(),
-                active: false,
-            },
-        ];
+        app.dispatch_action(ActionId::TopicClear);
+        assert_eq!(app.status_line, "Topic extension unavailable.");
 
-        app.handle_mouse(left_down(60, 13));
-        assert_eq!(app.focus, FocusPanel::Bookmarks);
-        assert_eq!(app.bookmarks_idx, 0);
+        app.snapshot.capabilities.has_topic = true;
+        app.dispatch_action(ActionId::TopicClear);
+        assert_eq!(app.status_line, "No active topic to clear.");
     }
 
-    #[test]
-    fn modal_blocks_mouse() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn phase_backward_move_needs_two_confirmations_with_force() {
         let mut app = make_app();
-        app.focus = FocusPanel::Files;
-        app.confirmation = Some(PendingConfirmation {
-            message: "Confirm".to_string(),
-            action: PendingRunAction::Hg(HgAction::Push),
-        });
-        app.handle_mouse(left_down(80, 3));
-        assert_eq!(app.focus, FocusPanel::Files);
+        let mut public = revision_fixture(4);
+        public.phase = "public".to_string();
+        app.snapshot.revisions = vec![public];
+
+        app.dispatch_action(ActionId::PhaseDraft);
+        let first = app.confirmation.as_ref().expect("first confirmation");
+        assert!(first.follow_up.is_some());
+        match &first.action {
+            PendingRunAction::Hg(HgAction::SetPhase {
+                rev: 4,
+                phase: Phase::Draft,
+                force: true,
+            }) => {}
+            other => panic!("unexpected phase action: {other:?}"),
+        }
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        let second = app.confirmation.as_ref().expect("second confirmation");
+        assert!(second.follow_up.is_none());
+        assert!(second.message.starts_with("Really force revision 4"));
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "Running: hg phase -d -f -r 4");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn phase_force_warning_survives_never_and_typed_policies() {
+        let mut public = revision_fixture(4);
+        public.phase = "public".to_string();
+
+        let mut app = app_with_confirmations(&[("phase_draft", "never")]);
+        app.snapshot.revisions = vec![public.clone()];
+        app.dispatch_action(ActionId::PhaseDraft);
+        let only = app.confirmation.as_ref().expect("force warning");
+        assert!(only.follow_up.is_none());
+        assert!(only.message.starts_with("Really force revision 4"));
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "Running: hg phase -d -f -r 4");
+
+        let mut app = app_with_confirmations(&[("phase_draft", "typed")]);
+        app.snapshot.revisions = vec![public];
+        app.dispatch_action(ActionId::PhaseDraft);
+        for ch in "phase_draft".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let second = app.confirmation.as_ref().expect("force warning");
+        assert!(second.typed.is_none());
+        assert!(second.message.starts_with("Really force revision 4"));
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        assert_eq!(app.status_line, "Running: hg phase -d -f -r 4");
     }
 
     #[test]
-    fn mouse_scroll_falls_back_to_focused_panel_when_not_over_panel() {
+    fn phase_forward_move_needs_single_confirmation() {
         let mut app = make_app();
-        app.focus = FocusPanel::Bookmarks;
-        app.snapshot.bookmarks = vec![
-            crate::domain::Bookmark {
-                name: "a".to_string(),
-                rev: 1,
-                node: "a".to_string(),
-                active: false,
-            },
-            crate::domain::Bookmark {
-                name: "b".to_string(),
-                rev: 2,
-                node: "b".to_string(),
-                active: false,
-            },
-        ];
+        app.snapshot.revisions = vec![revision_fixture(4)];
 
-        app.handle_mouse(scroll_down(1, 1));
-        assert_eq!(app.focus, FocusPanel::Bookmarks);
-        assert_eq!(app.bookmarks_idx, 1);
+        app.dispatch_action(ActionId::PhasePublic);
+        let confirm = app.confirmation.as_ref().expect("confirmation");
+        assert!(confirm.follow_up.is_none());
+        assert_eq!(confirm.action.command_preview(), "hg phase -p -r 4");
+
+        app.confirmation = None;
+        app.dispatch_action(ActionId::PhaseDraft);
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "Revision 4 is already draft.");
     }
 
     #[test]
-    fn mouse_scroll_prefers_hovered_panel_over_focused_panel() {
+    fn public_revisions_are_guarded_from_rewrites() {
         let mut app = make_app();
-        app.focus = FocusPanel::Conflicts;
-        app.conflicts_idx = 1;
-        app.snapshot.bookmarks = vec![
-            crate::domain::Bookmark {
-                name: "a".to_string(),
-                rev: 1,
-                node: "a".to_string(),
-                active: false,
-            },
-            crate::domain::Bookmark {
-                name: "b".to_string(),
-                rev: 2,
-                node: "b".to_string(),
-                active: false,
-            },
-        ];
-        app.snapshot.conflicts = vec![
-            crate::domain::ConflictEntry {
-                resolved: false,
-                path: "x".to_string(),
-            },
-            crate::domain::ConflictEntry {
-                resolved: false,
-                path: "y".to_string(),
-            },
+        app.snapshot.capabilities.has_rebase = true;
+        app.snapshot.capabilities.has_histedit = true;
+        app.snapshot.capabilities.has_evolve = true;
+        let mut public = revision_fixture(4);
+        public.phase = "public".to_string();
+        app.snapshot.revisions = vec![public];
+
+        app.dispatch_action(ActionId::RebaseSelected);
+        assert_eq!(app.pending_rebase_source, None);
+        assert!(app.status_line.starts_with("Revision 4 is public; rebase"));
+
+        app.dispatch_action(ActionId::HisteditSelected);
+        assert!(
+            app.status_line
+                .starts_with("Revision 4 is public; histedit")
+        );
+
+        app.dispatch_action(ActionId::PruneSelected);
+        assert!(app.confirmation.is_none());
+        assert!(app.status_line.starts_with("Revision 4 is public; prune"));
+    }
+
+    fn path_fixture(name: &str, url: &str) -> HgPath {
+        HgPath {
+            name: name.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn push_picker_prefers_default_push_and_remembers_choice() {
+        let mut app = make_app();
+        app.snapshot.repo_root = Some("/repo".to_string());
+        app.snapshot.paths = vec![
+            path_fixture("default", "https://example.com/repo"),
+            path_fixture("default-push", "ssh://example.com/repo"),
+            path_fixture("mirror", "file:///srv/mirror"),
         ];
 
-        app.handle_mouse(scroll_down(60, 13));
-        assert_eq!(app.focus, FocusPanel::Bookmarks);
-        assert_eq!(app.bookmarks_idx, 1);
-        assert_eq!(app.conflicts_idx, 1);
+        app.dispatch_action(ActionId::Push);
+        assert_eq!(app.path_picker.as_ref().expect("picker").selected, 1);
+        app.handle_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.path_picker.is_none());
+        assert_eq!(app.status_line, "Checking outgoing: hg outgoing mirror");
+
+        app.dispatch_action(ActionId::Push);
+        assert_eq!(app.path_picker.as_ref().expect("picker").selected, 2);
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(app.path_picker.is_none());
+        assert!(app.confirmation.is_none());
     }
 
-    #[test]
-    fn mouse_scroll_on_details_uses_details_offset_without_changing_focus() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn remote_changes_fill_panel_and_load_patch_preview() {
         let mut app = make_app();
-        app.focus = FocusPanel::Files;
-        app.files_idx = 2;
-        app.detail_text = (0..30)
-            .map(|i| format!("line-{i}"))
-            .collect::<Vec<_>>()
-            .join("\n");
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.handle_app_event(AppEvent::RemoteChangesLoaded {
+            direction: RemoteDirection::Pull,
+            path: Some("upstream".to_string()),
+            result: Ok(vec![revision_fixture(3), revision_fixture(4)]),
+        });
+        assert_eq!(app.focus, FocusPanel::Remote);
+        assert_eq!(app.remote_revisions().len(), 2);
+        assert_eq!(app.status_line, "2 incoming changeset(s) (upstream).");
+        let detail = tokio::time::timeout(Duration::from_secs(1), app.event_rx.recv())
+            .await
+            .expect("detail timeout")
+            .expect("detail event");
+        app.handle_app_event(detail);
+        assert_eq!(app.detail_text, "incoming patch for node-3");
 
-        app.handle_mouse(scroll_down(2, 15));
-        assert_eq!(app.details_scroll, 1);
-        assert_eq!(app.focus, FocusPanel::Files);
-        assert_eq!(app.files_idx, 2);
+        app.handle_app_event(AppEvent::RemoteChangesLoaded {
+            direction: RemoteDirection::Push,
+            path: None,
+            result: Ok(Vec::new()),
+        });
+        assert_eq!(app.remote_view, RemoteDirection::Push);
+        assert!(app.remote_revisions().is_empty());
+        assert_eq!(app.incoming_changes.as_ref().map(Vec::len), Some(2));
+        assert_eq!(app.detail_text, "No outgoing changesets.");
+    }
 
-        app.handle_mouse(scroll_up(2, 15));
-        assert_eq!(app.details_scroll, 0);
+    #[tokio::test(flavor = "current_thread")]
+    async fn remote_patches_are_cached_and_lists_reset_after_a_write() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.handle_app_event(AppEvent::RemoteChangesLoaded {
+            direction: RemoteDirection::Pull,
+            path: None,
+            result: Ok(vec![revision_fixture(3), revision_fixture(4)]),
+        });
+        let detail = tokio::time::timeout(Duration::from_secs(1), app.event_rx.recv())
+            .await
+            .expect("detail timeout")
+            .expect("detail event");
+        app.handle_app_event(detail);
+
+        app.remote_idx = 1;
+        app.refresh_detail_for_focus();
+        let detail = tokio::time::timeout(Duration::from_secs(1), app.event_rx.recv())
+            .await
+            .expect("detail timeout")
+            .expect("detail event");
+        app.handle_app_event(detail);
+        assert_eq!(app.detail_text, "incoming patch for node-4");
+
+        app.remote_idx = 0;
+        app.refresh_detail_for_focus();
+        assert_eq!(app.detail_text, "incoming patch for node-3");
+        assert!(app.event_rx.try_recv().is_err());
+
+        app.start_pending_action(PendingRunAction::Hg(HgAction::Pull {
+            source: None,
+            mode: PullMode::Update,
+            scope: SyncScope::All,
+        }));
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+                .await
+                .expect("event timeout")
+                .expect("event");
+            let finished = matches!(event, AppEvent::ActionFinished { .. });
+            app.handle_app_event(event);
+            if finished {
+                break;
+            }
+        }
+        assert!(app.incoming_changes.is_none());
+        assert!(app.remote_patches.is_empty());
     }
 
-    #[test]
-    fn detail_scroll_resets_when_new_detail_arrives() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn e2e_incoming_lists_changesets_from_local_clone() {
+        if Command::new("hg").arg("--version").output().is_err() {
+            eprintln!("skipping e2e test: hg binary not available");
+            return;
+        }
+
+        let upstream = temp_repo_dir();
+        fs::create_dir_all(&upstream).expect("create upstream repo directory");
+        run_hg(&upstream, &["init"]);
+        fs::write(upstream.join("a.txt"), "base\n").expect("write base file");
+        run_hg(&upstream, &["add", "a.txt"]);
+        run_hg(
+            &upstream,
+            &["commit", "-m", "init", "-u", "tester <tester@local>"],
+        );
+
+        let clone = upstream.with_extension("clone");
+        run_hg(
+            &upstream,
+            &[
+                "clone",
+                "-q",
+                ".",
+                clone.to_str().expect("utf-8 clone path"),
+            ],
+        );
+        fs::write(upstream.join("a.txt"), "base\nupstream\n").expect("write upstream change");
+        run_hg(
+            &upstream,
+            &[
+                "commit",
+                "-m",
+                "upstream change",
+                "-u",
+                "tester <tester@local>",
+            ],
+        );
+
+        let client = CliHgClient::new(clone.clone());
+        let upstream_path = upstream.display().to_string();
+        let incoming = client
+            .remote_changesets(RemoteDirection::Pull, Some(&upstream_path))
+            .await
+            .expect("incoming");
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].desc, "upstream change");
+        let patch = client
+            .remote_changeset_patch(
+                RemoteDirection::Pull,
+                Some(&upstream_path),
+                &incoming[0].node,
+            )
+            .await
+            .expect("incoming patch");
+        assert!(patch.contains("+upstream"));
+        let outgoing = client
+            .remote_changesets(RemoteDirection::Push, Some(&upstream_path))
+            .await
+            .expect("outgoing");
+        assert!(outgoing.is_empty());
+
+        fs::remove_dir_all(&upstream).ok();
+        fs::remove_dir_all(&clone).ok();
+    }
+
+    fn shelf_fixture(name: &str) -> crate::domain::Shelf {
+        crate::domain::Shelf {
+            name: name.to_string(),
+            age: None,
+            description: String::new(),
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn shelve_uses_selected_files_and_keep_flag() {
         let mut app = make_app();
-        app.details_scroll = 5;
-        app.detail_request_id = 99;
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.snapshot.capabilities.has_shelve = true;
+        app.commit_file_selection.insert("a.txt".into());
+        app.dispatch_action(ActionId::ToggleShelveKeep);
+        assert!(app.shelve_keep);
 
-        app.handle_app_event(AppEvent::DetailLoaded {
-            request_id: 99,
-            result: Ok("new detail text".to_string()),
-        });
+        app.dispatch_action(ActionId::Shelve);
+        assert!(
+            app.input
+                .as_ref()
+                .expect("shelve input")
+                .title
+                .contains("1 selected file")
+        );
+        app.input.as_mut().expect("shelve input").value = "wip".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.status_line,
+            "Running: hg shelve --name wip --keep <1 files>"
+        );
 
-        assert_eq!(app.details_scroll, 0);
+        app.snapshot.shelves = vec![shelf_fixture("wip")];
+        app.dispatch_action(ActionId::UnshelveSelected);
+        let confirm = app.confirmation.take().expect("unshelve confirmation");
+        assert_eq!(
+            confirm.action.command_preview(),
+            "hg unshelve --keep --name wip"
+        );
+
+        app.dispatch_action(ActionId::ShelveDelete);
+        let confirm = app.confirmation.take().expect("delete confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg shelve -d wip");
+        assert!(confirm.message.contains("permanently"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn bookmarks_focus_shows_log_since_ancestor_and_patch() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.snapshot.bookmarks = vec![bookmark_fixture("feature", 7, false)];
+        app.focus = FocusPanel::Bookmarks;
+        app.refresh_detail_for_focus();
+        let detail = tokio::time::timeout(Duration::from_secs(1), app.event_rx.recv())
+            .await
+            .expect("detail timeout")
+            .expect("detail event");
+        app.handle_app_event(detail);
+        assert!(app.detail_text.starts_with("Bookmark 'feature' @ 7"));
+        assert!(app.detail_text.contains("log ancestor(7, .)::7"));
     }
 
     #[test]
-    fn periodic_snapshot_refresh_preserves_detail_scroll_for_same_target() {
+    fn conflicts_focus_shows_file_with_markers() {
+        let repo_dir = temp_repo_dir();
+        fs::create_dir_all(&repo_dir).expect("create temp dir");
+        fs::write(
+            repo_dir.join("a.txt"),
+            "<<<<<<< working copy\nmine\n=======\ntheirs\n>>>>>>> merge rev\n",
+        )
+        .expect("write conflict file");
         let mut app = make_app();
-        app.focus = FocusPanel::Files;
-        app.files_idx = 0;
-        app.snapshot.files = vec![crate::domain::FileChange {
-            path: "src/main.rs".to_string(),
-            status: crate::domain::FileStatus::Modified,
+        app.snapshot.repo_root = Some(repo_dir.display().to_string());
+        app.snapshot.conflicts = vec![crate::domain::ConflictEntry {
+            resolved: false,
+            path: "a.txt".into(),
         }];
-        app.detail_text = (0..30)
-            .map(|i| format!("line-{i}"))
-            .collect::<Vec<_>>()
-            .join("\n");
-        app.details_scroll = 7;
+        app.focus = FocusPanel::Conflicts;
+        app.refresh_detail_for_focus();
+        assert!(app.detail_text.starts_with("a.txt\n"));
+        assert!(app.detail_text.contains("<<<<<<< working copy"));
+        fs::remove_dir_all(&repo_dir).ok();
+    }
 
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot {
-                files: vec![crate::domain::FileChange {
-                    path: "src/main.rs".to_string(),
-                    status: crate::domain::FileStatus::Modified,
-                }],
-                ..RepoSnapshot::default()
+    fn finish_action(app: &mut App, preview: &str, success: bool, stderr: &str) {
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Other,
+            action_preview: preview.to_string(),
+            show_output: false,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: preview.to_string(),
+                success,
+                exit_code: Some(if success { 0 } else { 255 }),
+                stdout: "out\n".to_string(),
+                stderr: stderr.to_string(),
             }),
         });
-
-        assert_eq!(app.details_scroll, 7);
     }
 
-    #[test]
-    fn periodic_snapshot_refresh_resets_detail_scroll_when_target_changes() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn log_records_filter_failures_and_export() {
         let mut app = make_app();
-        app.focus = FocusPanel::Files;
-        app.files_idx = 0;
-        app.snapshot.files = vec![crate::domain::FileChange {
-            path: "src/main.rs".to_string(),
-            status: crate::domain::FileStatus::Modified,
-        }];
-        app.details_scroll = 7;
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        finish_action(&mut app, "hg pull -u", true, "");
+        finish_action(&mut app, "hg push", false, "abort: push failed\n");
 
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot::default()),
-        });
+        let record = app
+            .log_entries
+            .iter()
+            .filter_map(|entry| entry.record.as_ref())
+            .find(|record| record.command == "hg push")
+            .expect("push record");
+        assert_eq!(record.exit_code, Some(255));
+        assert!(!record.success);
+        assert!(record.finished_at >= record.started_at);
+
+        app.dispatch_action(ActionId::ToggleLogFailures);
+        assert_eq!(app.visible_log_entries().len(), 1);
+        app.focus = FocusPanel::Log;
+        app.refresh_detail_for_focus();
+        assert!(app.detail_text.contains("Command: hg push"));
+        assert!(app.detail_text.contains("Exit status: 255 (failed)"));
+        assert!(app.detail_text.contains("abort: push failed"));
 
-        assert_eq!(app.details_scroll, 0);
+        let repo_dir = temp_repo_dir();
+        fs::create_dir_all(&repo_dir).expect("create export dir");
+        fs::create_dir_all(repo_dir.join(".hg")).expect("create .hg dir");
+        app.snapshot.repo_root = Some(repo_dir.display().to_string());
+        app.dispatch_action(ActionId::ExportLog);
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let export_path = repo_dir.join(".hg").join("easyhg-command-log.txt");
+        let exported = fs::read_to_string(&export_path).expect("exported log");
+        assert!(exported.contains("Command: hg push"));
+        assert!(!exported.contains("Command: hg pull -u"));
+        assert!(app.status_line.starts_with("Exported 1 log entries"));
+
+        // An existing file is only replaced after a second Enter on the same path.
+        fs::write(&export_path, "keep me").expect("seed export");
+        app.dispatch_action(ActionId::ExportLog);
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.status_line.ends_with("already exists."));
+        assert_eq!(fs::read_to_string(&export_path).expect("export"), "keep me");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.input.is_none());
+        assert!(
+            fs::read_to_string(&export_path)
+                .expect("export")
+                .contains("Command: hg push")
+        );
+        fs::remove_dir_all(&repo_dir).ok();
     }
 
-    #[test]
-    fn explicit_snapshot_refresh_resets_detail_scroll() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn log_focus_shows_full_output_of_selected_entry() {
         let mut app = make_app();
-        app.focus = FocusPanel::Files;
-        app.files_idx = 0;
-        app.snapshot.files = vec![crate::domain::FileChange {
-            path: "src/main.rs".to_string(),
-            status: crate::domain::FileStatus::Modified,
-        }];
-        app.details_scroll = 7;
-
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: false,
-            include_revisions: true,
-            result: Ok(RepoSnapshot::default()),
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        let long_stdout = (0..50).map(|i| format!("line {i}\n")).collect::<String>();
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Other,
+            action_preview: "hg summary".to_string(),
+            show_output: false,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: "hg summary".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: long_stdout,
+                stderr: String::new(),
+            }),
         });
+        app.focus = FocusPanel::Log;
+        app.log_idx = app
+            .log_entries
+            .iter()
+            .position(|entry| entry.text.contains("OK: hg summary"))
+            .expect("summary log entry");
+        app.refresh_detail_for_focus();
+        assert!(app.detail_text.contains("line 49"));
+    }
 
-        assert_eq!(app.details_scroll, 0);
+    #[tokio::test(flavor = "current_thread")]
+    async fn shelves_focus_loads_shelf_patch() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.snapshot.shelves = vec![shelf_fixture("one"), shelf_fixture("two")];
+        app.focus = FocusPanel::Shelves;
+        app.dispatch_action(ActionId::MoveDown);
+        let detail = tokio::time::timeout(Duration::from_secs(1), app.event_rx.recv())
+            .await
+            .expect("detail timeout")
+            .expect("detail event");
+        app.handle_app_event(detail);
+        assert_eq!(app.detail_text, "shelf patch for two");
     }
 
-    #[test]
-    fn lightweight_snapshot_refresh_preserves_existing_revisions() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn pull_modes_and_scopes_shape_the_pull_command() {
         let mut app = make_app();
-        app.snapshot.revisions = vec![crate::domain::Revision {
-            rev: 7,
-            node: "abc".to_string(),
-            desc: "old".to_string(),
-            user: "u".to_string(),
-            branch: "default".to_string(),
-            phase: "draft".to_string(),
-            tags: Vec::new(),
-            bookmarks: Vec::new(),
-            date_unix_secs: 0,
-            graph_prefix: None,
-        }];
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.dispatch_action(ActionId::CyclePullMode);
+        assert_eq!(app.pull_mode, PullMode::NoUpdate);
+        app.dispatch_action(ActionId::CyclePullMode);
+        assert_eq!(app.pull_mode, PullMode::Update);
 
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: false,
-            result: Ok(RepoSnapshot::default()),
-        });
+        app.snapshot.capabilities.has_rebase = true;
+        app.dispatch_action(ActionId::CyclePullMode);
+        app.dispatch_action(ActionId::CyclePullMode);
+        assert_eq!(app.pull_mode, PullMode::Rebase);
 
-        assert_eq!(app.snapshot.revisions.len(), 1);
-        assert_eq!(app.snapshot.revisions[0].rev, 7);
+        app.dispatch_action(ActionId::PullBookmark);
+        app.input.as_mut().expect("bookmark input").value = "feature".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.status_line, "Running: hg pull --rebase -B feature");
+
+        app.snapshot.capabilities.has_rebase = false;
+        app.dispatch_action(ActionId::PullRevision);
+        app.input.as_mut().expect("revision input").value = "abc123".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.status_line,
+            "Queued: hg pull -u -r abc123 (waiting for running write)"
+        );
     }
 
-    #[test]
-    fn full_snapshot_refresh_replaces_revisions() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn lock_wait_prompt_offers_wait_cancel_and_break() {
         let mut app = make_app();
-        app.snapshot.revisions = vec![crate::domain::Revision {
-            rev: 7,
-            node: "abc".to_string(),
-            desc: "old".to_string(),
-            user: "u".to_string(),
-            branch: "default".to_string(),
-            phase: "draft".to_string(),
-            tags: Vec::new(),
-            bookmarks: Vec::new(),
-            date_unix_secs: 0,
-            graph_prefix: None,
-        }];
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.run_hg_action(HgAction::UpdateToRevision { rev: 3 });
+        let job_id = app.jobs.jobs()[0].id;
+        let waiting =
+            "waiting for lock on working directory of /repo held by process '77' on host 'box'";
+        app.handle_app_event(AppEvent::CommandOutput {
+            job_id,
+            chunk: OutputChunk::Stderr(waiting.to_string()),
+        });
+        let prompt = app.lock_wait.as_ref().expect("lock prompt");
+        assert!(prompt.wait.wlock);
+        assert_eq!(
+            prompt.wait.holder.as_deref(),
+            Some("process '77' on host 'box'")
+        );
 
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot {
-                revisions: vec![crate::domain::Revision {
-                    rev: 8,
-                    node: "def".to_string(),
-                    desc: "new".to_string(),
-                    user: "u".to_string(),
-                    branch: "default".to_string(),
-                    phase: "draft".to_string(),
-                    tags: Vec::new(),
-                    bookmarks: Vec::new(),
-                    date_unix_secs: 0,
-                    graph_prefix: None,
-                }],
-                ..RepoSnapshot::default()
-            }),
+        app.handle_key(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE));
+        assert!(app.lock_wait.is_none());
+        assert_eq!(app.jobs.jobs().len(), 1);
+
+        app.handle_app_event(AppEvent::CommandOutput {
+            job_id,
+            chunk: OutputChunk::Stderr(waiting.to_string()),
         });
+        app.handle_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
+        assert!(app.lock_wait.is_none());
+        let confirmation = app.confirmation.as_ref().expect("break confirmation");
+        assert!(confirmation.follow_up.is_some());
+        assert!(matches!(
+            confirmation.action,
+            PendingRunAction::Hg(HgAction::BreakLock { wlock: true })
+        ));
+        app.confirmation = None;
 
-        assert_eq!(app.snapshot.revisions.len(), 1);
-        assert_eq!(app.snapshot.revisions[0].rev, 8);
+        app.handle_app_event(AppEvent::CommandOutput {
+            job_id,
+            chunk: OutputChunk::Stderr(waiting.to_string()),
+        });
+        app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
+        assert!(app.lock_wait.is_none());
+        assert!(app.jobs.is_empty());
+        assert_eq!(app.status_line, "Cancelled running job: hg update -r 3");
     }
 
     #[tokio::test(flavor = "current_thread")]
-    async fn periodic_refresh_uses_lightweight_snapshot_mode() {
+    async fn cancel_job_kills_latest_and_logs_it() {
         let mut app = make_app();
-        let client = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
-        app.hg = client.clone();
-        app.last_refresh = Instant::now() - Duration::from_secs(8);
-
-        app.periodic_refresh();
-        let snapshot_event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
-            .await
-            .expect("snapshot timeout")
-            .expect("snapshot event");
-        app.handle_app_event(snapshot_event);
-
-        let calls = client.calls();
-        assert_eq!(calls.len(), 1);
-        assert!(!calls[0].include_revisions);
-        assert_eq!(calls[0].revision_limit, LOG_LIMIT);
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.dispatch_action(ActionId::CancelJob);
+        assert_eq!(app.status_line, "No running jobs.");
+
+        app.run_hg_action(HgAction::Pull {
+            source: None,
+            mode: PullMode::Update,
+            scope: SyncScope::All,
+        });
+        app.run_hg_action(HgAction::Outgoing {
+            dest: None,
+            scope: SyncScope::All,
+        });
+        assert_eq!(app.status_line, "Running: hg outgoing");
+        assert_eq!(app.jobs.jobs().len(), 2);
+
+        let outgoing_id = app.jobs.jobs()[1].id;
+        app.handle_app_event(AppEvent::CommandOutput {
+            job_id: outgoing_id,
+            chunk: OutputChunk::Progress(Some(HgProgress {
+                topic: "searching".to_string(),
+                pos: 2,
+                total: Some(4),
+            })),
+        });
+        app.handle_app_event(AppEvent::CommandOutput {
+            job_id: outgoing_id,
+            chunk: OutputChunk::Stderr("comparing with default".to_string()),
+        });
+        assert_eq!(app.jobs.jobs()[1].progress.as_ref().map(|p| p.pos), Some(2));
+        assert_eq!(app.status_line, "hg outgoing: comparing with default");
+
+        app.dispatch_action(ActionId::CancelJob);
+        assert_eq!(app.status_line, "Cancelled running job: hg outgoing");
+        assert_eq!(app.jobs.jobs().len(), 1);
+        assert!(
+            app.log_entries
+                .last()
+                .is_some_and(|entry| entry.text.contains("CANCELLED: hg outgoing"))
+        );
     }
 
     #[tokio::test(flavor = "current_thread")]
-    async fn manual_refresh_uses_full_snapshot_mode() {
+    async fn killing_a_running_write_needs_confirmation() {
         let mut app = make_app();
-        let client = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
-        app.hg = client.clone();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.run_hg_action(HgAction::RebaseSourceDest {
+            source_rev: 3,
+            dest_rev: 5,
+        });
+        let job_id = app.jobs.jobs()[0].id;
+        let snapshot = RepoSnapshot {
+            repo_root: Some("/nonexistent-easyhg-repo".to_string()),
+            ..RepoSnapshot::default()
+        };
+        let capture = undo_capture("hg rebase", UndoKind::Rewrite, &snapshot);
+        app.pending_undo.insert(job_id, capture);
 
-        app.refresh_snapshot(false);
-        let snapshot_event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
-            .await
-            .expect("snapshot timeout")
-            .expect("snapshot event");
-        app.handle_app_event(snapshot_event);
+        app.dispatch_action(ActionId::CancelJob);
+        assert!(app.cancel_prompt.is_some());
+        app.handle_key(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE));
+        assert!(app.cancel_prompt.is_none());
+        assert_eq!(app.jobs.jobs().len(), 1);
 
-        let calls = client.calls();
-        assert_eq!(calls.len(), 1);
-        assert!(calls[0].include_revisions);
-        assert_eq!(calls[0].revision_limit, LOG_LIMIT);
+        app.dispatch_action(ActionId::CancelJob);
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        assert!(app.jobs.is_empty());
+        assert!(app.pending_undo.is_empty());
+        assert!(app.status_line.contains("hg recover"));
     }
 
-    #[test]
-    fn rebase_picker_requires_distinct_source_and_destination() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn pull_success_shows_parsed_summary() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.revisions = vec![revision_fixture(12)];
-        app.rev_idx = 0;
-
-        app.dispatch_action(ActionId::RebaseSelected);
-        assert_eq!(app.pending_rebase_source, Some(12));
-        assert!(app.confirmation.is_none());
-
-        app.dispatch_action(ActionId::RebaseSelected);
-        assert_eq!(app.pending_rebase_source, Some(12));
-        assert!(app.confirmation.is_none());
-        assert!(app.status_line.contains("different destination"));
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Pull,
+            action_preview: "hg pull -u".to_string(),
+            show_output: false,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: "hg pull -u".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: "pulling from /srv/repo\nadded 3 changesets with 3 changes to 2 files\n2 files updated, 0 files merged, 0 files removed, 0 files unresolved\n".to_string(),
+                stderr: String::new(),
+            }),
+        });
+        assert!(
+            app.status_line
+                .starts_with("Pull: 3 new changeset(s), working copy updated")
+        );
     }
 
-    #[test]
-    fn rebase_picker_two_step_sets_confirmation() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn incoming_and_outgoing_use_remembered_paths() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.revisions = vec![revision_fixture(20), revision_fixture(18)];
-        app.rev_idx = 0;
+        app.snapshot.repo_root = Some("/repo".to_string());
+        app.snapshot.paths = vec![
+            path_fixture("default", "https://example.com/repo"),
+            path_fixture("upstream", "/srv/upstream"),
+        ];
 
-        app.dispatch_action(ActionId::RebaseSelected);
-        assert_eq!(app.pending_rebase_source, Some(20));
-        assert!(app.confirmation.is_none());
-        assert!(app.status_line.contains("step 1/2"));
+        app.dispatch_action(ActionId::Incoming);
+        assert_eq!(app.status_line, "Loading incoming changesets…");
 
-        app.rev_idx = 1;
-        app.dispatch_action(ActionId::RebaseSelected);
-        assert_eq!(app.pending_rebase_source, None);
-        assert!(app.status_line.contains("step 2/2"));
+        app.dispatch_action(ActionId::Pull);
+        assert_eq!(app.path_picker.as_ref().expect("picker").selected, 0);
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.status_line, "Running: hg pull -u upstream");
+        assert_eq!(app.pull_mode, PullMode::Update);
 
-        let confirm = app.confirmation.as_ref().expect("rebase confirmation");
-        assert!(confirm.message.contains("20"));
-        assert!(confirm.message.contains("18"));
-        match &confirm.action {
-            PendingRunAction::Hg(HgAction::RebaseSourceDest {
-                source_rev,
-                dest_rev,
-            }) => {
-                assert_eq!(*source_rev, 20);
-                assert_eq!(*dest_rev, 18);
-            }
-            other => panic!("unexpected action: {other:?}"),
-        }
+        app.dispatch_action(ActionId::Incoming);
+        assert_eq!(app.status_line, "Loading incoming changesets (upstream)…");
+        app.dispatch_action(ActionId::Outgoing);
+        assert_eq!(app.status_line, "Loading outgoing changesets…");
     }
 
-    #[test]
-    fn esc_cancels_rebase_picker_source_selection() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn push_without_configured_paths_previews_default() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.revisions = vec![revision_fixture(20), revision_fixture(18)];
-        app.rev_idx = 0;
-        app.dispatch_action(ActionId::RebaseSelected);
-        assert_eq!(app.pending_rebase_source, Some(20));
-
-        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
-        assert_eq!(app.pending_rebase_source, None);
-        assert_eq!(app.status_line, "Rebase selection cancelled.");
+        app.dispatch_action(ActionId::Push);
+        assert!(app.path_picker.is_none());
+        assert_eq!(app.status_line, "Checking outgoing: hg outgoing");
+    }
+
+    fn outgoing_result(success: bool, stdout: &str, stderr: &str) -> CommandResult {
+        CommandResult {
+            command_preview: "hg outgoing".to_string(),
+            success,
+            exit_code: Some(if success { 0 } else { 1 }),
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+        }
     }
 
-    #[test]
-    fn rebase_continue_blocked_without_in_progress_rebase() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn scoped_push_confirms_with_restricted_outgoing_preview() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.rebase.in_progress = false;
+        app.snapshot.paths = vec![path_fixture("default", "https://example.com/repo")];
+        app.snapshot.revisions = vec![revision_fixture(7)];
 
-        app.dispatch_action(ActionId::RebaseContinue);
-        assert!(app.confirmation.is_none());
-        assert_eq!(app.status_line, "No rebase is currently in progress.");
-        assert!(
-            app.detail_text
-                .contains("No rebase is currently in progress.")
+        app.dispatch_action(ActionId::PushRevision);
+        assert_eq!(
+            app.path_picker.as_ref().expect("picker").scope,
+            SyncScope::Rev("7".to_string())
+        );
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.status_line,
+            "Checking outgoing: hg outgoing -r 7 default"
         );
+
+        app.handle_app_event(AppEvent::PushPreviewLoaded {
+            push: HgAction::Push {
+                dest: Some("default".to_string()),
+                scope: SyncScope::Rev("7".to_string()),
+                new_branch: false,
+            },
+            target: "'default' (https://example.com/repo)".to_string(),
+            result: Ok(outgoing_result(
+                true,
+                "comparing with default\nchangeset: 7:abc\n",
+                "",
+            )),
+            outgoing: None,
+        });
+        let confirm = app.confirmation.as_ref().expect("push confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg push -r 7 default");
+        assert!(confirm.message.contains("revision 7 and its ancestors"));
+        assert!(confirm.message.contains("changeset: 7:abc"));
     }
 
     #[test]
-    fn rebase_continue_blocked_with_unresolved_conflicts() {
+    fn push_preview_with_no_changes_skips_confirmation() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.rebase.in_progress = true;
-        app.snapshot.rebase.unresolved_conflicts = 2;
-
-        app.dispatch_action(ActionId::RebaseContinue);
+        app.handle_app_event(AppEvent::PushPreviewLoaded {
+            push: HgAction::Push {
+                dest: None,
+                scope: SyncScope::Bookmark("feature".to_string()),
+                new_branch: false,
+            },
+            target: "the default path".to_string(),
+            result: Ok(outgoing_result(
+                false,
+                "comparing with default\nno changes found\n",
+                "",
+            )),
+            outgoing: None,
+        });
         assert!(app.confirmation.is_none());
-        assert!(app.status_line.contains("Cannot continue rebase"));
-        assert!(app.detail_text.contains("2 unresolved conflict"));
+        assert!(app.status_line.contains("Nothing to push"));
     }
 
-    #[test]
-    fn rebase_continue_and_abort_open_confirmations_when_in_progress_and_clear() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn push_refused_for_new_branch_offers_retry_with_new_branch() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.rebase.in_progress = true;
-        app.snapshot.rebase.unresolved_conflicts = 0;
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.run_hg_action(HgAction::Push {
+            dest: None,
+            scope: SyncScope::Branch("feature".to_string()),
+            new_branch: false,
+        });
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Push,
+            action_preview: "hg push -b feature".to_string(),
+            show_output: false,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: "hg push -b feature".to_string(),
+                success: false,
+                exit_code: Some(1),
+                stdout: "pushing to default\n".to_string(),
+                stderr: "abort: push creates new remote branches: feature\n(use 'hg push --new-branch' to create new remote branches)\n".to_string(),
+            }),
+        });
+        let confirm = app.confirmation.as_ref().expect("new branch retry");
+        assert_eq!(
+            confirm.action.command_preview(),
+            "hg push -b feature --new-branch"
+        );
+    }
 
-        app.dispatch_action(ActionId::RebaseContinue);
-        match app.confirmation.as_ref().map(|c| &c.action) {
-            Some(PendingRunAction::Hg(HgAction::RebaseContinue)) => {}
-            other => panic!("unexpected continue confirmation: {other:?}"),
-        }
+    #[tokio::test(flavor = "current_thread")]
+    async fn only_pushing_a_secret_revision_itself_is_refused() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.snapshot.paths = vec![path_fixture("default", "https://example.com/repo")];
+        let mut secret = revision_fixture(4);
+        secret.phase = "secret".to_string();
+        app.snapshot.revisions = vec![secret];
+        app.snapshot.working_parent_rev = Some(4);
+
+        // hg push leaves secret changesets behind on its own; the rest still goes out.
+        app.dispatch_action(ActionId::Push);
+        assert!(app.path_picker.is_some());
+
+        app.path_picker = None;
+        app.dispatch_action(ActionId::PushRevision);
+        assert!(app.path_picker.is_none());
+        assert!(
+            app.status_line
+                .contains("is secret and would not be pushed")
+        );
+    }
 
-        app.confirmation = None;
-        app.dispatch_action(ActionId::RebaseAbort);
-        match app.confirmation.as_ref().map(|c| &c.action) {
-            Some(PendingRunAction::Hg(HgAction::RebaseAbort)) => {}
-            other => panic!("unexpected abort confirmation: {other:?}"),
+    fn bookmark_fixture(name: &str, rev: i64, active: bool) -> crate::domain::Bookmark {
+        crate::domain::Bookmark {
+            name: name.to_string(),
+            rev,
+            node: format!("node-{rev}"),
+            active,
         }
     }
 
     #[test]
-    fn rebase_abort_blocked_without_in_progress_rebase() {
+    fn update_from_branches_panel_targets_branch_head() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.snapshot.rebase.in_progress = false;
+        app.focus = FocusPanel::Branches;
+        app.snapshot.branches = vec![crate::domain::Branch {
+            name: "stable".to_string(),
+            rev: 8,
+            node: "node-8".to_string(),
+            active: true,
+            closed: false,
+        }];
 
-        app.dispatch_action(ActionId::RebaseAbort);
-        assert!(app.confirmation.is_none());
-        assert_eq!(app.status_line, "No rebase is currently in progress.");
+        app.dispatch_action(ActionId::UpdateSelected);
+        let confirm = app.confirmation.as_ref().expect("update confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg update stable");
+        assert!(confirm.message.contains("head of branch 'stable'"));
     }
 
-    #[test]
-    fn rebase_action_without_extension_sets_actionable_detail_help() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn close_branch_toggle_marks_next_commit_and_resets_after_success() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = false;
-        app.snapshot.revisions = vec![revision_fixture(20)];
-        app.rev_idx = 0;
+        app.snapshot.branch = Some("feature".to_string());
 
-        app.dispatch_action(ActionId::RebaseSelected);
-        assert_eq!(app.status_line, "Rebase extension not enabled.");
-        assert!(app.detail_text.contains("[extensions]"));
-        assert!(app.detail_text.contains("rebase ="));
+        app.dispatch_action(ActionId::ToggleCloseBranch);
+        assert!(app.close_branch_on_commit);
+        assert!(app.status_line.contains("close branch 'feature'"));
+
+        app.dispatch_action(ActionId::Commit);
+        let input = app.input.as_mut().expect("commit input");
+        assert!(input.title.ends_with("[--close-branch]"));
+        input.value = "done with feature".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.status_line,
+            "Running: hg commit --close-branch -m <message>"
+        );
+
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Other,
+            action_preview: "hg commit --close-branch -m <message>".to_string(),
+            show_output: false,
+            clear_commit_selection: true,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: "hg commit --close-branch -m <message>".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            }),
+        });
+        assert!(!app.close_branch_on_commit);
     }
 
-    #[test]
-    fn snapshot_logs_rebase_unavailable_notice_once() {
+    fn tag_fixture(name: &str, rev: i64, local: bool) -> crate::domain::Tag {
+        crate::domain::Tag {
+            name: name.to_string(),
+            rev,
+            node: format!("node-{rev}"),
+            local,
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn jump_to_revision_selects_tagged_revision() {
         let mut app = make_app();
-        let snapshot = RepoSnapshot {
-            capabilities: crate::domain::HgCapabilities {
-                has_rebase: false,
-                ..crate::domain::HgCapabilities::default()
-            },
-            ..RepoSnapshot::default()
-        };
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.focus = FocusPanel::Tags;
+        app.snapshot.revisions = vec![revision_fixture(9), revision_fixture(5)];
+        app.snapshot.tags = vec![tag_fixture("v1.0", 5, false), tag_fixture("old", 2, false)];
 
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(snapshot.clone()),
-        });
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(snapshot),
-        });
+        app.dispatch_action(ActionId::JumpToRevision);
+        assert_eq!(app.focus, FocusPanel::Revisions);
+        assert_eq!(app.rev_idx, 1);
 
-        let count = app
-            .log_lines
-            .iter()
-            .filter(|line| line.contains("Rebase unavailable"))
-            .count();
-        assert_eq!(count, 1);
+        app.focus = FocusPanel::Tags;
+        app.tags_idx = 1;
+        app.dispatch_action(ActionId::JumpToRevision);
+        assert_eq!(app.focus, FocusPanel::Tags);
+        assert!(app.status_line.contains("not in the loaded history"));
     }
 
-    #[test]
-    fn snapshot_rebase_hint_indicates_continue_when_conflicts_resolved() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn regular_tag_confirms_commit_but_local_tag_runs_directly() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_rebase = true;
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot {
-                capabilities: crate::domain::HgCapabilities {
-                    has_rebase: true,
-                    ..crate::domain::HgCapabilities::default()
-                },
-                rebase: crate::domain::RebaseState {
-                    in_progress: true,
-                    unresolved_conflicts: 0,
-                    resolved_conflicts: 2,
-                    total_conflicts: 2,
-                },
-                ..RepoSnapshot::default()
-            }),
-        });
-        assert!(app.status_line.contains("all conflicts resolved"));
-        assert!(
-            app.log_lines
-                .iter()
-                .any(|line| line.contains("all conflicts resolved"))
-        );
+        app.snapshot.revisions = vec![revision_fixture(5)];
+
+        app.dispatch_action(ActionId::TagCreate);
+        app.input.as_mut().expect("tag input").value = "v1.0".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let confirm = app.confirmation.take().expect("regular tag confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg tag -r 5 v1.0");
+        assert!(confirm.message.contains("create a new commit"));
+
+        app.dispatch_action(ActionId::TagCreateLocal);
+        app.input.as_mut().expect("local tag input").value = "wip".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "Running: hg tag -l -r 5 wip");
     }
 
     #[test]
-    fn snapshot_rebase_hint_indicates_remaining_conflicts() {
+    fn tag_remove_explains_commit_and_refuses_tip() {
         let mut app = make_app();
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot {
-                capabilities: crate::domain::HgCapabilities {
-                    has_rebase: true,
-                    ..crate::domain::HgCapabilities::default()
-                },
-                rebase: crate::domain::RebaseState {
-                    in_progress: true,
-                    unresolved_conflicts: 3,
-                    resolved_conflicts: 1,
-                    total_conflicts: 4,
-                },
-                ..RepoSnapshot::default()
-            }),
-        });
-        assert!(app.status_line.contains("3 unresolved conflict"));
+        app.snapshot.tags = vec![tag_fixture("tip", 9, false), tag_fixture("v1.0", 5, false)];
+
+        app.dispatch_action(ActionId::TagRemove);
+        assert!(app.confirmation.is_none());
+        assert_eq!(app.status_line, "'tip' is not a removable tag.");
+
+        app.tags_idx = 1;
+        app.dispatch_action(ActionId::TagRemove);
+        let confirm = app.confirmation.as_ref().expect("remove confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg tag --remove v1.0");
+        assert!(confirm.message.contains("creates a new commit"));
     }
 
     #[test]
-    fn snapshot_rebase_completion_emits_completion_status() {
+    fn bookmark_delete_and_move_require_confirmation() {
         let mut app = make_app();
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot {
-                capabilities: crate::domain::HgCapabilities {
-                    has_rebase: true,
-                    ..crate::domain::HgCapabilities::default()
-                },
-                rebase: crate::domain::RebaseState {
-                    in_progress: true,
-                    unresolved_conflicts: 0,
-                    resolved_conflicts: 0,
-                    total_conflicts: 0,
-                },
-                ..RepoSnapshot::default()
-            }),
-        });
-        app.handle_app_event(AppEvent::SnapshotLoaded {
-            preserve_details: true,
-            include_revisions: true,
-            result: Ok(RepoSnapshot {
-                capabilities: crate::domain::HgCapabilities {
-                    has_rebase: true,
-                    ..crate::domain::HgCapabilities::default()
-                },
-                rebase: crate::domain::RebaseState {
-                    in_progress: false,
-                    unresolved_conflicts: 0,
-                    resolved_conflicts: 0,
-                    total_conflicts: 0,
-                },
-                ..RepoSnapshot::default()
-            }),
-        });
-        assert_eq!(app.status_line, "Rebase is no longer in progress.");
+        app.snapshot.bookmarks = vec![bookmark_fixture("main", 3, false)];
+        app.snapshot.revisions = vec![revision_fixture(5)];
+
+        app.dispatch_action(ActionId::BookmarkDelete);
+        let confirm = app.confirmation.take().expect("delete confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg bookmark -d main");
+
+        app.dispatch_action(ActionId::BookmarkMove);
+        let confirm = app.confirmation.take().expect("move confirmation");
+        assert_eq!(confirm.action.command_preview(), "hg bookmark -f -r 5 main");
+        assert!(confirm.message.contains("from revision 3 to revision 5"));
     }
 
-    #[test]
-    fn update_selected_without_target_sets_status_message() {
+    #[tokio::test(flavor = "current_thread")]
+    async fn bookmark_rename_prefills_current_name() {
         let mut app = make_app();
-        app.focus = FocusPanel::Revisions;
-        app.snapshot.revisions.clear();
+        app.snapshot.bookmarks = vec![bookmark_fixture("main", 3, false)];
 
-        app.dispatch_action(ActionId::UpdateSelected);
-        assert_eq!(app.status_line, "No revision selected.");
+        app.dispatch_action(ActionId::BookmarkRename);
+        let input = app.input.as_mut().expect("rename input");
+        assert_eq!(input.value, "main");
+        input.value = "trunk".to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.status_line, "Running: hg bookmark -m main trunk");
+    }
 
-        app.focus = FocusPanel::Bookmarks;
-        app.snapshot.bookmarks.clear();
-        app.dispatch_action(ActionId::UpdateSelected);
-        assert_eq!(app.status_line, "No bookmark selected.");
+    #[test]
+    fn bookmark_deactivate_requires_active_bookmark() {
+        let mut app = make_app();
+        app.snapshot.bookmarks = vec![bookmark_fixture("main", 3, false)];
+        app.dispatch_action(ActionId::BookmarkDeactivate);
+        assert_eq!(app.status_line, "No active bookmark to deactivate.");
     }
 
     #[test]
-    fn histedit_without_selected_revision_sets_status_message() {
+    fn backout_selected_prefills_message_and_uses_merge_off_working_parent() {
         let mut app = make_app();
-        app.snapshot.capabilities.has_histedit = true;
-        app.snapshot.revisions.clear();
+        app.snapshot.revisions = vec![revision_fixture(5), revision_fixture(4)];
+        app.snapshot.working_parent_rev = Some(5);
+        app.rev_idx = 1;
 
-        app.dispatch_action(ActionId::HisteditSelected);
-        assert_eq!(app.status_line, "No revision selected for histedit.");
+        app.dispatch_action(ActionId::BackoutSelected);
+        let input = app.input.as_ref().expect("backout editor");
+        assert_eq!(input.value, "Backed out changeset node-4");
+        assert!(input.title.contains("--merge"));
+        assert!(matches!(
+            input.purpose,
+            InputPurpose::BackoutMessage {
+                rev: 4,
+                merge: true
+            }
+        ));
+
+        app.input = None;
+        app.rev_idx = 0;
+        app.dispatch_action(ActionId::BackoutSelected);
+        let input = app.input.as_ref().expect("backout editor");
+        assert!(matches!(
+            input.purpose,
+            InputPurpose::BackoutMessage {
+                rev: 5,
+                merge: false
+            }
+        ));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn backout_conflict_failure_points_to_conflicts_panel() {
+        let mut app = make_app();
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Backout,
+            action_preview: "hg backout -r 4 --merge -m <message>".to_string(),
+            show_output: false,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: "hg backout -r 4 --merge -m msg".to_string(),
+                success: false,
+                exit_code: Some(1),
+                stdout: "merging a.txt\nwarning: conflicts while merging a.txt!".to_string(),
+                stderr: "use 'hg resolve' to retry unresolved file merges".to_string(),
+            }),
+        });
+        assert!(app.status_line.contains("Conflicts panel"));
     }
 
     #[test]
@@ -2664,24 +6589,24 @@ mod tests {
         app.handle_app_event(AppEvent::SnapshotLoaded {
             preserve_details: true,
             include_revisions: true,
-            result: Ok(flat_snapshot.clone()),
+            result: Ok(Box::new(flat_snapshot.clone())),
         });
         let warning_count_after_first = app
-            .log_lines
+            .log_entries
             .iter()
-            .filter(|line| line.contains("Commit graph unavailable"))
+            .filter(|line| line.text.contains("Commit graph unavailable"))
             .count();
         assert_eq!(warning_count_after_first, 1);
 
         app.handle_app_event(AppEvent::SnapshotLoaded {
             preserve_details: true,
             include_revisions: true,
-            result: Ok(flat_snapshot),
+            result: Ok(Box::new(flat_snapshot)),
         });
         let warning_count_after_second = app
-            .log_lines
+            .log_entries
             .iter()
-            .filter(|line| line.contains("Commit graph unavailable"))
+            .filter(|line| line.text.contains("Commit graph unavailable"))
             .count();
         assert_eq!(warning_count_after_second, 1);
     }
@@ -2786,7 +6711,7 @@ mod tests {
         app.snapshot.repo_root = Some("/repo".to_string());
         app.snapshot.branch = Some("default".to_string());
         app.snapshot.files = vec![crate::domain::FileChange {
-            path: "src/main.rs".to_string(),
+            path: "src/main.rs".into(),
             status: crate::domain::FileStatus::Modified,
         }];
         app.snapshot.revisions = vec![crate::domain::Revision {
@@ -2799,6 +6724,9 @@ mod tests {
             tags: Vec::new(),
             bookmarks: Vec::new(),
             date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
             graph_prefix: None,
         }];
         let mut env = HashMap::new();
@@ -2891,6 +6819,9 @@ mod tests {
             tags: Vec::new(),
             bookmarks: Vec::new(),
             date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
             graph_prefix: None,
         }];
         let mut env = HashMap::new();
@@ -2930,6 +6861,9 @@ mod tests {
             tags: Vec::new(),
             bookmarks: Vec::new(),
             date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
             graph_prefix: None,
         }];
         let command = CustomCommand {
@@ -2987,25 +6921,25 @@ mod tests {
     fn toggle_file_selection_adds_and_removes_path() {
         let mut app = make_app();
         app.snapshot.files = vec![crate::domain::FileChange {
-            path: "src/main.rs".to_string(),
+            path: "src/main.rs".into(),
             status: crate::domain::FileStatus::Modified,
         }];
         app.files_idx = 0;
 
         app.toggle_selected_file_for_commit();
-        assert!(app.is_file_selected_for_commit("src/main.rs"));
+        assert!(app.is_file_selected_for_commit(&"src/main.rs".into()));
         assert_eq!(app.selected_file_commit_count(), 1);
 
         app.toggle_selected_file_for_commit();
-        assert!(!app.is_file_selected_for_commit("src/main.rs"));
+        assert!(!app.is_file_selected_for_commit(&"src/main.rs".into()));
         assert_eq!(app.selected_file_commit_count(), 0);
     }
 
     #[test]
     fn clear_file_selection_empties_selection() {
         let mut app = make_app();
-        app.commit_file_selection.insert("a".to_string());
-        app.commit_file_selection.insert("b".to_string());
+        app.commit_file_selection.insert("a".into());
+        app.commit_file_selection.insert("b".into());
         app.clear_file_selection();
         assert_eq!(app.selected_file_commit_count(), 0);
         assert!(app.status_line.contains("Cleared commit file selection"));
@@ -3014,15 +6948,19 @@ mod tests {
     #[tokio::test(flavor = "current_thread")]
     async fn successful_commit_action_event_clears_selected_files() {
         let mut app = make_app();
-        app.commit_file_selection.insert("src/app.rs".to_string());
+        app.commit_file_selection.insert("src/app.rs".into());
         app.handle_app_event(AppEvent::ActionFinished {
             action_kind: ActionOutcomeKind::Other,
             action_preview: "hg commit -m <message> <1 files>".to_string(),
             show_output: false,
             clear_commit_selection: true,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
             result: Ok(CommandResult {
                 command_preview: "hg commit -m test src/app.rs".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: String::new(),
                 stderr: String::new(),
             }),
@@ -3038,9 +6976,13 @@ mod tests {
             action_preview: "hg rebase -s 5 -d 2".to_string(),
             show_output: false,
             clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
             result: Ok(CommandResult {
                 command_preview: "hg rebase -s 5 -d 2".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: String::new(),
                 stderr: String::new(),
             }),
@@ -3059,9 +7001,13 @@ mod tests {
             action_preview: "hg resolve -m src/main.rs".to_string(),
             show_output: false,
             clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
             result: Ok(CommandResult {
                 command_preview: "hg resolve -m src/main.rs".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: String::new(),
                 stderr: String::new(),
             }),
@@ -3077,9 +7023,13 @@ mod tests {
             action_preview: "hg rebase --continue".to_string(),
             show_output: false,
             clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
             result: Ok(CommandResult {
                 command_preview: "hg rebase --continue".to_string(),
                 success: false,
+                exit_code: Some(1),
                 stdout: String::new(),
                 stderr: "abort: unresolved conflicts".to_string(),
             }),
@@ -3091,15 +7041,19 @@ mod tests {
     #[tokio::test(flavor = "current_thread")]
     async fn failed_commit_action_event_keeps_selected_files() {
         let mut app = make_app();
-        app.commit_file_selection.insert("src/app.rs".to_string());
+        app.commit_file_selection.insert("src/app.rs".into());
         app.handle_app_event(AppEvent::ActionFinished {
             action_kind: ActionOutcomeKind::Other,
             action_preview: "hg commit -m <message> <1 files>".to_string(),
             show_output: false,
             clear_commit_selection: true,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
             result: Ok(CommandResult {
                 command_preview: "hg commit -m test src/app.rs".to_string(),
                 success: false,
+                exit_code: Some(1),
                 stdout: String::new(),
                 stderr: "abort: no username configured".to_string(),
             }),
@@ -3110,7 +7064,7 @@ mod tests {
     #[test]
     fn interactive_commit_input_creates_request() {
         let mut app = make_app();
-        app.commit_file_selection.insert("src/app.rs".to_string());
+        app.commit_file_selection.insert("src/app.rs".into());
         app.input = Some(InputState {
             title: "Interactive".to_string(),
             value: "msg".to_string(),
@@ -3123,7 +7077,7 @@ mod tests {
             .as_ref()
             .expect("interactive request created");
         assert_eq!(request.message, "msg");
-        assert_eq!(request.files, vec!["src/app.rs".to_string()]);
+        assert_eq!(request.files, vec![RepoPath::from("src/app.rs")]);
     }
 
     #[test]
@@ -3183,4 +7137,554 @@ mod tests {
         });
         assert!(!app.is_double_click(FocusPanel::Files, Some(1), MouseButton::Left));
     }
+
+    fn undo_capture(label: &str, kind: UndoKind, snapshot: &RepoSnapshot) -> UndoCapture {
+        let root = snapshot.repo_root.as_deref().expect("repo root");
+        UndoCapture::from_state(
+            label.to_string(),
+            kind,
+            Path::new(root),
+            RepoState::capture(snapshot),
+        )
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn undo_previews_and_reverts_last_commit() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.dispatch_action(ActionId::Undo);
+        assert_eq!(app.status_line, "Nothing to undo.");
+
+        app.snapshot = RepoSnapshot {
+            repo_root: Some("/nonexistent-easyhg-repo".to_string()),
+            working_parent_rev: Some(0),
+            revisions: vec![revision_fixture(0)],
+            bookmarks: vec![bookmark_fixture("main", 0, true)],
+            ..RepoSnapshot::default()
+        };
+        let action = HgAction::Commit {
+            message: "oops".to_string(),
+            files: Vec::new(),
+            close_branch: false,
+        };
+        let undo = UndoKind::for_action(&action)
+            .map(|kind| undo_capture(&action.command_preview(), kind, &app.snapshot));
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Other,
+            action_preview: action.command_preview(),
+            show_output: false,
+            clear_commit_selection: true,
+            started_at: Local::now(),
+            job_id: 1,
+            undo,
+            result: Ok(CommandResult {
+                command_preview: action.command_preview(),
+                success: true,
+                exit_code: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            }),
+        });
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: false,
+            include_revisions: true,
+            result: Ok(Box::new(RepoSnapshot {
+                repo_root: Some("/nonexistent-easyhg-repo".to_string()),
+                working_parent_rev: Some(1),
+                revisions: vec![revision_fixture(1), revision_fixture(0)],
+                bookmarks: vec![bookmark_fixture("main", 1, true)],
+                ..RepoSnapshot::default()
+            })),
+        });
+        assert_eq!(app.undo_stack.len(), 1);
+
+        app.dispatch_action(ActionId::Undo);
+        let confirm = app.confirmation.clone().expect("undo confirmation");
+        assert!(confirm.message.contains("Strip the new commit node-1"));
+        assert!(
+            confirm
+                .message
+                .contains("Move bookmark main back to node-0")
+        );
+        match &confirm.action {
+            PendingRunAction::Hg(HgAction::Undo { steps }) => {
+                assert_eq!(
+                    steps[0].args,
+                    [
+                        "--config",
+                        "extensions.strip=",
+                        "strip",
+                        "--keep",
+                        "-r",
+                        "node-1"
+                    ]
+                );
+            }
+            other => panic!("unexpected undo action: {other:?}"),
+        }
+
+        app.confirmation = None;
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::Undo,
+            action_preview: confirm.action.command_preview(),
+            show_output: false,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: confirm.action.command_preview(),
+                success: true,
+                exit_code: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            }),
+        });
+        assert!(app.undo_stack.is_empty());
+        assert_eq!(app.status_line, "Undid: hg commit -m <message>");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn undo_captures_state_at_job_start_and_keeps_every_pending_action() {
+        let state = |parent: i64, revs: &[i64]| RepoSnapshot {
+            repo_root: Some("/nonexistent-easyhg-repo".to_string()),
+            working_parent_rev: Some(parent),
+            revisions: revs
+                .iter()
+                .rev()
+                .map(|rev| revision_fixture(*rev))
+                .collect(),
+            bookmarks: vec![bookmark_fixture("main", parent, true)],
+            ..RepoSnapshot::default()
+        };
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(state(0, &[0])));
+        // The app's copy lags behind what the job sees when it starts.
+        app.snapshot = RepoSnapshot {
+            repo_root: Some("/nonexistent-easyhg-repo".to_string()),
+            ..RepoSnapshot::default()
+        };
+        let commit = |message: &str| HgAction::Commit {
+            message: message.to_string(),
+            files: Vec::new(),
+            close_branch: false,
+        };
+        app.start_pending_action(PendingRunAction::Hg(commit("first")));
+        let first = loop {
+            let event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+                .await
+                .expect("event timeout")
+                .expect("event");
+            if let AppEvent::ActionFinished { job_id, undo, .. } = event {
+                break (job_id, undo.expect("undo capture"));
+            }
+        };
+        assert_eq!(first.1.before.parent.as_deref(), Some("node-0"));
+
+        let second = undo_capture("second", UndoKind::Commit, &state(1, &[0, 1]));
+        let second_id = first.0 + 1;
+        for (job_id, undo) in [first, (second_id, second)] {
+            app.handle_app_event(AppEvent::ActionFinished {
+                action_kind: ActionOutcomeKind::Other,
+                action_preview: "hg commit".to_string(),
+                show_output: false,
+                clear_commit_selection: false,
+                started_at: Local::now(),
+                job_id,
+                undo: Some(undo),
+                result: Ok(CommandResult {
+                    command_preview: "hg commit".to_string(),
+                    success: true,
+                    exit_code: Some(0),
+                    stdout: String::new(),
+                    stderr: String::new(),
+                }),
+            });
+        }
+        app.handle_app_event(AppEvent::SnapshotLoaded {
+            preserve_details: false,
+            include_revisions: true,
+            result: Ok(Box::new(state(2, &[0, 1, 2]))),
+        });
+
+        let afters = app
+            .undo_stack
+            .iter()
+            .map(|entry| entry.after.parent.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(afters, vec![Some("node-1"), Some("node-2")]);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn destructive_actions_write_backup_bundle_and_backups_view_restores() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        let root = temp_repo_dir();
+        std::fs::create_dir_all(root.join(".hg")).expect("repo dir");
+        app.snapshot.repo_root = Some(root.display().to_string());
+
+        app.run_hg_action(HgAction::RebaseSourceDest {
+            source_rev: 3,
+            dest_rev: 5,
+        });
+        let event = tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+            .await
+            .expect("action event")
+            .expect("event");
+        assert!(matches!(
+            event,
+            AppEvent::ActionFinished { result: Ok(_), .. }
+        ));
+        let index = std::fs::read_to_string(backups::backup_dir(&root).join("index.toml"))
+            .expect("backup index");
+        assert!(index.contains("operation = \"hg rebase -s 3 -d 5\""));
+        assert!(index.contains("revset = \"3::\""));
+
+        app.dispatch_action(ActionId::ShowBackups);
+        assert!(app.backup_picker.is_none(), "bundle file was never written");
+        let strip_backup = root.join(".hg").join("strip-backup");
+        std::fs::create_dir_all(&strip_backup).expect("strip-backup dir");
+        std::fs::write(strip_backup.join("abc-amend.hg"), b"bundle").expect("bundle");
+        app.dispatch_action(ActionId::ShowBackups);
+        assert_eq!(app.backup_picker.as_ref().expect("picker").entries.len(), 1);
+        app.handle_key(KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::ALT));
+        assert!(app.backup_picker.is_none());
+        match app.confirmation.as_ref().map(|confirm| &confirm.action) {
+            Some(PendingRunAction::Hg(HgAction::Unbundle { file })) => {
+                assert_eq!(file, &strip_backup.join("abc-amend.hg"));
+            }
+            other => panic!("unexpected confirmation: {other:?}"),
+        }
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn editor_histedit_writes_backup_before_launching() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        let root = temp_repo_dir();
+        std::fs::create_dir_all(root.join(".hg")).expect("repo dir");
+        app.snapshot.repo_root = Some(root.display().to_string());
+        app.histedit_plan = Some(HisteditPlanState {
+            base_rev: 3,
+            rows: vec![HisteditPlanStep {
+                action: HisteditAction::Mess,
+                rev: 3,
+                node: "node-3".to_string(),
+                desc: "desc-3".to_string(),
+            }],
+            selected: 0,
+        });
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let backup = app
+            .interactive_histedit_request
+            .as_ref()
+            .and_then(|request| request.backup.clone())
+            .expect("backup planned");
+        assert_eq!(backup.entry.revset, "3::.");
+        assert!(app.write_foreground_backup(Some(&backup)).await);
+        let index = std::fs::read_to_string(backups::backup_dir(&root).join("index.toml"))
+            .expect("backup index");
+        assert!(index.contains("revset = \"3::.\""));
+
+        // A repository root that is not a directory makes the bundle fail.
+        let file_root = root.join("not-a-dir");
+        std::fs::write(&file_root, b"").expect("file");
+        let broken = SafetyBackup::new(&file_root, "hg histedit", "3::.".to_string());
+        assert!(!app.write_foreground_backup(Some(&broken)).await);
+        assert_eq!(
+            app.status_line,
+            "Safety backup failed, so `hg histedit` was not run."
+        );
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn disabled_backup_bundles_skip_safety_backup() {
+        let config = AppConfig {
+            backup_bundles: false,
+            ..AppConfig::default()
+        };
+        let mut app = App::new(config).expect("app");
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        let root = temp_repo_dir();
+        std::fs::create_dir_all(root.join(".hg")).expect("repo dir");
+        app.snapshot.repo_root = Some(root.display().to_string());
+
+        app.run_hg_action(HgAction::Prune { rev: 4 });
+        tokio::time::timeout(Duration::from_secs(3), app.event_rx.recv())
+            .await
+            .expect("action event")
+            .expect("event");
+        assert!(!backups::backup_dir(&root).exists());
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    fn protected_app(mode: &str) -> App {
+        let config = AppConfig {
+            protected: crate::config::ProtectedConfig {
+                names: vec!["main".to_string()],
+                mode: mode.to_string(),
+                ..crate::config::ProtectedConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        let mut app = App::new(config).expect("app");
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.snapshot.bookmarks = vec![bookmark_fixture("main", 0, true)];
+        app
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn protected_bookmark_requires_typed_confirmation() {
+        let mut app = protected_app("confirm");
+        app.run_hg_action(HgAction::Commit {
+            message: "direct".to_string(),
+            files: Vec::new(),
+            close_branch: false,
+        });
+        let confirm = app.confirmation.as_ref().expect("typed confirmation");
+        assert!(
+            confirm
+                .message
+                .contains("Committing directly on protected bookmark main")
+        );
+        assert!(app.jobs.is_empty());
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.confirmation.is_some(), "y is not the protected name");
+        assert_eq!(app.status_line, "Type `main` exactly to confirm.");
+        app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        for ch in "main".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.confirmation.is_none());
+        assert!(app.status_line.starts_with("Running: hg commit"));
+
+        // An existing confirmation gains the typed step instead of a second modal.
+        app.dispatch_action(ActionId::BookmarkDelete);
+        let confirm = app.confirmation.as_ref().expect("delete confirmation");
+        assert!(confirm.typed.is_some());
+        assert!(confirm.message.contains("Changing protected bookmark main"));
+    }
+
+    #[test]
+    fn interactive_commit_and_histedit_on_protected_targets_need_typed_confirmation() {
+        let mut app = protected_app("confirm");
+        app.input = Some(InputState {
+            title: "Interactive".to_string(),
+            value: "msg".to_string(),
+            purpose: InputPurpose::CommitMessageInteractive,
+        });
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.interactive_commit_request.is_none());
+        let confirm = app.confirmation.as_ref().expect("typed confirmation");
+        assert_eq!(
+            confirm.typed.as_ref().map(|typed| typed.expected.as_str()),
+            Some("main")
+        );
+        for ch in "main".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.interactive_commit_request.is_some());
+
+        let mut app = protected_app("refuse");
+        app.snapshot.revisions = vec![Revision {
+            bookmarks: vec!["main".to_string()],
+            ..revision_fixture(3)
+        }];
+        app.histedit_plan = Some(HisteditPlanState {
+            base_rev: 3,
+            rows: vec![HisteditPlanStep {
+                action: HisteditAction::Mess,
+                rev: 3,
+                node: "node-3".to_string(),
+                desc: "desc-3".to_string(),
+            }],
+            selected: 0,
+        });
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.interactive_histedit_request.is_none());
+        assert_eq!(
+            app.status_line,
+            "Refused: Rewriting protected bookmark main."
+        );
+    }
+
+    #[test]
+    fn unscoped_push_checks_every_outgoing_changeset() {
+        let mut app = protected_app("confirm");
+        // The working directory is not on main, but main moves with the push.
+        app.snapshot.bookmarks = vec![bookmark_fixture("main", 6, false)];
+        let push = HgAction::Push {
+            dest: None,
+            scope: SyncScope::All,
+            new_branch: false,
+        };
+        let outgoing = vec![Revision {
+            bookmarks: vec!["main".to_string()],
+            ..revision_fixture(6)
+        }];
+        app.handle_app_event(AppEvent::PushPreviewLoaded {
+            push: push.clone(),
+            target: "the default path".to_string(),
+            result: Ok(outgoing_result(true, "changeset: 6:abc\n", "")),
+            outgoing: Some(outgoing),
+        });
+        let confirm = app.confirmation.take().expect("typed confirmation");
+        assert_eq!(
+            confirm.typed.as_ref().map(|typed| typed.expected.as_str()),
+            Some("main")
+        );
+        assert!(
+            confirm
+                .message
+                .contains("Pushing to protected bookmark main")
+        );
+
+        app.handle_app_event(AppEvent::PushPreviewLoaded {
+            push,
+            target: "the default path".to_string(),
+            result: Ok(outgoing_result(true, "changeset: 7:def\n", "")),
+            outgoing: Some(vec![revision_fixture(7)]),
+        });
+        let confirm = app.confirmation.as_ref().expect("plain confirmation");
+        assert!(confirm.typed.is_none());
+    }
+
+    #[test]
+    fn refuse_mode_blocks_protected_actions() {
+        let mut app = protected_app("refuse");
+        app.focus = FocusPanel::Bookmarks;
+        app.dispatch_action(ActionId::BookmarkDelete);
+        assert!(app.confirmation.is_none());
+        assert_eq!(
+            app.status_line,
+            "Refused: Changing protected bookmark main."
+        );
+    }
+
+    fn app_with_confirmations(entries: &[(&str, &str)]) -> App {
+        let config = AppConfig {
+            confirmations: entries
+                .iter()
+                .map(|(action, policy)| (action.to_string(), policy.to_string()))
+                .collect(),
+            ..AppConfig::default()
+        };
+        let mut app = App::new(config).expect("app");
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn confirmation_policies_override_builtin_choices() {
+        let mut app = app_with_confirmations(&[
+            ("rebase_selected", "never"),
+            ("bookmark_deactivate", "always"),
+            ("prune_selected", "typed"),
+        ]);
+
+        app.confirm_action(
+            PendingRunAction::Hg(HgAction::RebaseSourceDest {
+                source_rev: 3,
+                dest_rev: 5,
+            }),
+            "Rebase 3 onto 5?",
+        );
+        assert!(app.confirmation.is_none());
+        assert!(app.status_line.starts_with("Running: hg rebase"));
+
+        app.run_hg_action(HgAction::BookmarkDeactivate);
+        let confirm = app.confirmation.take().expect("always confirms");
+        assert!(confirm.typed.is_none());
+        assert!(confirm.message.contains("hg bookmark -i"));
+
+        app.confirm_action(PendingRunAction::Hg(HgAction::Prune { rev: 4 }), "Prune 4?");
+        let typed = app
+            .confirmation
+            .as_ref()
+            .and_then(|confirm| confirm.typed.as_ref())
+            .expect("typed confirmation");
+        assert_eq!(typed.expected, "prune_selected");
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.confirmation.is_some());
+    }
+
+    #[test]
+    fn never_policy_does_not_skip_protected_typed_confirmation() {
+        let mut app = protected_app("confirm");
+        app.confirmations = actions::confirmation_policies(&HashMap::from([(
+            "bookmark_delete".to_string(),
+            "never".to_string(),
+        )]));
+        app.confirm_action(
+            PendingRunAction::Hg(HgAction::BookmarkDelete {
+                name: "main".to_string(),
+            }),
+            "Delete bookmark main?",
+        );
+        let confirm = app.confirmation.as_ref().expect("still confirmed");
+        assert_eq!(
+            confirm.typed.as_ref().map(|typed| typed.expected.as_str()),
+            Some("main")
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn armed_dry_run_previews_next_action_instead_of_running_it() {
+        let mut app = make_app();
+        app.hg = Arc::new(RecordingHgClient::new(RepoSnapshot::default()));
+        app.dispatch_action(ActionId::DryRunNext);
+        assert!(app.dry_run_next);
+
+        app.confirm_action(
+            PendingRunAction::Hg(HgAction::RebaseSourceDest {
+                source_rev: 3,
+                dest_rev: 5,
+            }),
+            "Rebase 3 onto 5?",
+        );
+        assert!(
+            app.confirmation.is_none(),
+            "a preview needs no confirmation"
+        );
+        assert!(!app.dry_run_next, "dry run applies to one action");
+        assert_eq!(app.status_line, "Running: dry run: hg rebase -s 3 -d 5");
+        assert_eq!(
+            app.jobs.jobs().last().map(|job| job.kind),
+            Some(JobKind::Read)
+        );
+
+        app.handle_app_event(AppEvent::ActionFinished {
+            action_kind: ActionOutcomeKind::DryRun,
+            action_preview: "dry run: hg rebase -s 3 -d 5".to_string(),
+            show_output: true,
+            clear_commit_selection: false,
+            started_at: Local::now(),
+            job_id: 0,
+            undo: None,
+            result: Ok(CommandResult {
+                command_preview: "dry run: hg rebase -s 3 -d 5".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: "Would rebase 3 and its descendants onto 5.\n\n$ hg rebase --dry-run -s 3 -d 5\nrebasing 3:abc\n".to_string(),
+                stderr: String::new(),
+            }),
+        });
+        assert_eq!(
+            app.status_line,
+            "Dry run finished; nothing was changed. Preview of `hg rebase -s 3 -d 5` in Details."
+        );
+        assert!(app.detail_text.contains("rebasing 3:abc"));
+
+        app.dispatch_action(ActionId::DryRunNext);
+        app.dispatch_action(ActionId::DryRunNext);
+        assert!(!app.dry_run_next);
+        assert_eq!(app.status_line, "Dry run disarmed.");
+    }
 }
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::hg::{CommandResult, HgAction, HgClient};
+use crate::undo::backup_bundles;
+
+const BACKUP_DIR: &str = "easyhg-backups";
+const INDEX_FILE: &str = "index.toml";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub file: PathBuf,
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub revset: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BackupIndex {
+    #[serde(default)]
+    backups: Vec<BackupEntry>,
+}
+
+// A bundle written before a destructive action. Nothing is recorded unless
+// `hg bundle` succeeded, and the action does not run if it failed.
+#[derive(Debug, Clone)]
+pub struct SafetyBackup {
+    pub repo_root: PathBuf,
+    pub entry: BackupEntry,
+}
+
+impl SafetyBackup {
+    pub fn new(repo_root: &Path, operation: &str, revset: String) -> Self {
+        let now = Local::now();
+        let command = operation
+            .split_whitespace()
+            .nth(1)
+            .filter(|word| word.chars().all(|ch| ch.is_ascii_alphanumeric()))
+            .unwrap_or("backup");
+        let file =
+            backup_dir(repo_root).join(format!("{}-{command}.hg", now.format("%Y%m%d-%H%M%S%.3f")));
+        Self {
+            repo_root: repo_root.to_path_buf(),
+            entry: BackupEntry {
+                file,
+                operation: operation.to_string(),
+                revset,
+                created_at: now.format(TIMESTAMP_FORMAT).to_string(),
+            },
+        }
+    }
+
+    pub fn action(&self) -> HgAction {
+        HgAction::BackupBundle {
+            revset: self.entry.revset.clone(),
+            file: self.entry.file.clone(),
+        }
+    }
+
+    pub async fn write(&self, hg: &dyn HgClient) -> Result<CommandResult, String> {
+        fs::create_dir_all(backup_dir(&self.repo_root))
+            .map_err(|err| format!("failed creating backup directory: {err}"))?;
+        let out = hg
+            .run_action(&self.action(), None)
+            .await
+            .map_err(|err| err.to_string())?;
+        if out.success {
+            record(&self.repo_root, self.entry.clone())?;
+        }
+        Ok(out)
+    }
+}
+
+pub fn backup_dir(repo_root: &Path) -> PathBuf {
+    repo_root.join(".hg").join(BACKUP_DIR)
+}
+
+fn index_path(repo_root: &Path) -> PathBuf {
+    backup_dir(repo_root).join(INDEX_FILE)
+}
+
+// A missing index is empty; an unreadable one is an error so record() never
+// overwrites the entries it could not parse.
+fn load_index(repo_root: &Path) -> Result<BackupIndex, String> {
+    let path = index_path(repo_root);
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(BackupIndex::default());
+        }
+        Err(err) => return Err(format!("failed reading {path:?}: {err}")),
+    };
+    toml::from_str(&raw).map_err(|err| format!("backup index {path:?} is corrupt: {err}"))
+}
+
+pub fn record(repo_root: &Path, entry: BackupEntry) -> Result<(), String> {
+    let mut index = load_index(repo_root)?;
+    index.backups.push(entry);
+    let raw =
+        toml::to_string(&index).map_err(|err| format!("failed serializing backup index: {err}"))?;
+    let path = index_path(repo_root);
+    fs::write(&path, raw).map_err(|err| format!("failed writing {path:?}: {err}"))
+}
+
+// easyhg's own bundles plus the ones hg left in .hg/strip-backup, newest
+// first. Index entries whose bundle was deleted are skipped. When the index
+// cannot be read the strip-backup bundles are still listed, alongside the
+// error.
+pub fn list(repo_root: &Path) -> (Vec<BackupEntry>, Option<String>) {
+    let (index, warning) = match load_index(repo_root) {
+        Ok(index) => (index, None),
+        Err(err) => (BackupIndex::default(), Some(err)),
+    };
+    let mut entries = index
+        .backups
+        .into_iter()
+        .filter(|entry| entry.file.exists())
+        .collect::<Vec<_>>();
+    entries.extend(backup_bundles(repo_root).into_iter().map(|file| {
+        let created_at = fs::metadata(&file)
+            .and_then(|meta| meta.modified())
+            .map(|modified| {
+                DateTime::<Local>::from(modified)
+                    .format(TIMESTAMP_FORMAT)
+                    .to_string()
+            })
+            .unwrap_or_default();
+        BackupEntry {
+            file,
+            operation: "hg strip-backup".to_string(),
+            revset: String::new(),
+            created_at,
+        }
+    }));
+    entries.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    (entries, warning)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn recorded_backups_are_listed_with_strip_backups() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock")
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("easyhg-backups-{nanos}"));
+        let strip_backup = root.join(".hg").join("strip-backup");
+        fs::create_dir_all(&strip_backup).expect("strip-backup dir");
+        fs::create_dir_all(backup_dir(&root)).expect("backup dir");
+        fs::write(strip_backup.join("abc-rebase.hg"), b"bundle").expect("strip bundle");
+
+        let backup = SafetyBackup::new(&root, "hg rebase -s 3 -d 5", "3::".to_string());
+        assert!(backup.entry.file.starts_with(backup_dir(&root)));
+        assert!(backup.entry.file.to_string_lossy().ends_with("-rebase.hg"));
+        record(&root, backup.entry.clone()).expect("record");
+        let missing = SafetyBackup::new(&root, "hg prune -r 4", "4".to_string());
+        record(&root, missing.entry).expect("record missing");
+        fs::write(&backup.entry.file, b"bundle").expect("bundle file");
+
+        let (listed, warning) = list(&root);
+        assert_eq!(warning, None);
+        assert_eq!(listed.len(), 2);
+        assert!(listed.contains(&backup.entry));
+        assert!(
+            listed
+                .iter()
+                .any(|entry| entry.operation == "hg strip-backup"
+                    && entry.file.ends_with("abc-rebase.hg"))
+        );
+
+        fs::write(index_path(&root), "backups = [").expect("corrupt index");
+        let next = SafetyBackup::new(&root, "hg prune -r 6", "6".to_string());
+        assert!(record(&root, next.entry).unwrap_err().contains("corrupt"));
+        let (listed, warning) = list(&root);
+        assert!(warning.expect("warning").contains("corrupt"));
+        assert_eq!(listed.len(), 1);
+        assert_eq!(
+            fs::read_to_string(index_path(&root)).expect("index"),
+            "backups = ["
+        );
+        fs::remove_dir_all(root).ok();
+    }
+}
@@ -6,15 +6,81 @@ use serde::Deserialize;
 
 use crate::actions;
 use crate::custom_commands::{SUPPORTED_TEMPLATE_VARS, parse_command_parts, unknown_template_vars};
+use crate::hg::{
+    CommandTimeouts, DEFAULT_NETWORK_TIMEOUT_SECS, DEFAULT_READ_TIMEOUT_SECS,
+    DEFAULT_WRITE_TIMEOUT_SECS, HGPLAIN_EXCEPT_NAMES, PullMode,
+};
+use crate::protection::{PROTECTION_MODES, ProtectionMode};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
     #[serde(default = "default_theme")]
     pub theme: String,
+    #[serde(default = "default_pull_mode")]
+    pub pull_mode: String,
     #[serde(default)]
     pub keybinds: HashMap<String, String>,
     #[serde(default)]
     pub custom_commands: Vec<CustomCommand>,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub hgplain_except: Vec<String>,
+    #[serde(default = "default_backup_bundles")]
+    pub backup_bundles: bool,
+    #[serde(default)]
+    pub protected: ProtectedConfig,
+    #[serde(default)]
+    pub confirmations: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProtectedConfig {
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub public_phase: bool,
+    #[serde(default = "default_protected_mode")]
+    pub mode: String,
+}
+
+impl Default for ProtectedConfig {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            patterns: Vec::new(),
+            public_phase: false,
+            mode: default_protected_mode(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TimeoutConfig {
+    #[serde(default = "default_read_timeout_secs")]
+    pub read_secs: u64,
+    #[serde(default = "default_write_timeout_secs")]
+    pub write_secs: u64,
+    #[serde(default = "default_network_timeout_secs")]
+    pub network_secs: u64,
+}
+
+impl TimeoutConfig {
+    pub fn command_timeouts(&self) -> CommandTimeouts {
+        CommandTimeouts::from_secs(self.read_secs, self.write_secs, self.network_secs)
+    }
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            read_secs: default_read_timeout_secs(),
+            write_secs: default_write_timeout_secs(),
+            network_secs: default_network_timeout_secs(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -45,6 +111,30 @@ fn default_theme() -> String {
     "auto".to_string()
 }
 
+fn default_pull_mode() -> String {
+    "update".to_string()
+}
+
+fn default_read_timeout_secs() -> u64 {
+    DEFAULT_READ_TIMEOUT_SECS
+}
+
+fn default_write_timeout_secs() -> u64 {
+    DEFAULT_WRITE_TIMEOUT_SECS
+}
+
+fn default_network_timeout_secs() -> u64 {
+    DEFAULT_NETWORK_TIMEOUT_SECS
+}
+
+fn default_protected_mode() -> String {
+    "confirm".to_string()
+}
+
+fn default_backup_bundles() -> bool {
+    true
+}
+
 fn default_show_output() -> bool {
     true
 }
@@ -53,8 +143,14 @@ impl Default for AppConfig {
     fn default() -> Self {
         Self {
             theme: default_theme(),
+            pull_mode: default_pull_mode(),
             keybinds: HashMap::new(),
             custom_commands: Vec::new(),
+            timeouts: TimeoutConfig::default(),
+            hgplain_except: Vec::new(),
+            backup_bundles: default_backup_bundles(),
+            protected: ProtectedConfig::default(),
+            confirmations: HashMap::new(),
         }
     }
 }
@@ -127,7 +223,44 @@ pub fn validate_config(config: &AppConfig) -> Vec<String> {
         )),
     }
 
+    if PullMode::from_name(config.pull_mode.trim()).is_none() {
+        issues.push(format!(
+            "invalid pull_mode '{}' (expected: update, no-update, rebase)",
+            config.pull_mode.trim()
+        ));
+    }
+
+    for name in &config.hgplain_except {
+        if !HGPLAIN_EXCEPT_NAMES.contains(&name.trim()) {
+            issues.push(format!(
+                "invalid hgplain_except entry '{}' (expected: {})",
+                name.trim(),
+                HGPLAIN_EXCEPT_NAMES.join(", ")
+            ));
+        }
+    }
+
+    if ProtectionMode::from_name(config.protected.mode.trim()).is_none() {
+        issues.push(format!(
+            "invalid protected.mode '{}' (expected: {})",
+            config.protected.mode.trim(),
+            PROTECTION_MODES.join(", ")
+        ));
+    }
+    if config
+        .protected
+        .names
+        .iter()
+        .chain(&config.protected.patterns)
+        .any(|name| name.trim().is_empty())
+    {
+        issues.push("protected names and patterns must not be empty".to_string());
+    }
+
     issues.extend(actions::validate_key_overrides(&config.keybinds));
+    issues.extend(actions::validate_confirmation_overrides(
+        &config.confirmations,
+    ));
 
     let mut ids = std::collections::HashSet::new();
     for command in &config.custom_commands {
@@ -199,6 +332,10 @@ theme = "dark"
 [keybinds]
 commit = "C"
 
+[confirmations]
+rebase_selected = "never"
+push = "typed"
+
 [[custom_commands]]
 id = "lint"
 title = "Run Lint"
@@ -210,7 +347,14 @@ needs_confirmation = true
 "#;
         let config = toml::from_str::<AppConfig>(raw).expect("config parses");
         assert_eq!(config.theme, "dark");
+        assert_eq!(config.pull_mode, "update");
+        assert_eq!(config.timeouts.read_secs, DEFAULT_READ_TIMEOUT_SECS);
+        assert_eq!(config.timeouts.command_timeouts().write, None);
         assert_eq!(config.keybinds.get("commit"), Some(&"C".to_string()));
+        assert_eq!(
+            config.confirmations.get("push").map(String::as_str),
+            Some("typed")
+        );
         assert_eq!(config.custom_commands.len(), 1);
         assert!(config.custom_commands[0].needs_confirmation);
         assert_eq!(config.custom_commands[0].args, vec!["--all-targets"]);
@@ -219,11 +363,25 @@ needs_confirmation = true
 
     #[test]
     fn validate_config_reports_errors() {
-        let mut config = AppConfig::default();
-        config.theme = "neon".to_string();
+        let mut config = AppConfig {
+            theme: "neon".to_string(),
+            pull_mode: "fetch".to_string(),
+            hgplain_except: vec!["pager".to_string(), "i18n".to_string()],
+            protected: ProtectedConfig {
+                names: vec![" ".to_string()],
+                mode: "block".to_string(),
+                ..ProtectedConfig::default()
+            },
+            ..AppConfig::default()
+        };
         config
             .keybinds
             .insert("unknown_action".to_string(), "x".to_string());
+        config.confirmations = HashMap::from([
+            ("rebase_selected".to_string(), "sometimes".to_string()),
+            ("move_down".to_string(), "always".to_string()),
+            ("fold".to_string(), "typed".to_string()),
+        ]);
         config.custom_commands = vec![
             CustomCommand {
                 id: "dup".to_string(),
@@ -249,11 +407,45 @@ needs_confirmation = true
 
         let issues = validate_config(&config);
         assert!(issues.iter().any(|line| line.contains("invalid theme")));
+        assert!(issues.iter().any(|line| line.contains("invalid pull_mode")));
+        assert!(
+            issues
+                .iter()
+                .any(|line| line.contains("invalid hgplain_except entry 'pager'"))
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|line| line.contains("invalid hgplain_except entry 'i18n'"))
+        );
         assert!(
             issues
                 .iter()
                 .any(|line| line.contains("unknown keybinding action"))
         );
+        assert!(
+            issues
+                .iter()
+                .any(|line| line.contains("invalid protected.mode 'block'"))
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|line| line.contains("protected names and patterns must not be empty"))
+        );
+        assert!(issues.iter().any(|line| line.contains(
+            "invalid confirmation policy 'sometimes' for rebase_selected (expected: never, always, typed)"
+        )));
+        assert!(
+            issues
+                .iter()
+                .any(|line| line.contains("confirmations.move_down: action does not run"))
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|line| line.contains("unknown confirmations action 'fold'"))
+        );
         assert!(issues.iter().any(|line| line.contains("empty title")));
         assert!(issues.iter().any(|line| line.contains("empty command")));
         assert!(issues.iter().any(|line| line.contains("empty arg entry")));
@@ -266,17 +458,19 @@ needs_confirmation = true
 
     #[test]
     fn validate_config_reports_custom_command_syntax_and_template_issues() {
-        let mut config = AppConfig::default();
-        config.custom_commands = vec![CustomCommand {
-            id: "bad".to_string(),
-            title: "Bad".to_string(),
-            context: CommandContext::Repo,
-            command: r#"echo "unterminated"#.to_string(),
-            args: vec!["{nope}".to_string()],
-            env: HashMap::from([("TARGET".to_string(), "{still_bad}".to_string())]),
-            show_output: true,
-            needs_confirmation: false,
-        }];
+        let config = AppConfig {
+            custom_commands: vec![CustomCommand {
+                id: "bad".to_string(),
+                title: "Bad".to_string(),
+                context: CommandContext::Repo,
+                command: r#"echo "unterminated"#.to_string(),
+                args: vec!["{nope}".to_string()],
+                env: HashMap::from([("TARGET".to_string(), "{still_bad}".to_string())]),
+                show_output: true,
+                needs_confirmation: false,
+            }],
+            ..AppConfig::default()
+        };
 
         let issues = validate_config(&config);
         assert!(
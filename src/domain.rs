@@ -1,10 +1,92 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
 use std::fmt;
+use std::path::PathBuf;
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+
+// A repository path exactly as hg reported it. Filenames need not be UTF-8,
+// so the raw bytes are carried through to hg arguments and only converted
+// lossily for display.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RepoPath(Vec<u8>);
+
+impl RepoPath {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn display(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.as_bytes())
+    }
+
+    #[cfg(unix)]
+    pub fn to_os_string(&self) -> OsString {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(self.0.clone())
+    }
+
+    #[cfg(not(unix))]
+    pub fn to_os_string(&self) -> OsString {
+        OsString::from(self.display().into_owned())
+    }
+
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf::from(self.to_os_string())
+    }
+
+    // `path:` makes hg match exactly this repo-root-relative path instead of
+    // treating the name as a cwd-relative pattern.
+    pub fn hg_pattern(&self) -> OsString {
+        let mut pattern = OsString::from("path:");
+        pattern.push(self.to_os_string());
+        pattern
+    }
+}
+
+impl fmt::Display for RepoPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display())
+    }
+}
+
+impl From<&str> for RepoPath {
+    fn from(path: &str) -> Self {
+        Self(path.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for RepoPath {
+    fn from(path: String) -> Self {
+        Self(path.into_bytes())
+    }
+}
+
+impl PartialEq<str> for RepoPath {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for RepoPath {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl Serialize for RepoPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.display())
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct FileChange {
-    pub path: String,
+    pub path: RepoPath,
     pub status: FileStatus,
 }
 
@@ -68,10 +150,49 @@ pub struct Revision {
     pub tags: Vec<String>,
     pub bookmarks: Vec<String>,
     pub date_unix_secs: i64,
+    pub obsolete: bool,
+    pub instabilities: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub graph_prefix: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Public,
+    Draft,
+    Secret,
+}
+
+impl Phase {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "public" => Some(Self::Public),
+            "draft" => Some(Self::Draft),
+            "secret" => Some(Self::Secret),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Draft => "draft",
+            Self::Secret => "secret",
+        }
+    }
+
+    pub fn flag(self) -> &'static str {
+        match self {
+            Self::Public => "-p",
+            Self::Draft => "-d",
+            Self::Secret => "-s",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Bookmark {
     pub name: String,
@@ -80,10 +201,33 @@ pub struct Bookmark {
     pub active: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Branch {
+    pub name: String,
+    pub rev: i64,
+    pub node: String,
+    pub active: bool,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HgPath {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tag {
+    pub name: String,
+    pub rev: i64,
+    pub node: String,
+    pub local: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct ConflictEntry {
     pub resolved: bool,
-    pub path: String,
+    pub path: RepoPath,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -93,6 +237,13 @@ pub struct Shelf {
     pub description: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Topic {
+    pub name: String,
+    pub active: bool,
+    pub changesets: Option<usize>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct RebaseState {
     pub in_progress: bool,
@@ -101,12 +252,67 @@ pub struct RebaseState {
     pub total_conflicts: usize,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct HisteditState {
+    pub in_progress: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HisteditAction {
+    Pick,
+    Edit,
+    Fold,
+    Roll,
+    Drop,
+    Mess,
+    Base,
+}
+
+impl HisteditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pick => "pick",
+            Self::Edit => "edit",
+            Self::Fold => "fold",
+            Self::Roll => "roll",
+            Self::Drop => "drop",
+            Self::Mess => "mess",
+            Self::Base => "base",
+        }
+    }
+
+    pub fn from_key(key: char) -> Option<Self> {
+        match key {
+            'p' => Some(Self::Pick),
+            'e' => Some(Self::Edit),
+            'f' => Some(Self::Fold),
+            'r' => Some(Self::Roll),
+            'd' => Some(Self::Drop),
+            'm' => Some(Self::Mess),
+            'b' => Some(Self::Base),
+            _ => None,
+        }
+    }
+
+    pub fn needs_editor(self) -> bool {
+        matches!(self, Self::Fold | Self::Mess)
+    }
+
+    pub fn needs_predecessor(self) -> bool {
+        matches!(self, Self::Fold | Self::Roll)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct HgCapabilities {
     pub version: String,
     pub has_rebase: bool,
     pub has_histedit: bool,
     pub has_shelve: bool,
+    pub has_evolve: bool,
+    pub has_topic: bool,
+    pub has_undo: bool,
     pub supports_json_status: bool,
     pub supports_json_log: bool,
     pub supports_json_bookmarks: bool,
@@ -116,15 +322,31 @@ pub struct HgCapabilities {
 pub struct RepoSnapshot {
     pub repo_root: Option<String>,
     pub branch: Option<String>,
+    pub working_parent_rev: Option<i64>,
     pub files: Vec<FileChange>,
     pub revisions: Vec<Revision>,
     pub bookmarks: Vec<Bookmark>,
+    pub branches: Vec<Branch>,
+    pub branch_heads: Vec<i64>,
+    pub tags: Vec<Tag>,
+    pub paths: Vec<HgPath>,
     pub shelves: Vec<Shelf>,
+    pub topics: Vec<Topic>,
     pub conflicts: Vec<ConflictEntry>,
     pub rebase: RebaseState,
+    pub histedit: HisteditState,
     pub capabilities: HgCapabilities,
 }
 
+impl RepoSnapshot {
+    pub fn active_topic(&self) -> Option<&str> {
+        self.topics
+            .iter()
+            .find(|topic| topic.active)
+            .map(|topic| topic.name.as_str())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,13 +370,34 @@ mod tests {
         assert_eq!(FileStatus::from_hg_code("Z"), FileStatus::Other('Z'));
     }
 
+    #[test]
+    fn histedit_action_keys_round_trip_to_verbs() {
+        assert_eq!(HisteditAction::from_key('p'), Some(HisteditAction::Pick));
+        assert_eq!(HisteditAction::from_key('b'), Some(HisteditAction::Base));
+        assert_eq!(HisteditAction::from_key('x'), None);
+        assert_eq!(HisteditAction::Roll.as_str(), "roll");
+        assert!(HisteditAction::Fold.needs_editor());
+        assert!(!HisteditAction::Roll.needs_editor());
+        assert!(HisteditAction::Roll.needs_predecessor());
+    }
+
+    #[test]
+    fn phase_order_runs_from_public_to_secret() {
+        assert_eq!(Phase::from_name("draft"), Some(Phase::Draft));
+        assert_eq!(Phase::from_name("bogus"), None);
+        assert!(Phase::Public < Phase::Draft);
+        assert!(Phase::Draft < Phase::Secret);
+        assert_eq!(Phase::Secret.flag(), "-s");
+    }
+
     #[test]
     fn repo_snapshot_serializes_expected_shape() {
         let snapshot = RepoSnapshot {
             repo_root: Some("/repo".to_string()),
             branch: Some("default".to_string()),
+            working_parent_rev: Some(1),
             files: vec![FileChange {
-                path: "src/main.rs".to_string(),
+                path: "src/main.rs".into(),
                 status: FileStatus::Modified,
             }],
             revisions: vec![Revision {
@@ -167,6 +410,9 @@ mod tests {
                 tags: vec!["tip".to_string()],
                 bookmarks: vec!["main".to_string()],
                 date_unix_secs: 10,
+                obsolete: false,
+                instabilities: Vec::new(),
+                topic: None,
                 graph_prefix: Some("@".to_string()),
             }],
             bookmarks: vec![Bookmark {
@@ -175,14 +421,37 @@ mod tests {
                 node: "abc".to_string(),
                 active: true,
             }],
+            branches: vec![Branch {
+                name: "default".to_string(),
+                rev: 1,
+                node: "abc".to_string(),
+                active: true,
+                closed: false,
+            }],
+            branch_heads: vec![1],
+            tags: vec![Tag {
+                name: "v1.0".to_string(),
+                rev: 1,
+                node: "abc".to_string(),
+                local: false,
+            }],
+            paths: vec![HgPath {
+                name: "default".to_string(),
+                url: "https://example.com/repo".to_string(),
+            }],
             shelves: vec![Shelf {
                 name: "wip".to_string(),
                 age: None,
                 description: "work in progress".to_string(),
             }],
+            topics: vec![Topic {
+                name: "feature-x".to_string(),
+                active: true,
+                changesets: Some(1),
+            }],
             conflicts: vec![ConflictEntry {
                 resolved: false,
-                path: "src/lib.rs".to_string(),
+                path: "src/lib.rs".into(),
             }],
             rebase: RebaseState {
                 in_progress: true,
@@ -190,11 +459,15 @@ mod tests {
                 resolved_conflicts: 0,
                 total_conflicts: 1,
             },
+            histedit: HisteditState { in_progress: false },
             capabilities: HgCapabilities {
                 version: "hg 6.9".to_string(),
                 has_rebase: true,
                 has_histedit: true,
                 has_shelve: true,
+                has_evolve: true,
+                has_topic: true,
+                has_undo: false,
                 supports_json_status: true,
                 supports_json_log: true,
                 supports_json_bookmarks: true,
@@ -204,11 +477,18 @@ mod tests {
         let json = serde_json::to_value(&snapshot).expect("serialize snapshot");
         assert_eq!(json["repo_root"], "/repo");
         assert_eq!(json["branch"], "default");
+        assert_eq!(json["working_parent_rev"], 1);
         assert_eq!(json["files"][0]["path"], "src/main.rs");
         assert_eq!(json["revisions"][0]["graph_prefix"], "@");
         assert_eq!(json["bookmarks"][0]["name"], "main");
+        assert_eq!(json["branches"][0]["closed"], false);
+        assert_eq!(json["branch_heads"][0], 1);
+        assert_eq!(json["tags"][0]["name"], "v1.0");
+        assert_eq!(json["paths"][0]["name"], "default");
         assert_eq!(json["rebase"]["in_progress"], true);
         assert_eq!(json["rebase"]["unresolved_conflicts"], 1);
+        assert_eq!(json["histedit"]["in_progress"], false);
+        assert_eq!(json["topics"][0]["name"], "feature-x");
         assert_eq!(json["capabilities"]["version"], "hg 6.9");
         assert_eq!(json["capabilities"]["supports_json_bookmarks"], true);
     }
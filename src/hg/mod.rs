@@ -1,23 +1,30 @@
 use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use async_trait::async_trait;
 use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::Command;
 use tokio::sync::Mutex;
 
 use crate::domain::{
-    Bookmark, ConflictEntry, FileChange, FileStatus, HgCapabilities, RebaseState, RepoSnapshot,
-    Revision, Shelf,
+    Bookmark, Branch, ConflictEntry, FileChange, FileStatus, HgCapabilities, HgPath,
+    HisteditAction, HisteditState, Phase, RebaseState, RepoPath, RepoSnapshot, Revision, Shelf,
+    Tag, Topic,
 };
+use crate::remotes::RemoteDirection;
+use crate::undo::RepoState;
 
 #[derive(Debug, Clone)]
 pub struct CommandResult {
     pub command_preview: String,
     pub success: bool,
+    pub exit_code: Option<i32>,
     pub stdout: String,
     pub stderr: String,
 }
@@ -29,26 +36,174 @@ pub struct SnapshotOptions {
 }
 
 const LOG_TEMPLATE_FIELD_SEP: char = '\u{1f}';
+const LOG_SUMMARY_TEMPLATE: &str = "{rev}:{node|short} {author|user} {desc|firstline}\n";
+const LOG_METADATA_TEMPLATE: &str = "{rev}\u{1f}{obsolete}\u{1f}{instabilities}\n";
+const LOG_METADATA_TOPIC_TEMPLATE: &str =
+    "{rev}\u{1f}{obsolete}\u{1f}{instabilities}\u{1f}{topic}\n";
+const REPO_STATE_LOG_TEMPLATE: &str = "{node}\u{1f}{rev}\n";
+const REPO_STATE_BOOKMARKS_TEMPLATE: &str = "{if(active, '*')}\u{1f}{node}\u{1f}{bookmark}\n";
 const LOG_PLAIN_TEMPLATE: &str = "{rev}\u{1f}{node}\u{1f}{desc|firstline}\u{1f}{author}\u{1f}{branch}\u{1f}{phase}\u{1f}{tags}\u{1f}{bookmarks}\u{1f}{date|hgdate}\n";
 
+// Everything below the bundled revisions is assumed present; `null` keeps the
+// base non-empty when the revisions start at the root.
+fn backup_base(revset: &str) -> String {
+    format!("parents(roots({revset})) + null")
+}
+
+// `\0` is hg's template escape for NUL; a literal NUL byte in argv makes
+// the spawn fail.
+const RESOLVE_PRINT0_ARGS: [&str; 4] = ["resolve", "-l", "-T", "{mergestatus} {path}\\0"];
+
 #[derive(Debug, Clone)]
 pub enum HgAction {
-    Commit { message: String, files: Vec<String> },
-    Pull,
-    Push,
-    Incoming,
-    Outgoing,
-    BookmarkCreate { name: String },
-    UpdateToRevision { rev: i64 },
-    UpdateToBookmark { name: String },
-    ShelveCreate { name: String },
-    Unshelve { name: String },
-    ResolveMark { path: String },
-    ResolveUnmark { path: String },
-    RebaseSourceDest { source_rev: i64, dest_rev: i64 },
+    Commit {
+        message: String,
+        files: Vec<RepoPath>,
+        close_branch: bool,
+    },
+    Pull {
+        source: Option<String>,
+        mode: PullMode,
+        scope: SyncScope,
+    },
+    Push {
+        dest: Option<String>,
+        scope: SyncScope,
+        new_branch: bool,
+    },
+    Outgoing {
+        dest: Option<String>,
+        scope: SyncScope,
+    },
+    BookmarkCreate {
+        name: String,
+    },
+    BookmarkDelete {
+        name: String,
+    },
+    BookmarkRename {
+        old: String,
+        new: String,
+    },
+    BookmarkMove {
+        name: String,
+        rev: i64,
+    },
+    BookmarkDeactivate,
+    UpdateToRevision {
+        rev: i64,
+    },
+    UpdateToBookmark {
+        name: String,
+    },
+    UpdateToBranch {
+        name: String,
+    },
+    BranchCreate {
+        name: String,
+    },
+    TagCreate {
+        name: String,
+        rev: i64,
+        local: bool,
+    },
+    TagRemove {
+        name: String,
+        local: bool,
+    },
+    ShelveCreate {
+        name: String,
+        files: Vec<RepoPath>,
+        keep: bool,
+    },
+    Unshelve {
+        name: String,
+        keep: bool,
+    },
+    ShelveDelete {
+        name: String,
+    },
+    ResolveMark {
+        path: RepoPath,
+    },
+    ResolveUnmark {
+        path: RepoPath,
+    },
+    RebaseSourceDest {
+        source_rev: i64,
+        dest_rev: i64,
+    },
     RebaseContinue,
     RebaseAbort,
-    HisteditBase { base_rev: i64 },
+    HisteditPlan {
+        base_rev: i64,
+        steps: Vec<HisteditPlanStep>,
+    },
+    HisteditContinue,
+    HisteditAbort,
+    Backout {
+        rev: i64,
+        message: String,
+        merge: bool,
+    },
+    Prev,
+    Next,
+    Evolve {
+        all: bool,
+    },
+    Prune {
+        rev: i64,
+    },
+    SetPhase {
+        rev: i64,
+        phase: Phase,
+        force: bool,
+    },
+    TopicSet {
+        name: String,
+    },
+    TopicClear,
+    BreakLock {
+        wlock: bool,
+    },
+    UpdateToTopic {
+        name: String,
+    },
+    Undo {
+        steps: Vec<UndoStep>,
+    },
+    BackupBundle {
+        revset: String,
+        file: PathBuf,
+    },
+    Unbundle {
+        file: PathBuf,
+    },
+    DryRun {
+        action: Box<HgAction>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoStep {
+    pub args: Vec<String>,
+    pub summary: String,
+}
+
+// Read-only commands showing what an action would change; `summary` covers
+// what hg cannot report without running it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRunPlan {
+    pub summary: String,
+    pub steps: Vec<Vec<OsString>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HisteditPlanStep {
+    pub action: HisteditAction,
+    pub rev: i64,
+    pub node: String,
+    pub desc: String,
 }
 
 #[derive(Debug, Clone)]
@@ -67,24 +222,93 @@ impl CustomInvocation {
 }
 
 impl HgAction {
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, Self::Outgoing { .. } | Self::DryRun { .. })
+    }
+
+    // Changesets a destructive action may strip or hide, bundled first when
+    // backup_bundles is enabled.
+    pub fn backup_revset(&self) -> Option<String> {
+        match self {
+            Self::RebaseSourceDest { source_rev, .. } => Some(format!("{source_rev}::")),
+            Self::HisteditPlan { base_rev, .. } => Some(format!("{base_rev}::.")),
+            Self::Prune { rev } => Some(rev.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn command_class(&self) -> CommandClass {
+        match self {
+            Self::Pull { .. } | Self::Push { .. } | Self::Outgoing { .. } => CommandClass::Network,
+            Self::DryRun { action } if action.command_class() == CommandClass::Network => {
+                CommandClass::Network
+            }
+            Self::DryRun { .. } => CommandClass::Read,
+            _ => CommandClass::Write,
+        }
+    }
+
     pub fn command_preview(&self) -> String {
         match self {
-            Self::Commit { files, .. } => {
+            Self::Commit {
+                files,
+                close_branch,
+                ..
+            } => {
+                let close = if *close_branch { " --close-branch" } else { "" };
                 if files.is_empty() {
-                    "hg commit -m <message>".to_string()
+                    format!("hg commit{close} -m <message>")
                 } else {
-                    format!("hg commit -m <message> <{} files>", files.len())
+                    format!("hg commit{close} -m <message> <{} files>", files.len())
                 }
             }
-            Self::Pull => "hg pull -u".to_string(),
-            Self::Push => "hg push".to_string(),
-            Self::Incoming => "hg incoming".to_string(),
-            Self::Outgoing => "hg outgoing".to_string(),
+            Self::Pull {
+                source,
+                mode,
+                scope,
+            } => with_path(&pull_args(*mode, scope).join(" "), source),
+            Self::Push {
+                dest,
+                scope,
+                new_branch,
+            } => with_path(&push_args(scope, *new_branch).join(" "), dest),
+            Self::Outgoing { dest, scope } => with_path(&outgoing_args(scope).join(" "), dest),
             Self::BookmarkCreate { name } => format!("hg bookmark {name}"),
+            Self::BookmarkDelete { name } => format!("hg bookmark -d {name}"),
+            Self::BookmarkRename { old, new } => format!("hg bookmark -m {old} {new}"),
+            Self::BookmarkMove { name, rev } => format!("hg bookmark -f -r {rev} {name}"),
+            Self::BookmarkDeactivate => "hg bookmark -i".to_string(),
             Self::UpdateToRevision { rev } => format!("hg update -r {rev}"),
             Self::UpdateToBookmark { name } => format!("hg update {name}"),
-            Self::ShelveCreate { name } => format!("hg shelve --name {name}"),
-            Self::Unshelve { name } => format!("hg unshelve --name {name}"),
+            Self::UpdateToBranch { name } => format!("hg update {name}"),
+            Self::BranchCreate { name } => format!("hg branch {name}"),
+            Self::TagCreate { name, rev, local } => {
+                if *local {
+                    format!("hg tag -l -r {rev} {name}")
+                } else {
+                    format!("hg tag -r {rev} {name}")
+                }
+            }
+            Self::TagRemove { name, local } => {
+                if *local {
+                    format!("hg tag -l --remove {name}")
+                } else {
+                    format!("hg tag --remove {name}")
+                }
+            }
+            Self::ShelveCreate { name, files, keep } => {
+                let keep = if *keep { " --keep" } else { "" };
+                if files.is_empty() {
+                    format!("hg shelve --name {name}{keep}")
+                } else {
+                    format!("hg shelve --name {name}{keep} <{} files>", files.len())
+                }
+            }
+            Self::Unshelve { name, keep } => {
+                let keep = if *keep { " --keep" } else { "" };
+                format!("hg unshelve{keep} --name {name}")
+            }
+            Self::ShelveDelete { name } => format!("hg shelve -d {name}"),
             Self::ResolveMark { path } => format!("hg resolve -m {path}"),
             Self::ResolveUnmark { path } => format!("hg resolve -u {path}"),
             Self::RebaseSourceDest {
@@ -93,24 +317,658 @@ impl HgAction {
             } => format!("hg rebase -s {source_rev} -d {dest_rev}"),
             Self::RebaseContinue => "hg rebase --continue".to_string(),
             Self::RebaseAbort => "hg rebase --abort".to_string(),
-            Self::HisteditBase { base_rev } => format!("hg histedit {base_rev}"),
+            Self::HisteditPlan { base_rev, steps } => format!(
+                "hg histedit --commands <plan: {} steps> {base_rev}",
+                steps.len()
+            ),
+            Self::HisteditContinue => "hg histedit --continue".to_string(),
+            Self::HisteditAbort => "hg histedit --abort".to_string(),
+            Self::Backout { rev, merge, .. } => {
+                if *merge {
+                    format!("hg backout -r {rev} --merge -m <message>")
+                } else {
+                    format!("hg backout -r {rev} -m <message>")
+                }
+            }
+            Self::Prev => "hg prev".to_string(),
+            Self::Next => "hg next".to_string(),
+            Self::Evolve { all } => {
+                if *all {
+                    "hg evolve --all".to_string()
+                } else {
+                    "hg evolve".to_string()
+                }
+            }
+            Self::Prune { rev } => format!("hg prune -r {rev}"),
+            Self::SetPhase { rev, phase, force } => {
+                if *force {
+                    format!("hg phase {} -f -r {rev}", phase.flag())
+                } else {
+                    format!("hg phase {} -r {rev}", phase.flag())
+                }
+            }
+            Self::TopicSet { name } => format!("hg topic {name}"),
+            Self::TopicClear => "hg topic --clear".to_string(),
+            Self::BreakLock { wlock: true } => "hg debuglocks -W".to_string(),
+            Self::BreakLock { wlock: false } => "hg debuglocks -L".to_string(),
+            Self::UpdateToTopic { name } => format!("hg update {name}"),
+            Self::Undo { steps } => steps
+                .iter()
+                .map(|step| format!("hg {}", step.args.join(" ")))
+                .collect::<Vec<_>>()
+                .join(" && "),
+            Self::BackupBundle { revset, file } => format!(
+                "hg bundle -r {revset} --base {} {}",
+                backup_base(revset),
+                file.display()
+            ),
+            Self::Unbundle { file } => format!("hg unbundle {}", file.display()),
+            Self::DryRun { action } => format!("dry run: {}", action.command_preview()),
         }
     }
+
+    pub fn dry_run_plan(&self) -> DryRunPlan {
+        let (summary, steps) = match self {
+            Self::Commit {
+                message,
+                files,
+                close_branch,
+            } => {
+                let scope = if files.is_empty() {
+                    "all tracked changes".to_string()
+                } else {
+                    format!("{} picked file(s)", files.len())
+                };
+                let close = if *close_branch {
+                    ", closing the branch"
+                } else {
+                    ""
+                };
+                let first_line = message.lines().next().unwrap_or("");
+                (
+                    format!("Would commit {scope}{close} with message \"{first_line}\"."),
+                    vec![
+                        with_files(&["status", "-mar"], files),
+                        with_files(&["diff"], files),
+                    ],
+                )
+            }
+            Self::Pull {
+                source,
+                mode,
+                scope,
+            } => {
+                let after = match mode {
+                    PullMode::Update => "then update the working directory",
+                    PullMode::NoUpdate => "without updating",
+                    PullMode::Rebase => "then rebase local changesets onto them",
+                };
+                (
+                    format!("Would pull these changesets, {after}."),
+                    vec![os_args(&path_args(
+                        &remote_changes_args("incoming", scope)[1..],
+                        source,
+                    ))],
+                )
+            }
+            Self::Push {
+                dest,
+                scope,
+                new_branch,
+            } => {
+                let branch = if *new_branch {
+                    " (creating new remote branches)"
+                } else {
+                    ""
+                };
+                (
+                    format!("Would push these changesets{branch}."),
+                    vec![os_args(&path_args(&outgoing_args(scope)[1..], dest))],
+                )
+            }
+            Self::Outgoing { dest, scope } => (
+                "Lists outgoing changesets; nothing is changed.".to_string(),
+                vec![os_args(&path_args(&outgoing_args(scope)[1..], dest))],
+            ),
+            Self::BookmarkCreate { name } => (
+                format!(
+                    "Would create bookmark {name} at the working directory parent and activate it."
+                ),
+                vec![os_args(&["bookmarks"])],
+            ),
+            Self::BookmarkDelete { name } => (
+                format!("Would delete bookmark {name}; its changesets stay."),
+                vec![os_args(&["bookmarks"])],
+            ),
+            Self::BookmarkRename { old, new } => (
+                format!("Would rename bookmark {old} to {new}."),
+                vec![os_args(&["bookmarks"])],
+            ),
+            Self::BookmarkMove { name, rev } => (
+                format!("Would move bookmark {name} to revision {rev}:"),
+                vec![os_args(&["log", "-r", &rev.to_string()])],
+            ),
+            Self::BookmarkDeactivate => (
+                "Would deactivate the active bookmark; it stays where it is.".to_string(),
+                vec![os_args(&["bookmarks"])],
+            ),
+            Self::UpdateToRevision { rev } => update_plan(&rev.to_string()),
+            Self::UpdateToBookmark { name }
+            | Self::UpdateToBranch { name }
+            | Self::UpdateToTopic { name } => update_plan(name),
+            Self::BranchCreate { name } => (
+                format!(
+                    "Would mark the working directory as branch {name}; the branch exists once committed."
+                ),
+                vec![os_args(&["branch"])],
+            ),
+            Self::TagCreate { name, rev, local } => (
+                if *local {
+                    format!("Would add local tag {name} (.hg/localtags) to revision {rev}:")
+                } else {
+                    format!("Would tag revision {rev} as {name} and commit .hgtags:")
+                },
+                vec![os_args(&["log", "-r", &rev.to_string()])],
+            ),
+            Self::TagRemove { name, local } => (
+                if *local {
+                    format!("Would remove local tag {name}.")
+                } else {
+                    format!("Would remove tag {name} and commit .hgtags.")
+                },
+                vec![os_args(&["log", "-r", name])],
+            ),
+            Self::ShelveCreate { name, files, keep } => {
+                let keep = if *keep {
+                    " and keep them in the working directory"
+                } else {
+                    " and revert them"
+                };
+                (
+                    format!("Would shelve these changes as {name}{keep}."),
+                    vec![
+                        with_files(&["status", "-mar"], files),
+                        with_files(&["diff"], files),
+                    ],
+                )
+            }
+            Self::Unshelve { name, keep } => (
+                if *keep {
+                    format!("Would apply shelf {name} and keep it.")
+                } else {
+                    format!("Would apply shelf {name} and delete it.")
+                },
+                vec![os_args(&["shelve", "-p", name])],
+            ),
+            Self::ShelveDelete { name } => (
+                format!("Would delete shelf {name} and discard these changes:"),
+                vec![os_args(&["shelve", "-p", name])],
+            ),
+            Self::ResolveMark { path } => (
+                format!("Would mark {} as resolved.", path.display()),
+                vec![vec!["resolve".into(), "-l".into(), path.hg_pattern()]],
+            ),
+            Self::ResolveUnmark { path } => (
+                format!("Would mark {} as unresolved.", path.display()),
+                vec![vec!["resolve".into(), "-l".into(), path.hg_pattern()]],
+            ),
+            Self::RebaseSourceDest {
+                source_rev,
+                dest_rev,
+            } => (
+                format!("Would rebase {source_rev} and its descendants onto {dest_rev}."),
+                vec![os_args(&[
+                    "rebase",
+                    "--dry-run",
+                    "-s",
+                    &source_rev.to_string(),
+                    "-d",
+                    &dest_rev.to_string(),
+                ])],
+            ),
+            Self::RebaseContinue | Self::HisteditContinue => (
+                "Would continue with these conflict states:".to_string(),
+                vec![os_args(&["resolve", "-l"])],
+            ),
+            Self::RebaseAbort | Self::HisteditAbort => (
+                "Would abort and restore the original changesets from this state:".to_string(),
+                vec![os_args(&["summary"])],
+            ),
+            Self::HisteditPlan { base_rev, steps } => (
+                format!(
+                    "Would run this histedit plan on {base_rev}::.:\n{}",
+                    render_histedit_plan(steps).trim_end()
+                ),
+                vec![os_args(&["log", "-r", &format!("{base_rev}::.")])],
+            ),
+            Self::Backout { rev, merge, .. } => (
+                if *merge {
+                    format!(
+                        "Would commit the reverse of {rev} and merge it into the working directory:"
+                    )
+                } else {
+                    format!("Would commit the reverse of {rev}:")
+                },
+                vec![os_args(&["diff", "-c", &rev.to_string(), "--reverse"])],
+            ),
+            Self::Prev => (
+                "Would update to the parent changeset.".to_string(),
+                vec![os_args(&["prev", "--dry-run"])],
+            ),
+            Self::Next => (
+                "Would update to the child changeset.".to_string(),
+                vec![os_args(&["next", "--dry-run"])],
+            ),
+            Self::Evolve { all } => (
+                "Would stabilize these changesets:".to_string(),
+                if *all {
+                    vec![os_args(&["evolve", "--all", "--dry-run"])]
+                } else {
+                    vec![os_args(&["evolve", "--dry-run"])]
+                },
+            ),
+            Self::Prune { rev } => (
+                format!(
+                    "Would make {rev} obsolete; any descendants listed after it become orphans:"
+                ),
+                vec![os_args(&["log", "-r", &format!("{rev}::")])],
+            ),
+            Self::SetPhase { rev, phase, .. } => {
+                // Phases are monotonic along history, so ancestors or
+                // descendants move along with the revision.
+                let revset = match phase {
+                    Phase::Public => format!("::{rev} and not public()"),
+                    Phase::Draft => format!("(::{rev} and secret()) or ({rev}:: and public())"),
+                    Phase::Secret => format!("{rev}:: and not secret()"),
+                };
+                (
+                    format!("Would move these changesets to {}:", phase.as_str()),
+                    vec![os_args(&["log", "-r", &revset])],
+                )
+            }
+            Self::TopicSet { name } => (
+                format!("Would set the working directory topic to {name}."),
+                vec![os_args(&["topics"])],
+            ),
+            Self::TopicClear => (
+                "Would clear the working directory topic.".to_string(),
+                vec![os_args(&["topics"])],
+            ),
+            Self::BreakLock { wlock } => (
+                if *wlock {
+                    "Would remove the working directory lock:".to_string()
+                } else {
+                    "Would remove the store lock:".to_string()
+                },
+                vec![os_args(&["debuglocks"])],
+            ),
+            Self::Undo { steps } => (
+                format!(
+                    "Would run:\n{}",
+                    steps
+                        .iter()
+                        .map(|step| format!("  hg {}  ({})", step.args.join(" "), step.summary))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+                Vec::new(),
+            ),
+            Self::BackupBundle { revset, file } => (
+                format!("Would bundle these changesets into {}:", file.display()),
+                vec![os_args(&["log", "-r", revset])],
+            ),
+            Self::Unbundle { file } => (
+                format!("Would add these changesets from {}:", file.display()),
+                vec![vec!["incoming".into(), file.into()]],
+            ),
+            Self::DryRun { action } => return action.dry_run_plan(),
+        };
+        DryRunPlan { summary, steps }
+    }
+}
+
+fn os_args<S: AsRef<str>>(args: &[S]) -> Vec<OsString> {
+    args.iter()
+        .map(|arg| OsString::from(arg.as_ref()))
+        .collect()
+}
+
+fn with_files(base: &[&str], files: &[RepoPath]) -> Vec<OsString> {
+    let mut args = os_args(base);
+    args.extend(files.iter().map(RepoPath::hg_pattern));
+    args
+}
+
+fn update_plan(target: &str) -> (String, Vec<Vec<OsString>>) {
+    (
+        format!(
+            "Would update the working directory to {target}; these files change (uncommitted changes are listed first):"
+        ),
+        vec![
+            os_args(&["status", "-mard"]),
+            os_args(&["status", "--rev", ".", "--rev", target]),
+        ],
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HgEnv {
+    // Output is parsed: HGPLAIN with no exceptions plus PARSE_CONFIG.
+    Parsed,
+    // Output is shown to the user: HGPLAIN honouring the configured HGPLAINEXCEPT.
+    UserFacing,
+    // The user's environment untouched; only used by --doctor to compare.
+    Inherited,
+}
+
+// i18n is left out: pull summaries, rebase hints and lock waits are read
+// from user-facing output and only understood in English.
+pub const HGPLAIN_EXCEPT_NAMES: [&str; 6] = [
+    "alias",
+    "color",
+    "progress",
+    "revsetalias",
+    "strictflags",
+    "templatealias",
+];
+
+// HGPLAIN does not cover every setting that changes output shape, so parsing
+// calls pin these explicitly.
+const PARSE_CONFIG: [&str; 8] = [
+    "ui.interactive=false",
+    "ui.paginate=false",
+    "ui.color=never",
+    "ui.report_untrusted=false",
+    "ui.relative-paths=false",
+    "ui.statuscopies=false",
+    "commands.status.relative=false",
+    "commands.status.terse=",
+];
+
+const OUTPUT_AFFECTING_CONFIG: [&str; 18] = [
+    "alias.",
+    "defaults.",
+    "color.",
+    "pager.",
+    "templatealias.",
+    "revsetalias.",
+    "command-templates.",
+    "ui.tweakdefaults",
+    "ui.color",
+    "ui.paginate",
+    "ui.verbose",
+    "ui.quiet",
+    "ui.debug",
+    "ui.logtemplate",
+    "ui.style",
+    "ui.relative-paths",
+    "commands.status.",
+    "ui.statuscopies",
+];
+
+pub fn output_affecting_settings(config_output: &str) -> Vec<String> {
+    config_output
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            OUTPUT_AFFECTING_CONFIG
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandClass {
+    Read,
+    Write,
+    Network,
+}
+
+impl CommandClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Network => "network",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandTimeouts {
+    pub read: Option<Duration>,
+    pub write: Option<Duration>,
+    pub network: Option<Duration>,
+}
+
+impl Default for CommandTimeouts {
+    fn default() -> Self {
+        Self::from_secs(
+            DEFAULT_READ_TIMEOUT_SECS,
+            DEFAULT_WRITE_TIMEOUT_SECS,
+            DEFAULT_NETWORK_TIMEOUT_SECS,
+        )
+    }
+}
+
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 120;
+// Killing a write mid-transaction leaves the repository needing `hg recover`,
+// so writes only time out when configured to.
+pub const DEFAULT_WRITE_TIMEOUT_SECS: u64 = 0;
+pub const DEFAULT_NETWORK_TIMEOUT_SECS: u64 = 1800;
+
+impl CommandTimeouts {
+    // 0 disables the timeout for that class.
+    pub fn from_secs(read: u64, write: u64, network: u64) -> Self {
+        let limit = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        Self {
+            read: limit(read),
+            write: limit(write),
+            network: limit(network),
+        }
+    }
+
+    pub fn for_class(&self, class: CommandClass) -> Option<Duration> {
+        match class {
+            CommandClass::Read => self.read,
+            CommandClass::Write => self.write,
+            CommandClass::Network => self.network,
+        }
+    }
+
+    // Dropping the timed-out future drops the child, which kill_on_drop
+    // turns into a kill.
+    async fn apply<T>(
+        &self,
+        class: CommandClass,
+        preview: &str,
+        run: impl Future<Output = T>,
+    ) -> Result<T> {
+        let Some(limit) = self.for_class(class) else {
+            return Ok(run.await);
+        };
+        tokio::time::timeout(limit, run).await.map_err(|_| {
+            anyhow!(
+                "{preview} timed out after {}s ({} timeout)",
+                limit.as_secs(),
+                class.as_str()
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockWait {
+    pub wlock: bool,
+    pub holder: Option<String>,
+    pub message: String,
+}
+
+pub fn parse_lock_wait(line: &str) -> Option<LockWait> {
+    let line = line.trim();
+    let rest = line.strip_prefix("waiting for lock on ")?;
+    let wlock = rest.starts_with("working directory");
+    let holder = rest
+        .split_once(" held by ")
+        .map(|(_, holder)| holder.trim().to_string())
+        .filter(|holder| !holder.is_empty() && holder != "''");
+    Some(LockWait {
+        wlock,
+        holder,
+        message: line.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HgProgress {
+    pub topic: String,
+    pub pos: u64,
+    pub total: Option<u64>,
+}
+
+impl HgProgress {
+    pub fn fraction(&self) -> Option<f64> {
+        let total = self.total.filter(|total| *total > 0)?;
+        Some((self.pos as f64 / total as f64).clamp(0.0, 1.0))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputChunk {
+    Stdout(String),
+    Stderr(String),
+    Progress(Option<HgProgress>),
+}
+
+pub type OutputSink = Arc<dyn Fn(OutputChunk) + Send + Sync>;
+
+// hg only draws its progress bar on a tty; force it on and keep the format
+// down to "topic pos/total" so it can be parsed from the \r-separated stderr.
+const PROGRESS_CONFIG: [&str; 5] = [
+    "progress.assume-tty=true",
+    "progress.delay=0",
+    "progress.changedelay=0",
+    "progress.refresh=0.1",
+    "progress.format=topic number",
+];
+
+pub fn parse_progress_line(segment: &str) -> Option<HgProgress> {
+    let (topic, number) = segment.trim().rsplit_once(' ')?;
+    let topic = topic.trim();
+    if topic.is_empty() {
+        return None;
+    }
+    let (pos, total) = match number.split_once('/') {
+        Some((pos, total)) => (pos.trim().parse().ok()?, Some(total.parse().ok()?)),
+        None => (number.parse().ok()?, None),
+    };
+    Some(HgProgress {
+        topic: topic.to_string(),
+        pos,
+        total,
+    })
+}
+
+async fn stream_pipe<R: AsyncRead + Unpin>(
+    mut pipe: R,
+    is_stderr: bool,
+    output: &OutputSink,
+) -> Result<String> {
+    let mut collected = Vec::new();
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 4096];
+    // Set when a `\r` already ended the line, so the `\n` of a CRLF pair
+    // does not add an empty one.
+    let mut ended_by_cr = false;
+    let emit = |pending: &mut Vec<u8>, collected: &mut Vec<u8>| {
+        let line = String::from_utf8_lossy(pending).into_owned();
+        collected.append(pending);
+        collected.push(b'\n');
+        output(if is_stderr {
+            OutputChunk::Stderr(line)
+        } else {
+            OutputChunk::Stdout(line)
+        });
+    };
+    loop {
+        let read = pipe.read(&mut buf).await.context("failed reading output")?;
+        if read == 0 {
+            break;
+        }
+        for &byte in &buf[..read] {
+            let after_cr = std::mem::take(&mut ended_by_cr);
+            match byte {
+                b'\n' if after_cr => {}
+                b'\n' => emit(&mut pending, &mut collected),
+                b'\r' if is_stderr => {
+                    let segment = String::from_utf8_lossy(&pending).into_owned();
+                    if let Some(progress) = parse_progress_line(&segment) {
+                        pending.clear();
+                        output(OutputChunk::Progress(Some(progress)));
+                    } else if segment.trim().is_empty() {
+                        pending.clear();
+                        if !segment.is_empty() {
+                            output(OutputChunk::Progress(None));
+                        }
+                    } else {
+                        // CRLF output or `remote:` text is kept as a line.
+                        emit(&mut pending, &mut collected);
+                        ended_by_cr = true;
+                    }
+                }
+                _ => pending.push(byte),
+            }
+        }
+    }
+    if !pending.is_empty() {
+        emit(&mut pending, &mut collected);
+        collected.pop();
+    }
+    Ok(String::from_utf8_lossy(&collected).into_owned())
 }
 
 #[async_trait]
 pub trait HgClient: Send + Sync {
     async fn refresh_snapshot(&self, options: SnapshotOptions) -> Result<RepoSnapshot>;
-    async fn file_diff(&self, file: &str) -> Result<String>;
+    async fn file_diff(&self, file: &RepoPath) -> Result<String>;
     async fn revision_patch(&self, rev: i64) -> Result<String>;
-    async fn run_action(&self, action: &HgAction) -> Result<CommandResult>;
-    async fn run_custom_command(&self, invocation: &CustomInvocation) -> Result<CommandResult>;
+    async fn log_revset(&self, revset: &str) -> Result<Vec<Revision>>;
+    async fn log_summary(&self, revset: &str) -> Result<String>;
+    async fn repo_state(&self, revision_limit: usize) -> Result<RepoState>;
+    async fn obslog(&self, rev: i64) -> Result<String>;
+    async fn topics_list(&self) -> Result<String>;
+    async fn shelve_patch(&self, name: &str) -> Result<String>;
+    async fn remote_changesets(
+        &self,
+        direction: RemoteDirection,
+        path: Option<&str>,
+    ) -> Result<Vec<Revision>>;
+    async fn remote_changeset_patch(
+        &self,
+        direction: RemoteDirection,
+        path: Option<&str>,
+        node: &str,
+    ) -> Result<String>;
+    async fn run_action(
+        &self,
+        action: &HgAction,
+        output: Option<&OutputSink>,
+    ) -> Result<CommandResult>;
+    async fn run_custom_command(
+        &self,
+        invocation: &CustomInvocation,
+        output: Option<&OutputSink>,
+    ) -> Result<CommandResult>;
 }
 
 #[derive(Debug, Clone)]
 pub struct CliHgClient {
     cwd: PathBuf,
     capabilities_cache: Arc<Mutex<Option<HgCapabilities>>>,
+    timeouts: CommandTimeouts,
+    plain_except: Vec<String>,
 }
 
 impl CliHgClient {
@@ -118,47 +976,237 @@ impl CliHgClient {
         Self {
             cwd,
             capabilities_cache: Arc::new(Mutex::new(None)),
+            timeouts: CommandTimeouts::default(),
+            plain_except: Vec::new(),
+        }
+    }
+
+    pub fn with_plain_except(mut self, plain_except: Vec<String>) -> Self {
+        self.plain_except = plain_except;
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: CommandTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub async fn run_hg<S: AsRef<OsStr>>(&self, args: &[S]) -> Result<CommandResult> {
+        self.run_hg_class(args, CommandClass::Read, HgEnv::Parsed)
+            .await
+    }
+
+    pub async fn run_hg_inherited<S: AsRef<OsStr>>(&self, args: &[S]) -> Result<CommandResult> {
+        self.run_hg_class(args, CommandClass::Read, HgEnv::Inherited)
+            .await
+    }
+
+    fn hg_command(&self, env: HgEnv) -> Command {
+        let mut command = Command::new("hg");
+        command
+            .current_dir(&self.cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        match env {
+            HgEnv::Parsed => {
+                command
+                    .env("HGPLAIN", "1")
+                    .env_remove("HGPLAINEXCEPT")
+                    .env("HGENCODING", "utf-8");
+                for setting in PARSE_CONFIG {
+                    command.args(["--config", setting]);
+                }
+            }
+            HgEnv::UserFacing => {
+                // progress is always excepted so the streamed progress bar
+                // survives HGPLAIN.
+                let mut except = self.plain_except.clone();
+                if !except.iter().any(|name| name == "progress") {
+                    except.push("progress".to_string());
+                }
+                command
+                    .env("HGPLAIN", "1")
+                    .env("HGPLAINEXCEPT", except.join(","))
+                    .env("HGENCODING", "utf-8");
+            }
+            HgEnv::Inherited => {}
+        }
+        command
+    }
+
+    async fn run_hg_class<S: AsRef<OsStr>>(
+        &self,
+        args: &[S],
+        class: CommandClass,
+        env: HgEnv,
+    ) -> Result<CommandResult> {
+        let (preview, output) = self.run_hg_output(args, class, env).await?;
+        Ok(CommandResult {
+            command_preview: preview,
+            success: output.status.success(),
+            exit_code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    // Raw stdout for commands whose output carries file names, which may not
+    // be valid UTF-8.
+    async fn run_hg_bytes<S: AsRef<OsStr>>(&self, args: &[S]) -> Result<Vec<u8>> {
+        let (preview, output) = self
+            .run_hg_output(args, CommandClass::Read, HgEnv::Parsed)
+            .await?;
+        if !output.status.success() {
+            bail!(
+                "{preview} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
         }
+        Ok(output.stdout)
     }
 
-    pub async fn run_hg<S: AsRef<str>>(&self, args: &[S]) -> Result<CommandResult> {
+    async fn run_hg_output<S: AsRef<OsStr>>(
+        &self,
+        args: &[S],
+        class: CommandClass,
+        env: HgEnv,
+    ) -> Result<(String, std::process::Output)> {
         let preview = format!(
             "hg {}",
             args.iter()
-                .map(|part| part.as_ref().to_string())
+                .map(|part| part.as_ref().to_string_lossy().into_owned())
                 .collect::<Vec<_>>()
                 .join(" ")
         );
 
-        let mut command = Command::new("hg");
-        command
-            .current_dir(&self.cwd)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let mut command = self.hg_command(env);
         for arg in args {
             command.arg(arg.as_ref());
         }
 
-        let output = command
-            .output()
-            .await
+        let output = self
+            .timeouts
+            .apply(class, &preview, command.output())
+            .await?
+            .with_context(|| format!("failed to spawn mercurial command: {preview}"))?;
+        Ok((preview, output))
+    }
+
+    async fn run_hg_with<S: AsRef<OsStr>>(
+        &self,
+        args: &[S],
+        class: CommandClass,
+        output: Option<&OutputSink>,
+    ) -> Result<CommandResult> {
+        match output {
+            Some(output) => self.run_hg_streaming(args, class, output).await,
+            None => self.run_hg_class(args, class, HgEnv::UserFacing).await,
+        }
+    }
+
+    async fn run_hg_streaming<S: AsRef<OsStr>>(
+        &self,
+        args: &[S],
+        class: CommandClass,
+        output: &OutputSink,
+    ) -> Result<CommandResult> {
+        let preview = format!(
+            "hg {}",
+            args.iter()
+                .map(|part| part.as_ref().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+
+        let mut command = self.hg_command(HgEnv::UserFacing);
+        for setting in PROGRESS_CONFIG {
+            command.args(["--config", setting]);
+        }
+        for arg in args {
+            command.arg(arg.as_ref());
+        }
+
+        let mut child = command
+            .spawn()
             .with_context(|| format!("failed to spawn mercurial command: {preview}"))?;
+        let stdout = child.stdout.take().context("missing stdout pipe")?;
+        let stderr = child.stderr.take().context("missing stderr pipe")?;
+        let run = async {
+            let (stdout, stderr) = tokio::join!(
+                stream_pipe(stdout, false, output),
+                stream_pipe(stderr, true, output)
+            );
+            (stdout, stderr, child.wait().await)
+        };
+        let (stdout, stderr, status) = self.timeouts.apply(class, &preview, run).await?;
+        let status =
+            status.with_context(|| format!("failed waiting for mercurial command: {preview}"))?;
         Ok(CommandResult {
             command_preview: preview,
-            success: output.status.success(),
-            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
-            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            success: status.success(),
+            exit_code: status.code(),
+            stdout: stdout?,
+            stderr: stderr?,
         })
     }
 
-    async fn probe_hg_success<S: AsRef<str>>(&self, args: &[S]) -> bool {
+    async fn run_branches_plain(&self) -> Result<Vec<Branch>> {
+        let out = self.run_hg(&["branches", "-c"]).await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(parse_branches_plain(&out.stdout))
+    }
+
+    async fn run_tags_plain(&self) -> Result<Vec<Tag>> {
+        let out = self.run_hg(&["tags", "-v"]).await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(parse_tags_plain(&out.stdout))
+    }
+
+    async fn probe_hg_success<S: AsRef<OsStr>>(&self, args: &[S]) -> bool {
         self.run_hg(args)
             .await
             .map(|out| out.success)
             .unwrap_or(false)
     }
 
+    // -0 and the NUL-terminated resolve template keep file names byte-exact;
+    // the JSON/plain forms are fallbacks for hg versions that reject them.
+    async fn status_files(&self, caps: &HgCapabilities) -> Result<Vec<FileChange>> {
+        if let Ok(raw) = self.run_hg_bytes(&["status", "-0"]).await {
+            return Ok(parse_status_print0(&raw));
+        }
+        if caps.supports_json_status {
+            let status = self.run_hg(&["status", "-Tjson"]).await?;
+            if status.success
+                && let Ok(parsed) = parse_status_json(&status.stdout)
+            {
+                return Ok(parsed);
+            }
+        }
+        let status = self.run_hg(&["status"]).await?;
+        if !status.success {
+            return Err(command_failed(&status));
+        }
+        Ok(parse_status_plain(&status.stdout))
+    }
+
+    async fn conflict_entries(&self) -> Result<Vec<ConflictEntry>> {
+        if let Ok(raw) = self.run_hg_bytes(&RESOLVE_PRINT0_ARGS).await {
+            return Ok(parse_resolve_print0(&raw));
+        }
+        let out = self.run_hg(&["resolve", "-l"]).await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(parse_resolve_list(&out.stdout))
+    }
+
     async fn run_log_template(&self, limit: usize) -> Result<CommandResult> {
         let limit_arg = limit.to_string();
         self.run_hg(&["log", "-l", limit_arg.as_str(), "-T", LOG_PLAIN_TEMPLATE])
@@ -185,6 +1233,9 @@ impl CliHgClient {
         let has_rebase = self.probe_hg_success(&["rebase", "-h"]).await;
         let has_histedit = self.probe_hg_success(&["histedit", "-h"]).await;
         let has_shelve = self.probe_hg_success(&["shelve", "-h"]).await;
+        let has_evolve = self.probe_hg_success(&["evolve", "-h"]).await;
+        let has_topic = self.probe_hg_success(&["topics", "-h"]).await;
+        let has_undo = self.probe_hg_success(&["undo", "-h"]).await;
         let supports_json_status = self.probe_hg_success(&["status", "-Tjson"]).await;
         let supports_json_log = self.probe_hg_success(&["log", "-l", "1", "-Tjson"]).await;
         let supports_json_bookmarks = self.probe_hg_success(&["bookmarks", "-Tjson"]).await;
@@ -194,6 +1245,9 @@ impl CliHgClient {
             has_rebase,
             has_histedit,
             has_shelve,
+            has_evolve,
+            has_topic,
+            has_undo,
             supports_json_status,
             supports_json_log,
             supports_json_bookmarks,
@@ -215,17 +1269,26 @@ impl HgClient for CliHgClient {
         let repo_root = root.stdout.trim().to_string();
 
         let rebase_state_path = PathBuf::from(&repo_root).join(".hg").join("rebasestate");
-        let (branch, status, bookmarks, conflicts, shelves, revisions, rebase_in_progress) = tokio::join!(
+        let histedit_state_path = PathBuf::from(&repo_root).join(".hg").join("histedit-state");
+        let (
+            branch,
+            working_parent,
+            status,
+            bookmarks,
+            branches,
+            branch_heads,
+            tags,
+            paths,
+            conflicts,
+            shelves,
+            topics,
+            revisions,
+            rebase_in_progress,
+            histedit_in_progress,
+        ) = tokio::join!(
             self.run_hg(&["branch"]),
-            async {
-                if caps.supports_json_status {
-                    self.run_hg(&["status", "-Tjson"])
-                        .await
-                        .map(|out| (out, true))
-                } else {
-                    self.run_hg(&["status"]).await.map(|out| (out, false))
-                }
-            },
+            self.run_hg(&["log", "-r", ".", "-T", "{rev}"]),
+            self.status_files(&caps),
             async {
                 if caps.supports_json_bookmarks {
                     self.run_hg(&["bookmarks", "-Tjson"])
@@ -235,7 +1298,29 @@ impl HgClient for CliHgClient {
                     self.run_hg(&["bookmarks"]).await.map(|out| (out, false))
                 }
             },
-            self.run_hg(&["resolve", "-l"]),
+            async {
+                let json = self.run_hg(&["branches", "-c", "-Tjson"]).await;
+                match json {
+                    Ok(out) if out.success => match parse_branches_json(&out.stdout) {
+                        Ok(parsed) => Ok(parsed),
+                        Err(_) => self.run_branches_plain().await,
+                    },
+                    _ => self.run_branches_plain().await,
+                }
+            },
+            self.run_hg(&["heads", "-T", "{rev}\n"]),
+            async {
+                let json = self.run_hg(&["tags", "-Tjson"]).await;
+                match json {
+                    Ok(out) if out.success => match parse_tags_json(&out.stdout) {
+                        Ok(parsed) => Ok(parsed),
+                        Err(_) => self.run_tags_plain().await,
+                    },
+                    _ => self.run_tags_plain().await,
+                }
+            },
+            self.run_hg(&["paths"]),
+            self.conflict_entries(),
             async {
                 if caps.has_shelve {
                     Some(self.run_hg(&["shelve", "--list"]).await)
@@ -243,60 +1328,70 @@ impl HgClient for CliHgClient {
                     None
                 }
             },
+            async {
+                if caps.has_topic {
+                    Some(self.run_hg(&["topics"]).await)
+                } else {
+                    None
+                }
+            },
             async {
                 if options.include_revisions {
                     let log_limit_arg = options.revision_limit.to_string();
                     let graph_args = ["log", "-G", "-l", log_limit_arg.as_str(), "-T", "{rev}\n"];
+                    let metadata_args = [
+                        "log",
+                        "-l",
+                        log_limit_arg.as_str(),
+                        "-T",
+                        if caps.has_topic {
+                            LOG_METADATA_TOPIC_TEMPLATE
+                        } else {
+                            LOG_METADATA_TEMPLATE
+                        },
+                    ];
+                    let metadata_log = async {
+                        if caps.has_evolve || caps.has_topic {
+                            Some(self.run_hg(&metadata_args).await)
+                        } else {
+                            None
+                        }
+                    };
                     if caps.supports_json_log {
                         let json_args = ["log", "-l", log_limit_arg.as_str(), "-Tjson"];
-                        let (log, graph_log) =
-                            tokio::join!(self.run_hg(&json_args), self.run_hg(&graph_args));
-                        Some((log, true, graph_log))
+                        let (log, graph_log, metadata_log) = tokio::join!(
+                            self.run_hg(&json_args),
+                            self.run_hg(&graph_args),
+                            metadata_log
+                        );
+                        Some((log, true, graph_log, metadata_log))
                     } else {
-                        let (log, graph_log) = tokio::join!(
+                        let (log, graph_log, metadata_log) = tokio::join!(
                             self.run_log_template(options.revision_limit),
-                            self.run_hg(&graph_args)
+                            self.run_hg(&graph_args),
+                            metadata_log
                         );
-                        Some((log, false, graph_log))
+                        Some((log, false, graph_log, metadata_log))
                     }
                 } else {
                     None
                 }
             },
-            async { std::fs::metadata(&rebase_state_path).is_ok() }
+            async { std::fs::metadata(&rebase_state_path).is_ok() },
+            async { std::fs::metadata(&histedit_state_path).is_ok() }
         );
 
         let branch = branch.ok().map(|out| out.stdout.trim().to_string());
+        let working_parent_rev = working_parent
+            .ok()
+            .filter(|out| out.success)
+            .and_then(|out| out.stdout.trim().parse::<i64>().ok())
+            .filter(|rev| *rev >= 0);
 
-        let (status, status_used_json) = status?;
-        let files = if status_used_json {
-            if status.success {
-                match parse_status_json(&status.stdout) {
-                    Ok(parsed) => parsed,
-                    Err(_) => {
-                        let fallback = self.run_hg(&["status"]).await?;
-                        if !fallback.success {
-                            return Err(command_failed(&fallback));
-                        }
-                        parse_status_plain(&fallback.stdout)
-                    }
-                }
-            } else {
-                let fallback = self.run_hg(&["status"]).await?;
-                if !fallback.success {
-                    return Err(command_failed(&fallback));
-                }
-                parse_status_plain(&fallback.stdout)
-            }
-        } else {
-            if !status.success {
-                return Err(command_failed(&status));
-            }
-            parse_status_plain(&status.stdout)
-        };
+        let files = status?;
 
         let revisions = if options.include_revisions {
-            let (log, log_used_json, graph_log) = revisions
+            let (log, log_used_json, graph_log, metadata_log) = revisions
                 .ok_or_else(|| anyhow!("missing log command result for revision refresh"))?;
             let log = log?;
             let mut revisions = if log_used_json {
@@ -324,14 +1419,19 @@ impl HgClient for CliHgClient {
                 }
                 parse_log_plain_template(&log.stdout)?
             };
-            if let Ok(graph_log) = graph_log {
-                if graph_log.success {
-                    let graph_rows = parse_log_graph(&graph_log.stdout);
-                    if !graph_rows.is_empty() {
-                        revisions = merge_log_graph(revisions, &graph_rows);
-                    }
+            if let Ok(graph_log) = graph_log
+                && graph_log.success
+            {
+                let graph_rows = parse_log_graph(&graph_log.stdout);
+                if !graph_rows.is_empty() {
+                    revisions = merge_log_graph(revisions, &graph_rows);
                 }
             }
+            if let Some(Ok(metadata_log)) = metadata_log
+                && metadata_log.success
+            {
+                apply_revision_metadata(&mut revisions, &metadata_log.stdout);
+            }
             revisions
         } else {
             Vec::new()
@@ -375,84 +1475,483 @@ impl HgClient for CliHgClient {
             Vec::new()
         };
 
-        let conflicts = {
-            let out = conflicts?;
-            if !out.success {
-                return Err(command_failed(&out));
+        let branches = branches?;
+        let tags = tags?;
+        let paths = paths
+            .ok()
+            .filter(|out| out.success)
+            .map(|out| parse_paths(&out.stdout))
+            .unwrap_or_default();
+        let branch_heads = branch_heads
+            .ok()
+            .filter(|out| out.success)
+            .map(|out| {
+                out.stdout
+                    .lines()
+                    .filter_map(|line| line.trim().parse::<i64>().ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let topics = match topics {
+            Some(out) => {
+                let out = out?;
+                if !out.success {
+                    return Err(command_failed(&out));
+                }
+                parse_topics_list(&out.stdout)
             }
-            parse_resolve_list(&out.stdout)
+            None => Vec::new(),
         };
+
+        let conflicts = conflicts?;
         let rebase = build_rebase_state(rebase_in_progress, &conflicts);
 
-        Ok(RepoSnapshot {
-            repo_root: Some(repo_root),
-            branch,
-            files,
-            revisions,
-            bookmarks,
-            shelves,
-            conflicts,
-            rebase,
-            capabilities: caps,
-        })
+        Ok(RepoSnapshot {
+            repo_root: Some(repo_root),
+            branch,
+            working_parent_rev,
+            files,
+            revisions,
+            bookmarks,
+            branches,
+            branch_heads,
+            tags,
+            paths,
+            shelves,
+            topics,
+            conflicts,
+            rebase,
+            histedit: HisteditState {
+                in_progress: histedit_in_progress,
+            },
+            capabilities: caps,
+        })
+    }
+
+    async fn file_diff(&self, file: &RepoPath) -> Result<String> {
+        let out = self
+            .run_hg(&[OsString::from("diff"), file.hg_pattern()])
+            .await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(out.stdout)
+    }
+
+    async fn revision_patch(&self, rev: i64) -> Result<String> {
+        let rev_s = rev.to_string();
+        let out = self.run_hg(&["log", "-r", &rev_s, "-p"]).await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(out.stdout)
+    }
+
+    async fn log_revset(&self, revset: &str) -> Result<Vec<Revision>> {
+        let caps = self.detect_capabilities().await;
+        if caps.supports_json_log {
+            let out = self.run_hg(&["log", "-r", revset, "-Tjson"]).await?;
+            if !out.success {
+                return Err(command_failed(&out));
+            }
+            return parse_log_json(&out.stdout);
+        }
+        let out = self
+            .run_hg(&["log", "-r", revset, "-T", LOG_PLAIN_TEMPLATE])
+            .await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        parse_log_plain_template(&out.stdout)
+    }
+
+    // Only what undo compares, so capturing it before each write stays cheap.
+    async fn repo_state(&self, revision_limit: usize) -> Result<RepoState> {
+        let limit_arg = revision_limit.to_string();
+        let log_args = [
+            "log",
+            "-l",
+            limit_arg.as_str(),
+            "-T",
+            REPO_STATE_LOG_TEMPLATE,
+        ];
+        let (log, parent, bookmarks) = tokio::join!(
+            self.run_hg(&log_args),
+            self.run_hg(&["log", "-r", ".", "-T", "{node}"]),
+            self.run_hg(&["bookmarks", "-T", REPO_STATE_BOOKMARKS_TEMPLATE]),
+        );
+        let (log, parent, bookmarks) = (log?, parent?, bookmarks?);
+        for out in [&log, &parent, &bookmarks] {
+            if !out.success {
+                return Err(command_failed(out));
+            }
+        }
+        parse_repo_state(&log.stdout, &parent.stdout, &bookmarks.stdout)
+    }
+
+    async fn log_summary(&self, revset: &str) -> Result<String> {
+        let out = self
+            .run_hg(&["log", "-r", revset, "-T", LOG_SUMMARY_TEMPLATE])
+            .await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(out.stdout)
+    }
+
+    async fn obslog(&self, rev: i64) -> Result<String> {
+        let rev_s = rev.to_string();
+        let out = self.run_hg(&["obslog", "-p", "-r", &rev_s]).await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(out.stdout)
+    }
+
+    async fn remote_changesets(
+        &self,
+        direction: RemoteDirection,
+        path: Option<&str>,
+    ) -> Result<Vec<Revision>> {
+        let caps = self.detect_capabilities().await;
+        let mut args = vec![remote_command(direction), "-q"];
+        if caps.supports_json_log {
+            args.push("-Tjson");
+        } else {
+            args.extend(["-T", LOG_PLAIN_TEMPLATE]);
+        }
+        args.extend(path);
+        let out = self
+            .run_hg_class(&args, CommandClass::Network, HgEnv::Parsed)
+            .await?;
+        if !out.success {
+            // incoming/outgoing exit 1 when there is nothing to transfer.
+            if out.stderr.trim().is_empty() {
+                return Ok(Vec::new());
+            }
+            return Err(command_failed(&out));
+        }
+        if caps.supports_json_log {
+            parse_log_json(&out.stdout)
+        } else {
+            parse_log_plain_template(&out.stdout)
+        }
+    }
+
+    async fn remote_changeset_patch(
+        &self,
+        direction: RemoteDirection,
+        path: Option<&str>,
+        node: &str,
+    ) -> Result<String> {
+        let mut args = vec![remote_command(direction), "-p", "-n", "-l", "1", "-r", node];
+        args.extend(path);
+        let out = self
+            .run_hg_class(&args, CommandClass::Network, HgEnv::Parsed)
+            .await?;
+        if !out.success {
+            return Err(command_failed(&out));
+        }
+        Ok(out.stdout)
     }
 
-    async fn file_diff(&self, file: &str) -> Result<String> {
-        let out = self.run_hg(&["diff", file]).await?;
+    async fn shelve_patch(&self, name: &str) -> Result<String> {
+        let out = self.run_hg(&["shelve", "-p", name]).await?;
         if !out.success {
             return Err(command_failed(&out));
         }
         Ok(out.stdout)
     }
 
-    async fn revision_patch(&self, rev: i64) -> Result<String> {
-        let rev_s = rev.to_string();
-        let out = self.run_hg(&["log", "-r", &rev_s, "-p"]).await?;
+    async fn topics_list(&self) -> Result<String> {
+        let out = self.run_hg(&["topics", "-v"]).await?;
         if !out.success {
             return Err(command_failed(&out));
         }
         Ok(out.stdout)
     }
 
-    async fn run_action(&self, action: &HgAction) -> Result<CommandResult> {
+    async fn run_action(
+        &self,
+        action: &HgAction,
+        output: Option<&OutputSink>,
+    ) -> Result<CommandResult> {
+        let class = action.command_class();
         match action {
-            HgAction::Commit { message, files } => {
-                let mut args = vec!["commit".to_string(), "-m".to_string(), message.to_string()];
-                args.extend(files.iter().cloned());
-                self.run_hg(&args).await
-            }
-            HgAction::Pull => self.run_hg(&["pull", "-u"]).await,
-            HgAction::Push => self.run_hg(&["push"]).await,
-            HgAction::Incoming => self.run_hg(&["incoming"]).await,
-            HgAction::Outgoing => self.run_hg(&["outgoing"]).await,
-            HgAction::BookmarkCreate { name } => self.run_hg(&["bookmark", name]).await,
+            HgAction::Commit {
+                message,
+                files,
+                close_branch,
+            } => {
+                let mut args = vec![OsString::from("commit")];
+                if *close_branch {
+                    args.push("--close-branch".into());
+                }
+                args.extend(["-m".into(), message.into()]);
+                args.extend(files.iter().map(RepoPath::hg_pattern));
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::Pull {
+                source,
+                mode,
+                scope,
+            } => {
+                let args = pull_args(*mode, scope);
+                self.run_hg_with(&path_args(&args[1..], source), class, output)
+                    .await
+            }
+            HgAction::Push {
+                dest,
+                scope,
+                new_branch,
+            } => {
+                let args = push_args(scope, *new_branch);
+                self.run_hg_with(&path_args(&args[1..], dest), class, output)
+                    .await
+            }
+            HgAction::Outgoing { dest, scope } => {
+                let args = outgoing_args(scope);
+                self.run_hg_with(&path_args(&args[1..], dest), class, output)
+                    .await
+            }
+            HgAction::BookmarkCreate { name } => {
+                self.run_hg_with(&["bookmark", name], class, output).await
+            }
+            HgAction::BookmarkDelete { name } => {
+                self.run_hg_with(&["bookmark", "-d", name], class, output)
+                    .await
+            }
+            HgAction::BookmarkRename { old, new } => {
+                self.run_hg_with(&["bookmark", "-m", old, new], class, output)
+                    .await
+            }
+            HgAction::BookmarkMove { name, rev } => {
+                let rev = rev.to_string();
+                self.run_hg_with(&["bookmark", "-f", "-r", &rev, name], class, output)
+                    .await
+            }
+            HgAction::BookmarkDeactivate => {
+                self.run_hg_with(&["bookmark", "-i"], class, output).await
+            }
             HgAction::UpdateToRevision { rev } => {
                 let rev = rev.to_string();
-                self.run_hg(&["update", "-r", &rev]).await
+                self.run_hg_with(&["update", "-r", &rev], class, output)
+                    .await
+            }
+            HgAction::UpdateToBookmark { name } => {
+                self.run_hg_with(&["update", name], class, output).await
+            }
+            HgAction::UpdateToBranch { name } => {
+                self.run_hg_with(&["update", name], class, output).await
+            }
+            HgAction::BranchCreate { name } => {
+                self.run_hg_with(&["branch", name], class, output).await
+            }
+            HgAction::TagCreate { name, rev, local } => {
+                let rev = rev.to_string();
+                let mut args = vec!["tag"];
+                if *local {
+                    args.push("-l");
+                }
+                args.extend(["-r", rev.as_str(), name.as_str()]);
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::TagRemove { name, local } => {
+                let mut args = vec!["tag"];
+                if *local {
+                    args.push("-l");
+                }
+                args.extend(["--remove", name.as_str()]);
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::ShelveCreate { name, files, keep } => {
+                let mut args = vec![OsString::from("shelve"), "--name".into(), name.into()];
+                if *keep {
+                    args.push("--keep".into());
+                }
+                args.extend(files.iter().map(RepoPath::hg_pattern));
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::Unshelve { name, keep } => {
+                let mut args = vec!["unshelve"];
+                if *keep {
+                    args.push("--keep");
+                }
+                args.extend(["--name", name.as_str()]);
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::ShelveDelete { name } => {
+                self.run_hg_with(&["shelve", "-d", name], class, output)
+                    .await
+            }
+            HgAction::ResolveMark { path } => {
+                let args = [OsString::from("resolve"), "-m".into(), path.hg_pattern()];
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::ResolveUnmark { path } => {
+                let args = [OsString::from("resolve"), "-u".into(), path.hg_pattern()];
+                self.run_hg_with(&args, class, output).await
             }
-            HgAction::UpdateToBookmark { name } => self.run_hg(&["update", name]).await,
-            HgAction::ShelveCreate { name } => self.run_hg(&["shelve", "--name", name]).await,
-            HgAction::Unshelve { name } => self.run_hg(&["unshelve", "--name", name]).await,
-            HgAction::ResolveMark { path } => self.run_hg(&["resolve", "-m", path]).await,
-            HgAction::ResolveUnmark { path } => self.run_hg(&["resolve", "-u", path]).await,
             HgAction::RebaseSourceDest {
                 source_rev,
                 dest_rev,
             } => {
                 let source = source_rev.to_string();
                 let dest = dest_rev.to_string();
-                self.run_hg(&["rebase", "-s", &source, "-d", &dest]).await
+                self.run_hg_with(&["rebase", "-s", &source, "-d", &dest], class, output)
+                    .await
+            }
+            HgAction::RebaseContinue => {
+                self.run_hg_with(&["rebase", "--continue"], class, output)
+                    .await
             }
-            HgAction::RebaseContinue => self.run_hg(&["rebase", "--continue"]).await,
-            HgAction::RebaseAbort => self.run_hg(&["rebase", "--abort"]).await,
-            HgAction::HisteditBase { base_rev } => {
+            HgAction::RebaseAbort => {
+                self.run_hg_with(&["rebase", "--abort"], class, output)
+                    .await
+            }
+            HgAction::HisteditPlan { base_rev, steps } => {
+                let plan_path = write_histedit_plan_file(steps)?;
+                let plan_arg = plan_path.display().to_string();
                 let rev = base_rev.to_string();
-                self.run_hg(&["histedit", &rev]).await
+                let result = self
+                    .run_hg_with(
+                        &["histedit", "--commands", plan_arg.as_str(), rev.as_str()],
+                        class,
+                        output,
+                    )
+                    .await;
+                std::fs::remove_file(&plan_path).ok();
+                result
+            }
+            HgAction::HisteditContinue => {
+                self.run_hg_with(&["histedit", "--continue"], class, output)
+                    .await
+            }
+            HgAction::HisteditAbort => {
+                self.run_hg_with(&["histedit", "--abort"], class, output)
+                    .await
+            }
+            HgAction::Backout {
+                rev,
+                message,
+                merge,
+            } => {
+                let mut args = vec!["backout".to_string(), "-r".to_string(), rev.to_string()];
+                if *merge {
+                    args.push("--merge".to_string());
+                }
+                args.push("-m".to_string());
+                args.push(message.clone());
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::Prev => self.run_hg_with(&["prev"], class, output).await,
+            HgAction::Next => self.run_hg_with(&["next"], class, output).await,
+            HgAction::Evolve { all } => {
+                if *all {
+                    self.run_hg_with(&["evolve", "--all"], class, output).await
+                } else {
+                    self.run_hg_with(&["evolve"], class, output).await
+                }
+            }
+            HgAction::Prune { rev } => {
+                let rev = rev.to_string();
+                self.run_hg_with(&["prune", "-r", &rev], class, output)
+                    .await
+            }
+            HgAction::SetPhase { rev, phase, force } => {
+                let rev = rev.to_string();
+                let mut args = vec!["phase", phase.flag()];
+                if *force {
+                    args.push("-f");
+                }
+                args.extend(["-r", rev.as_str()]);
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::TopicSet { name } => self.run_hg_with(&["topic", name], class, output).await,
+            HgAction::TopicClear => self.run_hg_with(&["topic", "--clear"], class, output).await,
+            HgAction::BreakLock { wlock } => {
+                let flag = if *wlock { "-W" } else { "-L" };
+                self.run_hg_with(&["debuglocks", flag], class, output).await
+            }
+            HgAction::UpdateToTopic { name } => {
+                self.run_hg_with(&["update", name], class, output).await
+            }
+            HgAction::Undo { steps } => {
+                let mut combined = CommandResult {
+                    command_preview: action.command_preview(),
+                    success: true,
+                    exit_code: Some(0),
+                    stdout: String::new(),
+                    stderr: String::new(),
+                };
+                for step in steps {
+                    let out = self.run_hg_with(&step.args, class, output).await?;
+                    combined.stdout.push_str(&out.stdout);
+                    combined.stderr.push_str(&out.stderr);
+                    if !out.success {
+                        // Later steps assume the earlier ones applied.
+                        combined.success = false;
+                        combined.exit_code = out.exit_code;
+                        combined.command_preview = out.command_preview;
+                        break;
+                    }
+                }
+                Ok(combined)
+            }
+            HgAction::BackupBundle { revset, file } => {
+                let args = [
+                    OsString::from("bundle"),
+                    "-r".into(),
+                    revset.into(),
+                    "--base".into(),
+                    backup_base(revset).into(),
+                    file.into(),
+                ];
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::Unbundle { file } => {
+                let args = [OsString::from("unbundle"), file.into()];
+                self.run_hg_with(&args, class, output).await
+            }
+            HgAction::DryRun { action: previewed } => {
+                let plan = previewed.dry_run_plan();
+                let mut combined = CommandResult {
+                    command_preview: action.command_preview(),
+                    success: true,
+                    exit_code: Some(0),
+                    stdout: format!("{}\n", plan.summary),
+                    stderr: String::new(),
+                };
+                // Every step runs: incoming/outgoing and `rebase --dry-run`
+                // exit 1 for "nothing to do", which is still a preview.
+                for step in &plan.steps {
+                    let out = self.run_hg_with(step, class, output).await?;
+                    combined
+                        .stdout
+                        .push_str(&format!("\n$ {}\n{}", out.command_preview, out.stdout));
+                    if !out.stderr.trim().is_empty() {
+                        combined.stdout.push_str(&out.stderr);
+                    }
+                    if !out.success {
+                        combined.stdout.push_str(&format!(
+                            "[exit status {}]\n",
+                            out.exit_code
+                                .map(|code| code.to_string())
+                                .unwrap_or_else(|| "unknown".to_string())
+                        ));
+                    }
+                }
+                Ok(combined)
             }
         }
     }
 
-    async fn run_custom_command(&self, invocation: &CustomInvocation) -> Result<CommandResult> {
+    async fn run_custom_command(
+        &self,
+        invocation: &CustomInvocation,
+        output: Option<&OutputSink>,
+    ) -> Result<CommandResult> {
         let preview = invocation.command_preview();
         let mut command = Command::new(&invocation.program);
         command
@@ -460,23 +1959,98 @@ impl HgClient for CliHgClient {
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .args(&invocation.args);
         for (key, value) in &invocation.env {
             command.env(key, value);
         }
-        let output = command
-            .output()
-            .await
+        let Some(output) = output else {
+            let result = command
+                .output()
+                .await
+                .with_context(|| format!("failed to spawn custom command: {preview}"))?;
+            return Ok(CommandResult {
+                command_preview: preview,
+                success: result.status.success(),
+                exit_code: result.status.code(),
+                stdout: String::from_utf8_lossy(&result.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&result.stderr).into_owned(),
+            });
+        };
+        let mut child = command
+            .spawn()
             .with_context(|| format!("failed to spawn custom command: {preview}"))?;
+        let stdout = child.stdout.take().context("missing stdout pipe")?;
+        let stderr = child.stderr.take().context("missing stderr pipe")?;
+        let (stdout, stderr) = tokio::join!(
+            stream_pipe(stdout, false, output),
+            stream_pipe(stderr, true, output)
+        );
+        let status = child
+            .wait()
+            .await
+            .with_context(|| format!("failed waiting for custom command: {preview}"))?;
         Ok(CommandResult {
             command_preview: preview,
-            success: output.status.success(),
-            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
-            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            success: status.success(),
+            exit_code: status.code(),
+            stdout: stdout?,
+            stderr: stderr?,
         })
     }
 }
 
+pub fn render_histedit_plan(steps: &[HisteditPlanStep]) -> String {
+    let mut plan = String::new();
+    for step in steps {
+        let short = step.node.chars().take(12).collect::<String>();
+        let desc = step.desc.lines().next().unwrap_or("");
+        plan.push_str(&format!(
+            "{} {} {} {}\n",
+            step.action.as_str(),
+            short,
+            step.rev,
+            desc
+        ));
+    }
+    plan
+}
+
+// The temp dir is shared, so the plan file must be new: `create_new` refuses
+// an existing path, including a symlink planted under the expected name.
+pub fn write_histedit_plan_file(steps: &[HisteditPlanStep]) -> Result<PathBuf> {
+    use std::io::Write;
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+    let mut attempt = 0u32;
+    let (path, mut file) = loop {
+        let path = std::env::temp_dir().join(format!(
+            "easyhg-histedit-{}-{nanos}-{attempt}.txt",
+            std::process::id()
+        ));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => break (path, file),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempt < 16 => {
+                attempt += 1;
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed creating histedit plan {}", path.display()));
+            }
+        }
+    };
+    file.write_all(render_histedit_plan(steps).as_bytes())
+        .with_context(|| format!("failed writing histedit plan to {}", path.display()))?;
+    Ok(path)
+}
+
 fn command_failed(out: &CommandResult) -> anyhow::Error {
     let stderr = compact_output(&out.stderr);
     let stdout = compact_output(&out.stdout);
@@ -505,7 +2079,7 @@ fn compact_output(text: &str) -> String {
         return trimmed.to_string();
     }
     let mut shortened = trimmed.chars().take(LIMIT).collect::<String>();
-    shortened.push_str("…");
+    shortened.push('…');
     shortened
 }
 
@@ -521,12 +2095,44 @@ fn parse_status_json(raw: &str) -> Result<Vec<FileChange>> {
     Ok(parsed
         .into_iter()
         .map(|item| FileChange {
-            path: item.path,
+            path: item.path.into(),
             status: FileStatus::from_hg_code(&item.status),
         })
         .collect())
 }
 
+fn parse_status_print0(raw: &[u8]) -> Vec<FileChange> {
+    raw.split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let (&code, rest) = entry.split_first()?;
+            let path = rest.strip_prefix(b" ")?;
+            if path.is_empty() {
+                return None;
+            }
+            Some(FileChange {
+                path: RepoPath::from_bytes(path),
+                status: FileStatus::from_hg_code(&char::from(code).to_string()),
+            })
+        })
+        .collect()
+}
+
+fn parse_resolve_print0(raw: &[u8]) -> Vec<ConflictEntry> {
+    raw.split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let (&code, rest) = entry.split_first()?;
+            let path = rest.strip_prefix(b" ")?;
+            if path.is_empty() {
+                return None;
+            }
+            Some(ConflictEntry {
+                resolved: code == b'R',
+                path: RepoPath::from_bytes(path),
+            })
+        })
+        .collect()
+}
+
 fn parse_status_plain(raw: &str) -> Vec<FileChange> {
     raw.lines()
         .filter_map(|line| {
@@ -541,7 +2147,7 @@ fn parse_status_plain(raw: &str) -> Vec<FileChange> {
                 return None;
             }
             Some(FileChange {
-                path: path.to_string(),
+                path: path.into(),
                 status: FileStatus::from_hg_code(status_token),
             })
         })
@@ -578,6 +2184,9 @@ fn parse_log_json(raw: &str) -> Result<Vec<Revision>> {
             tags: item.tags,
             bookmarks: item.bookmarks,
             date_unix_secs: item.date.0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
             graph_prefix: None,
         })
         .collect())
@@ -611,6 +2220,9 @@ fn parse_log_plain_template(raw: &str) -> Result<Vec<Revision>> {
             tags: split_whitespace_list(&fields[6]),
             bookmarks: split_whitespace_list(&fields[7]),
             date_unix_secs,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
             graph_prefix: None,
         });
     }
@@ -675,6 +2287,31 @@ fn merge_log_graph(revisions: Vec<Revision>, graph_rows: &[ParsedGraphRow]) -> V
     merged
 }
 
+fn apply_revision_metadata(revisions: &mut [Revision], raw: &str) {
+    let mut metadata = HashMap::new();
+    for line in raw.lines() {
+        let mut fields = line.split(LOG_TEMPLATE_FIELD_SEP);
+        let Some(rev) = fields.next().and_then(|rev| rev.trim().parse::<i64>().ok()) else {
+            continue;
+        };
+        let obsolete = fields.next().is_some_and(|field| !field.trim().is_empty());
+        let instabilities = fields.next().map(split_whitespace_list).unwrap_or_default();
+        let topic = fields
+            .next()
+            .map(str::trim)
+            .filter(|topic| !topic.is_empty())
+            .map(str::to_string);
+        metadata.insert(rev, (obsolete, instabilities, topic));
+    }
+    for revision in revisions {
+        if let Some((obsolete, instabilities, topic)) = metadata.remove(&revision.rev) {
+            revision.obsolete = obsolete;
+            revision.instabilities = instabilities;
+            revision.topic = topic;
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct BookmarkJsonItem {
     bookmark: String,
@@ -698,6 +2335,38 @@ fn parse_bookmarks_json(raw: &str) -> Result<Vec<Bookmark>> {
         .collect())
 }
 
+// Matches RepoState::capture: the working parent only counts when it is one
+// of the listed changesets.
+fn parse_repo_state(log: &str, parent: &str, bookmarks: &str) -> Result<RepoState> {
+    let mut state = RepoState::default();
+    for line in log.lines().filter(|line| !line.trim().is_empty()) {
+        let (node, rev) = line
+            .split_once(LOG_TEMPLATE_FIELD_SEP)
+            .ok_or_else(|| anyhow!("failed parsing hg log row: {line}"))?;
+        let rev = rev
+            .trim()
+            .parse::<i64>()
+            .with_context(|| format!("invalid revision number in log row: {line}"))?;
+        state.revisions.insert(node.to_string(), rev);
+    }
+    let parent = parent.trim();
+    if state.revisions.contains_key(parent) {
+        state.parent = Some(parent.to_string());
+    }
+    for line in bookmarks.lines().filter(|line| !line.trim().is_empty()) {
+        let mut fields = line.splitn(3, LOG_TEMPLATE_FIELD_SEP);
+        let (Some(active), Some(node), Some(name)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(anyhow!("failed parsing hg bookmarks row: {line}"));
+        };
+        if active == "*" {
+            state.active_bookmark = Some(name.to_string());
+        }
+        state.bookmarks.insert(name.to_string(), node.to_string());
+    }
+    Ok(state)
+}
+
 fn parse_bookmarks_plain(raw: &str) -> Vec<Bookmark> {
     raw.lines()
         .filter_map(|line| {
@@ -730,6 +2399,285 @@ fn parse_bookmarks_plain(raw: &str) -> Vec<Bookmark> {
         .collect()
 }
 
+#[derive(Debug, Deserialize)]
+struct BranchJsonItem {
+    branch: String,
+    rev: i64,
+    node: String,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    closed: bool,
+}
+
+fn parse_branches_json(raw: &str) -> Result<Vec<Branch>> {
+    let parsed = serde_json::from_str::<Vec<BranchJsonItem>>(raw)
+        .with_context(|| "failed parsing hg branches json")?;
+    Ok(parsed
+        .into_iter()
+        .map(|item| Branch {
+            name: item.branch,
+            rev: item.rev,
+            node: item.node,
+            active: item.active,
+            closed: item.closed,
+        })
+        .collect())
+}
+
+fn parse_branches_plain(raw: &str) -> Vec<Branch> {
+    raw.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim_end();
+            let (state, body) = if let Some(body) = trimmed.strip_suffix("(closed)") {
+                ("closed", body.trim_end())
+            } else if let Some(body) = trimmed.strip_suffix("(inactive)") {
+                ("inactive", body.trim_end())
+            } else {
+                ("active", trimmed)
+            };
+            let rev_node_start = body.rfind(char::is_whitespace)? + 1;
+            let mut rev_node = body[rev_node_start..].splitn(2, ':');
+            let rev = rev_node.next()?.parse::<i64>().ok()?;
+            let node = rev_node.next()?.to_string();
+            let name = body[..rev_node_start].trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(Branch {
+                name: name.to_string(),
+                rev,
+                node,
+                active: state == "active",
+                closed: state == "closed",
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct TagJsonItem {
+    tag: String,
+    rev: i64,
+    node: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+}
+
+fn parse_tags_json(raw: &str) -> Result<Vec<Tag>> {
+    let parsed = serde_json::from_str::<Vec<TagJsonItem>>(raw)
+        .with_context(|| "failed parsing hg tags json")?;
+    Ok(parsed
+        .into_iter()
+        .map(|item| Tag {
+            name: item.tag,
+            rev: item.rev,
+            node: item.node,
+            local: item.kind == "local",
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncScope {
+    All,
+    Rev(String),
+    Bookmark(String),
+    Branch(String),
+}
+
+impl SyncScope {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::All => "all outgoing changesets".to_string(),
+            Self::Rev(rev) => format!("revision {rev} and its ancestors"),
+            Self::Bookmark(name) => format!("bookmark '{name}'"),
+            Self::Branch(name) => format!("branch '{name}'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullMode {
+    NoUpdate,
+    Update,
+    Rebase,
+}
+
+impl PullMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "no-update" => Some(Self::NoUpdate),
+            "update" => Some(Self::Update),
+            "rebase" => Some(Self::Rebase),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NoUpdate => "no-update",
+            Self::Update => "update",
+            Self::Rebase => "rebase",
+        }
+    }
+}
+
+fn pull_args(mode: PullMode, scope: &SyncScope) -> Vec<String> {
+    let mut args = vec!["hg".to_string(), "pull".to_string()];
+    match mode {
+        PullMode::NoUpdate => {}
+        PullMode::Update => args.push("-u".to_string()),
+        PullMode::Rebase => args.push("--rebase".to_string()),
+    }
+    match scope {
+        SyncScope::All => {}
+        SyncScope::Rev(rev) => args.extend(["-r".to_string(), rev.clone()]),
+        SyncScope::Bookmark(name) => args.extend(["-B".to_string(), name.clone()]),
+        SyncScope::Branch(name) => args.extend(["-b".to_string(), name.clone()]),
+    }
+    args
+}
+
+pub fn summarize_pull_output(out: &CommandResult) -> String {
+    let output = format!("{}\n{}", out.stdout, out.stderr);
+    let mut parts = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if line == "no changes found" {
+            parts.push("no new changesets".to_string());
+        } else if let Some(rest) = line.strip_prefix("added ") {
+            let changesets = rest.split_whitespace().next().unwrap_or("0");
+            parts.push(format!("{changesets} new changeset(s)"));
+            if let Some(heads) = rest
+                .rsplit_once('(')
+                .and_then(|(_, tail)| tail.strip_suffix(" heads)"))
+            {
+                if let Some(added) = heads.strip_prefix('+') {
+                    parts.push(format!("{added} head(s) added"));
+                } else if let Some(removed) = heads.strip_prefix('-') {
+                    parts.push(format!("{removed} head(s) removed"));
+                }
+            }
+        } else if line.contains(" files updated, ") {
+            parts.push(format!("working copy updated ({line})"));
+        } else if let Some(reason) = line.strip_prefix("not updating: ") {
+            parts.push(format!("working copy not updated: {reason}"));
+        } else if line == "(run 'hg update' to get a working copy)" {
+            parts.push("working copy not updated (run hg update)".to_string());
+        } else if line.starts_with("(run 'hg heads") {
+            parts.push("multiple heads: merge or rebase needed".to_string());
+        } else if line == "nothing to rebase" {
+            parts.push("nothing to rebase".to_string());
+        } else if line.starts_with("rebasing ") && !parts.iter().any(|part| part == "rebased") {
+            parts.push("rebased".to_string());
+        }
+    }
+    if parts.is_empty() {
+        format!("Completed: {}", out.command_preview)
+    } else {
+        format!("Pull: {}", parts.join(", "))
+    }
+}
+
+fn push_args(scope: &SyncScope, new_branch: bool) -> Vec<String> {
+    let mut args = vec!["hg".to_string(), "push".to_string()];
+    match scope {
+        SyncScope::All => {}
+        SyncScope::Rev(rev) => args.extend(["-r".to_string(), rev.clone()]),
+        SyncScope::Bookmark(name) => args.extend(["-B".to_string(), name.clone()]),
+        SyncScope::Branch(name) => args.extend(["-b".to_string(), name.clone()]),
+    }
+    if new_branch {
+        args.push("--new-branch".to_string());
+    }
+    args
+}
+
+// `hg outgoing -B` compares bookmarks instead of limiting changesets, so a
+// bookmark scope is previewed through the revision it points at.
+fn outgoing_args(scope: &SyncScope) -> Vec<String> {
+    remote_changes_args("outgoing", scope)
+}
+
+fn remote_changes_args(command: &str, scope: &SyncScope) -> Vec<String> {
+    let mut args = vec!["hg".to_string(), command.to_string()];
+    match scope {
+        SyncScope::All => {}
+        SyncScope::Rev(rev) | SyncScope::Bookmark(rev) => {
+            args.extend(["-r".to_string(), rev.clone()])
+        }
+        SyncScope::Branch(name) => args.extend(["-b".to_string(), name.clone()]),
+    }
+    args
+}
+
+pub fn remote_command(direction: RemoteDirection) -> &'static str {
+    match direction {
+        RemoteDirection::Pull => "incoming",
+        RemoteDirection::Push => "outgoing",
+    }
+}
+
+fn with_path(base: &str, path: &Option<String>) -> String {
+    match path {
+        Some(path) => format!("{base} {path}"),
+        None => base.to_string(),
+    }
+}
+
+fn path_args<S: AsRef<str>>(base: &[S], path: &Option<String>) -> Vec<String> {
+    let mut args = base
+        .iter()
+        .map(|arg| arg.as_ref().to_string())
+        .collect::<Vec<_>>();
+    if let Some(path) = path {
+        args.push(path.clone());
+    }
+    args
+}
+
+fn parse_paths(raw: &str) -> Vec<HgPath> {
+    raw.lines()
+        .filter_map(|line| {
+            let (name, url) = line.split_once(" = ")?;
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(HgPath {
+                name: name.to_string(),
+                url: url.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_tags_plain(raw: &str) -> Vec<Tag> {
+    raw.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim_end();
+            let (local, body) = match trimmed.strip_suffix(" local") {
+                Some(body) => (true, body.trim_end()),
+                None => (false, trimmed),
+            };
+            let rev_node_start = body.rfind(char::is_whitespace)? + 1;
+            let mut rev_node = body[rev_node_start..].splitn(2, ':');
+            let rev = rev_node.next()?.parse::<i64>().ok()?;
+            let node = rev_node.next()?.to_string();
+            let name = body[..rev_node_start].trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(Tag {
+                name: name.to_string(),
+                rev,
+                node,
+                local,
+            })
+        })
+        .collect()
+}
+
 fn split_whitespace_list(raw: &str) -> Vec<String> {
     raw.split_whitespace()
         .map(|entry| entry.to_string())
@@ -755,6 +2703,29 @@ fn parse_shelve_list(raw: &str) -> Vec<Shelf> {
         .collect()
 }
 
+fn parse_topics_list(raw: &str) -> Vec<Topic> {
+    raw.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim();
+            let (active, rest) = match trimmed.strip_prefix('*') {
+                Some(rest) => (true, rest.trim_start()),
+                None => (false, trimmed),
+            };
+            let name = rest.split_whitespace().next()?.to_string();
+            let changesets = rest[name.len()..]
+                .trim()
+                .strip_prefix('(')
+                .and_then(|details| details.split_whitespace().next())
+                .and_then(|count| count.parse::<usize>().ok());
+            Some(Topic {
+                name,
+                active,
+                changesets,
+            })
+        })
+        .collect()
+}
+
 fn parse_resolve_list(raw: &str) -> Vec<ConflictEntry> {
     raw.lines()
         .filter_map(|line| {
@@ -769,7 +2740,7 @@ fn parse_resolve_list(raw: &str) -> Vec<ConflictEntry> {
             }
             Some(ConflictEntry {
                 resolved: status == 'R',
-                path,
+                path: path.into(),
             })
         })
         .collect()
@@ -809,6 +2780,23 @@ mod tests {
         assert_eq!(parsed[0].graph_prefix, None);
     }
 
+    #[test]
+    fn repo_state_parser_keeps_listed_parent_and_active_bookmark() {
+        let log = "bbb\u{1f}1\naaa\u{1f}0\n";
+        let bookmarks = "*\u{1f}bbb\u{1f}main\n\u{1f}aaa\u{1f}old release\n";
+        let state = parse_repo_state(log, "bbb", bookmarks).expect("parse state");
+        assert_eq!(state.parent.as_deref(), Some("bbb"));
+        assert_eq!(state.active_bookmark.as_deref(), Some("main"));
+        assert_eq!(
+            state.bookmarks.get("old release").map(String::as_str),
+            Some("aaa")
+        );
+        assert_eq!(state.revisions.get("aaa"), Some(&0));
+
+        let outside = parse_repo_state(log, "zzz", "").expect("parse state");
+        assert_eq!(outside.parent, None);
+    }
+
     #[test]
     fn parse_resolve_entries() {
         let raw = "U src/main.rs\nR README.md\n";
@@ -823,15 +2811,15 @@ mod tests {
         let conflicts = vec![
             ConflictEntry {
                 resolved: false,
-                path: "a".to_string(),
+                path: "a".into(),
             },
             ConflictEntry {
                 resolved: true,
-                path: "b".to_string(),
+                path: "b".into(),
             },
             ConflictEntry {
                 resolved: false,
-                path: "c".to_string(),
+                path: "c".into(),
             },
         ];
         let state = build_rebase_state(true, &conflicts);
@@ -878,6 +2866,194 @@ mod tests {
         assert!(!parsed[1].active);
     }
 
+    #[test]
+    fn branches_json_parser_maps_active_and_closed_state() {
+        let raw = r#"[{"active":true,"branch":"default","closed":false,"current":true,"node":"abc","rev":7},{"active":false,"branch":"old","closed":true,"current":false,"node":"def","rev":3}]"#;
+        let parsed = parse_branches_json(raw).expect("parse branches");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name, "default");
+        assert!(parsed[0].active);
+        assert!(!parsed[0].closed);
+        assert_eq!(parsed[1].rev, 3);
+        assert!(parsed[1].closed);
+    }
+
+    #[test]
+    fn branches_plain_parser_handles_state_suffixes_and_spaces() {
+        let raw = "default                        7:abc123\nfeature x                      5:def456 (inactive)\nold                            3:0123ab (closed)\n";
+        let parsed = parse_branches_plain(raw);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].name, "default");
+        assert!(parsed[0].active);
+        assert_eq!(parsed[1].name, "feature x");
+        assert_eq!(parsed[1].rev, 5);
+        assert!(!parsed[1].active);
+        assert!(!parsed[1].closed);
+        assert_eq!(parsed[2].node, "0123ab");
+        assert!(parsed[2].closed);
+    }
+
+    #[test]
+    fn paths_parser_reads_named_and_local_paths() {
+        let raw = "default = https://hg.example.com/repo\ndefault-push = ssh://hg@example.com/repo\nlocal = file:///srv/clones/repo\nsibling = /home/u/other clone\n";
+        let parsed = parse_paths(raw);
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[1].name, "default-push");
+        assert_eq!(parsed[2].url, "file:///srv/clones/repo");
+        assert_eq!(parsed[3].url, "/home/u/other clone");
+    }
+
+    #[test]
+    fn sync_previews_append_selected_path() {
+        assert_eq!(
+            HgAction::Pull {
+                source: None,
+                mode: PullMode::Update,
+                scope: SyncScope::All,
+            }
+            .command_preview(),
+            "hg pull -u"
+        );
+        assert_eq!(
+            HgAction::Pull {
+                source: Some("upstream".to_string()),
+                mode: PullMode::Rebase,
+                scope: SyncScope::Bookmark("feature".to_string()),
+            }
+            .command_preview(),
+            "hg pull --rebase -B feature upstream"
+        );
+        assert_eq!(
+            HgAction::Push {
+                dest: Some("default-push".to_string()),
+                scope: SyncScope::All,
+                new_branch: false,
+            }
+            .command_preview(),
+            "hg push default-push"
+        );
+        assert_eq!(remote_command(RemoteDirection::Pull), "incoming");
+        assert_eq!(
+            path_args(&["incoming"], &Some("upstream".to_string())),
+            vec!["incoming".to_string(), "upstream".to_string()]
+        );
+    }
+
+    fn pull_result(stdout: &str) -> CommandResult {
+        CommandResult {
+            command_preview: "hg pull -u".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    #[test]
+    fn pull_summary_reports_changesets_heads_and_update() {
+        let out = pull_result(
+            "pulling from /srv/repo\nsearching for changes\nadding changesets\nadding manifests\nadding file changes\nadded 2 changesets with 3 changes to 1 files (+1 heads)\nnew changesets abc:def\nnot updating: not a linear update\n(merge or update --check to force update)\n",
+        );
+        assert_eq!(
+            summarize_pull_output(&out),
+            "Pull: 2 new changeset(s), 1 head(s) added, working copy not updated: not a linear update"
+        );
+
+        let out = pull_result(
+            "pulling from /srv/repo\nadded 1 changesets with 1 changes to 1 files\n1 files updated, 0 files merged, 0 files removed, 0 files unresolved\n",
+        );
+        assert_eq!(
+            summarize_pull_output(&out),
+            "Pull: 1 new changeset(s), working copy updated (1 files updated, 0 files merged, 0 files removed, 0 files unresolved)"
+        );
+
+        let out = pull_result("pulling from /srv/repo\nsearching for changes\nno changes found\n");
+        assert_eq!(summarize_pull_output(&out), "Pull: no new changesets");
+    }
+
+    #[test]
+    fn shelve_previews_include_keep_and_selected_files() {
+        let shelve = HgAction::ShelveCreate {
+            name: "wip".to_string(),
+            files: vec!["a.txt".into(), "b.txt".into()],
+            keep: true,
+        };
+        assert_eq!(
+            shelve.command_preview(),
+            "hg shelve --name wip --keep <2 files>"
+        );
+        let unshelve = HgAction::Unshelve {
+            name: "wip".to_string(),
+            keep: true,
+        };
+        assert_eq!(unshelve.command_preview(), "hg unshelve --keep --name wip");
+        let delete = HgAction::ShelveDelete {
+            name: "wip".to_string(),
+        };
+        assert_eq!(delete.command_preview(), "hg shelve -d wip");
+    }
+
+    #[test]
+    fn scoped_push_and_outgoing_previews_match() {
+        let push = HgAction::Push {
+            dest: Some("mirror".to_string()),
+            scope: SyncScope::Bookmark("feature".to_string()),
+            new_branch: true,
+        };
+        assert_eq!(
+            push.command_preview(),
+            "hg push -B feature --new-branch mirror"
+        );
+        let outgoing = HgAction::Outgoing {
+            dest: Some("mirror".to_string()),
+            scope: SyncScope::Bookmark("feature".to_string()),
+        };
+        assert_eq!(outgoing.command_preview(), "hg outgoing -r feature mirror");
+        let branch = HgAction::Outgoing {
+            dest: None,
+            scope: SyncScope::Branch("stable".to_string()),
+        };
+        assert_eq!(branch.command_preview(), "hg outgoing -b stable");
+    }
+
+    #[test]
+    fn tags_json_parser_marks_local_tags() {
+        let raw = r#"[{"node":"abc","rev":9,"tag":"tip","type":""},{"node":"def","rev":4,"tag":"wip","type":"local"}]"#;
+        let parsed = parse_tags_json(raw).expect("parse tags");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name, "tip");
+        assert!(!parsed[0].local);
+        assert_eq!(parsed[1].rev, 4);
+        assert!(parsed[1].local);
+    }
+
+    #[test]
+    fn tags_plain_parser_reads_local_suffix() {
+        let raw = "tip                                9:abc123\nrelease 1.0                        5:def456\nwip                                4:0123ab local\n";
+        let parsed = parse_tags_plain(raw);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[1].name, "release 1.0");
+        assert_eq!(parsed[1].rev, 5);
+        assert!(!parsed[1].local);
+        assert_eq!(parsed[2].node, "0123ab");
+        assert!(parsed[2].local);
+    }
+
+    #[test]
+    fn tag_previews_include_local_flag() {
+        let regular = HgAction::TagCreate {
+            name: "v1.0".to_string(),
+            rev: 5,
+            local: false,
+        };
+        assert_eq!(regular.command_preview(), "hg tag -r 5 v1.0");
+        let local = HgAction::TagRemove {
+            name: "wip".to_string(),
+            local: true,
+        };
+        assert_eq!(local.command_preview(), "hg tag -l --remove wip");
+    }
+
     #[test]
     fn log_plain_template_parser_maps_all_fields() {
         let raw = "9\u{1f}abcdef\u{1f}msg\u{1f}u\u{1f}default\u{1f}draft\u{1f}tip\u{1f}main\u{1f}1700000000 0\n";
@@ -906,9 +3082,19 @@ mod tests {
     fn commit_preview_includes_selected_file_count() {
         let action = HgAction::Commit {
             message: "msg".to_string(),
-            files: vec!["a.txt".to_string(), "b.txt".to_string()],
+            files: vec!["a.txt".into(), "b.txt".into()],
+            close_branch: false,
         };
         assert_eq!(action.command_preview(), "hg commit -m <message> <2 files>");
+        let closing = HgAction::Commit {
+            message: "msg".to_string(),
+            files: Vec::new(),
+            close_branch: true,
+        };
+        assert_eq!(
+            closing.command_preview(),
+            "hg commit --close-branch -m <message>"
+        );
     }
 
     #[test]
@@ -957,6 +3143,9 @@ mod tests {
                 tags: Vec::new(),
                 bookmarks: Vec::new(),
                 date_unix_secs: 7,
+                obsolete: false,
+                instabilities: Vec::new(),
+                topic: None,
                 graph_prefix: None,
             },
             Revision {
@@ -969,6 +3158,9 @@ mod tests {
                 tags: Vec::new(),
                 bookmarks: Vec::new(),
                 date_unix_secs: 8,
+                obsolete: false,
+                instabilities: Vec::new(),
+                topic: None,
                 graph_prefix: None,
             },
             Revision {
@@ -981,6 +3173,9 @@ mod tests {
                 tags: Vec::new(),
                 bookmarks: Vec::new(),
                 date_unix_secs: 9,
+                obsolete: false,
+                instabilities: Vec::new(),
+                topic: None,
                 graph_prefix: None,
             },
         ];
@@ -1017,4 +3212,507 @@ mod tests {
         );
         assert_eq!(HgAction::RebaseAbort.command_preview(), "hg rebase --abort");
     }
+
+    #[test]
+    fn histedit_plan_files_are_created_fresh_and_private() {
+        let steps = vec![HisteditPlanStep {
+            action: HisteditAction::Pick,
+            rev: 3,
+            node: "0123456789abcdef".to_string(),
+            desc: "first".to_string(),
+        }];
+        let first = write_histedit_plan_file(&steps).expect("first plan");
+        let second = write_histedit_plan_file(&steps).expect("second plan");
+        assert_ne!(first, second);
+        assert_eq!(
+            std::fs::read_to_string(&first).expect("plan"),
+            render_histedit_plan(&steps)
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&first)
+                .expect("metadata")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o077, 0);
+        }
+        std::fs::remove_file(first).ok();
+        std::fs::remove_file(second).ok();
+    }
+
+    #[test]
+    fn histedit_plan_renders_one_verb_line_per_step() {
+        let steps = vec![
+            HisteditPlanStep {
+                action: HisteditAction::Pick,
+                rev: 3,
+                node: "0123456789abcdef".to_string(),
+                desc: "first\nbody".to_string(),
+            },
+            HisteditPlanStep {
+                action: HisteditAction::Roll,
+                rev: 4,
+                node: "fedcba9876543210".to_string(),
+                desc: "second".to_string(),
+            },
+        ];
+        assert_eq!(
+            render_histedit_plan(&steps),
+            "pick 0123456789ab 3 first\nroll fedcba987654 4 second\n"
+        );
+        let action = HgAction::HisteditPlan { base_rev: 3, steps };
+        assert_eq!(
+            action.command_preview(),
+            "hg histedit --commands <plan: 2 steps> 3"
+        );
+    }
+
+    #[test]
+    fn evolution_flags_mark_obsolete_and_unstable_revisions() {
+        let mut revisions = parse_log_plain_template(
+            "9\u{1f}n9\u{1f}nine\u{1f}u\u{1f}default\u{1f}draft\u{1f}\u{1f}\u{1f}9 0\n8\u{1f}n8\u{1f}eight\u{1f}u\u{1f}default\u{1f}draft\u{1f}\u{1f}\u{1f}8 0\n",
+        )
+        .expect("parse plain template");
+        apply_revision_metadata(
+            &mut revisions,
+            "9\u{1f}\u{1f}orphan content-divergent\n8\u{1f}obsolete\u{1f}\n",
+        );
+        assert!(!revisions[0].obsolete);
+        assert_eq!(
+            revisions[0].instabilities,
+            vec!["orphan", "content-divergent"]
+        );
+        assert!(revisions[1].obsolete);
+        assert!(revisions[1].instabilities.is_empty());
+    }
+
+    #[test]
+    fn revision_metadata_assigns_topic_when_present() {
+        let mut revisions = parse_log_plain_template(
+            "9\u{1f}n9\u{1f}nine\u{1f}u\u{1f}default\u{1f}draft\u{1f}\u{1f}\u{1f}9 0\n8\u{1f}n8\u{1f}eight\u{1f}u\u{1f}default\u{1f}draft\u{1f}\u{1f}\u{1f}8 0\n",
+        )
+        .expect("parse plain template");
+        apply_revision_metadata(
+            &mut revisions,
+            "9\u{1f}\u{1f}\u{1f}feature-x\n8\u{1f}\u{1f}\u{1f}\n",
+        );
+        assert_eq!(revisions[0].topic.as_deref(), Some("feature-x"));
+        assert_eq!(revisions[1].topic, None);
+    }
+
+    #[test]
+    fn topics_list_parser_reads_active_marker_and_changeset_count() {
+        let raw = "   feature-a (1 changesets)\n * feature-b (2 changesets, 1 behind)\n";
+        let parsed = parse_topics_list(raw);
+        assert_eq!(
+            parsed,
+            vec![
+                Topic {
+                    name: "feature-a".to_string(),
+                    active: false,
+                    changesets: Some(1),
+                },
+                Topic {
+                    name: "feature-b".to_string(),
+                    active: true,
+                    changesets: Some(2),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bookmark_lifecycle_previews_match_commands() {
+        assert_eq!(
+            HgAction::BookmarkDelete {
+                name: "old".to_string()
+            }
+            .command_preview(),
+            "hg bookmark -d old"
+        );
+        assert_eq!(
+            HgAction::BookmarkRename {
+                old: "old".to_string(),
+                new: "new".to_string()
+            }
+            .command_preview(),
+            "hg bookmark -m old new"
+        );
+        assert_eq!(
+            HgAction::BookmarkMove {
+                name: "main".to_string(),
+                rev: 7
+            }
+            .command_preview(),
+            "hg bookmark -f -r 7 main"
+        );
+        assert_eq!(
+            HgAction::BookmarkDeactivate.command_preview(),
+            "hg bookmark -i"
+        );
+    }
+
+    #[test]
+    fn phase_preview_adds_force_only_when_requested() {
+        let forward = HgAction::SetPhase {
+            rev: 4,
+            phase: Phase::Public,
+            force: false,
+        };
+        assert_eq!(forward.command_preview(), "hg phase -p -r 4");
+        let backward = HgAction::SetPhase {
+            rev: 4,
+            phase: Phase::Draft,
+            force: true,
+        };
+        assert_eq!(backward.command_preview(), "hg phase -d -f -r 4");
+    }
+
+    #[test]
+    fn evolve_previews_match_commands() {
+        assert_eq!(HgAction::Prev.command_preview(), "hg prev");
+        assert_eq!(HgAction::Next.command_preview(), "hg next");
+        assert_eq!(
+            HgAction::Evolve { all: false }.command_preview(),
+            "hg evolve"
+        );
+        assert_eq!(
+            HgAction::Evolve { all: true }.command_preview(),
+            "hg evolve --all"
+        );
+        assert_eq!(
+            HgAction::Prune { rev: 7 }.command_preview(),
+            "hg prune -r 7"
+        );
+    }
+
+    #[test]
+    fn backout_preview_adds_merge_flag_only_when_requested() {
+        let linear = HgAction::Backout {
+            rev: 4,
+            message: "Backed out changeset abc".to_string(),
+            merge: false,
+        };
+        assert_eq!(linear.command_preview(), "hg backout -r 4 -m <message>");
+        let merged = HgAction::Backout {
+            rev: 4,
+            message: "Backed out changeset abc".to_string(),
+            merge: true,
+        };
+        assert_eq!(
+            merged.command_preview(),
+            "hg backout -r 4 --merge -m <message>"
+        );
+    }
+
+    #[test]
+    fn parses_progress_segments() {
+        assert_eq!(
+            parse_progress_line("changesets   3/45"),
+            Some(HgProgress {
+                topic: "changesets".to_string(),
+                pos: 3,
+                total: Some(45),
+            })
+        );
+        assert_eq!(
+            parse_progress_line("searching 12 "),
+            Some(HgProgress {
+                topic: "searching".to_string(),
+                pos: 12,
+                total: None,
+            })
+        );
+        assert_eq!(parse_progress_line("abort: no changes"), None);
+        assert_eq!(parse_progress_line("   "), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn stream_pipe_splits_lines_and_progress() {
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink_chunks = Arc::clone(&chunks);
+        let output: OutputSink = Arc::new(move |chunk| {
+            sink_chunks.lock().expect("chunks").push(chunk);
+        });
+        let raw: &[u8] = b"\rfiles 1/2\rfiles 2/2\r         \rwarning: x\nadded 2 changesets";
+        let collected = stream_pipe(raw, true, &output).await.expect("stream");
+        assert_eq!(collected, "warning: x\nadded 2 changesets");
+        let chunks = chunks.lock().expect("chunks").clone();
+        assert_eq!(
+            chunks,
+            vec![
+                OutputChunk::Progress(Some(HgProgress {
+                    topic: "files".to_string(),
+                    pos: 1,
+                    total: Some(2),
+                })),
+                OutputChunk::Progress(Some(HgProgress {
+                    topic: "files".to_string(),
+                    pos: 2,
+                    total: Some(2),
+                })),
+                OutputChunk::Progress(None),
+                OutputChunk::Stderr("warning: x".to_string()),
+                OutputChunk::Stderr("added 2 changesets".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn stream_pipe_keeps_crlf_and_remote_text() {
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink_chunks = Arc::clone(&chunks);
+        let output: OutputSink = Arc::new(move |chunk| {
+            sink_chunks.lock().expect("chunks").push(chunk);
+        });
+        let raw: &[u8] = b"remote: adding changesets\r\nfiles 1/2\rremote: done\rpushed\r\n";
+        let collected = stream_pipe(raw, true, &output).await.expect("stream");
+        assert_eq!(
+            collected,
+            "remote: adding changesets\nremote: done\npushed\n"
+        );
+        let lines = chunks
+            .lock()
+            .expect("chunks")
+            .iter()
+            .filter_map(|chunk| match chunk {
+                OutputChunk::Stderr(line) => Some(line.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            ["remote: adding changesets", "remote: done", "pushed"]
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn custom_commands_stream_their_output() {
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink_chunks = Arc::clone(&chunks);
+        let output: OutputSink = Arc::new(move |chunk| {
+            sink_chunks.lock().expect("chunks").push(chunk);
+        });
+        let invocation = CustomInvocation {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), "echo out; echo err >&2".to_string()],
+            env: Vec::new(),
+        };
+        let client = CliHgClient::new(std::env::temp_dir());
+        let result = client
+            .run_custom_command(&invocation, Some(&output))
+            .await
+            .expect("custom command");
+        assert!(result.success);
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err\n");
+        let chunks = chunks.lock().expect("chunks").clone();
+        assert!(chunks.contains(&OutputChunk::Stdout("out".to_string())));
+        assert!(chunks.contains(&OutputChunk::Stderr("err".to_string())));
+    }
+
+    #[test]
+    fn parses_lock_wait_messages() {
+        let wait = parse_lock_wait(
+            "waiting for lock on working directory of /repo held by process '4242' on host 'box'",
+        )
+        .expect("wlock wait");
+        assert!(wait.wlock);
+        assert_eq!(wait.holder.as_deref(), Some("process '4242' on host 'box'"));
+        let wait = parse_lock_wait("waiting for lock on repository /repo held by 'box:4242'")
+            .expect("store lock wait");
+        assert!(!wait.wlock);
+        assert_eq!(wait.holder.as_deref(), Some("'box:4242'"));
+        assert_eq!(parse_lock_wait("pulling from default"), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn timeouts_abort_slow_commands_per_class() {
+        let timeouts = CommandTimeouts {
+            read: Some(Duration::from_millis(10)),
+            write: None,
+            network: None,
+        };
+        let err = timeouts
+            .apply(
+                CommandClass::Read,
+                "hg status",
+                tokio::time::sleep(Duration::from_secs(5)),
+            )
+            .await
+            .expect_err("read times out");
+        assert_eq!(
+            err.to_string(),
+            "hg status timed out after 0s (read timeout)"
+        );
+        assert!(
+            timeouts
+                .apply(CommandClass::Write, "hg commit", async { 7 })
+                .await
+                .is_ok_and(|value| value == 7)
+        );
+        assert_eq!(
+            CommandTimeouts::from_secs(0, 5, 0).for_class(CommandClass::Write),
+            Some(Duration::from_secs(5))
+        );
+    }
+
+    fn command_env(command: &Command, key: &str) -> Option<Option<String>> {
+        command
+            .as_std()
+            .get_envs()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.map(|value| value.to_string_lossy().into_owned()))
+    }
+
+    #[test]
+    fn parsing_commands_run_plain_with_pinned_config() {
+        let client =
+            CliHgClient::new(PathBuf::from("/repo")).with_plain_except(vec!["alias".to_string()]);
+        let parsed = client.hg_command(HgEnv::Parsed);
+        assert_eq!(command_env(&parsed, "HGPLAIN"), Some(Some("1".to_string())));
+        assert_eq!(command_env(&parsed, "HGPLAINEXCEPT"), Some(None));
+        assert_eq!(
+            command_env(&parsed, "HGENCODING"),
+            Some(Some("utf-8".to_string()))
+        );
+        let args = parsed
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert!(args.contains(&"ui.paginate=false".to_string()));
+
+        let user = client.hg_command(HgEnv::UserFacing);
+        assert_eq!(
+            command_env(&user, "HGPLAINEXCEPT"),
+            Some(Some("alias,progress".to_string()))
+        );
+        assert_eq!(user.as_std().get_args().count(), 0);
+
+        let inherited = client.hg_command(HgEnv::Inherited);
+        assert_eq!(inherited.as_std().get_envs().count(), 0);
+    }
+
+    #[test]
+    fn finds_output_affecting_user_settings() {
+        let config = "ui.username=Jo <jo@example.com>\nalias.st=status -q\nui.tweakdefaults=true\npaths.default=https://example.com\ncommands.status.relative=true\n";
+        assert_eq!(
+            output_affecting_settings(config),
+            vec![
+                "alias.st=status -q",
+                "ui.tweakdefaults=true",
+                "commands.status.relative=true",
+            ]
+        );
+    }
+
+    #[test]
+    fn print0_parsers_keep_non_utf8_names() {
+        let status = parse_status_print0(b"M caf\xe9.txt\0? new file\0");
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].status, FileStatus::Modified);
+        assert_eq!(status[0].path.as_bytes(), b"caf\xe9.txt");
+        assert_eq!(status[0].path.to_string(), "caf\u{fffd}.txt");
+        assert_eq!(status[1].path, "new file");
+
+        let conflicts = parse_resolve_print0(b"U a\xff.rs\0R b.rs\0");
+        assert!(!conflicts[0].resolved);
+        assert_eq!(conflicts[0].path.as_bytes(), b"a\xff.rs");
+        assert!(conflicts[1].resolved);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn repo_path_patterns_are_byte_exact() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = RepoPath::from_bytes(b"dir/caf\xe9".to_vec());
+        assert_eq!(path.hg_pattern().as_bytes(), b"path:dir/caf\xe9");
+    }
+
+    #[test]
+    fn dry_run_plans_use_read_only_equivalents() {
+        let push = HgAction::Push {
+            dest: Some("upstream".to_string()),
+            scope: SyncScope::Bookmark("feature".to_string()),
+            new_branch: false,
+        };
+        let plan = push.dry_run_plan();
+        assert_eq!(
+            plan.steps,
+            vec![os_args(&["outgoing", "-r", "feature", "upstream"])]
+        );
+        let dry_push = HgAction::DryRun {
+            action: Box::new(push),
+        };
+        assert!(dry_push.is_read_only());
+        assert_eq!(dry_push.command_class(), CommandClass::Network);
+        assert_eq!(
+            dry_push.command_preview(),
+            "dry run: hg push -B feature upstream"
+        );
+
+        let rebase = HgAction::RebaseSourceDest {
+            source_rev: 3,
+            dest_rev: 7,
+        };
+        assert_eq!(
+            rebase.dry_run_plan().steps,
+            vec![os_args(&["rebase", "--dry-run", "-s", "3", "-d", "7"])]
+        );
+        let dry_rebase = HgAction::DryRun {
+            action: Box::new(rebase),
+        };
+        assert_eq!(dry_rebase.command_class(), CommandClass::Read);
+
+        let commit = HgAction::Commit {
+            message: "fix parser\n\nbody".to_string(),
+            files: vec![RepoPath::from_bytes("src/a.rs")],
+            close_branch: false,
+        };
+        let plan = commit.dry_run_plan();
+        assert_eq!(
+            plan.summary,
+            "Would commit 1 picked file(s) with message \"fix parser\"."
+        );
+        assert_eq!(
+            plan.steps,
+            vec![
+                os_args(&["status", "-mar", "path:src/a.rs"]),
+                os_args(&["diff", "path:src/a.rs"]),
+            ]
+        );
+
+        let backout = HgAction::Backout {
+            rev: 5,
+            message: "m".to_string(),
+            merge: false,
+        };
+        assert_eq!(
+            backout.dry_run_plan().steps,
+            vec![os_args(&["diff", "-c", "5", "--reverse"])]
+        );
+
+        let update = HgAction::UpdateToBookmark {
+            name: "stable".to_string(),
+        };
+        assert_eq!(
+            update.dry_run_plan().steps[1],
+            os_args(&["status", "--rev", ".", "--rev", "stable"])
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn resolve_print0_args_can_be_spawned() {
+        assert!(RESOLVE_PRINT0_ARGS.iter().all(|arg| !arg.contains('\0')));
+        let client = CliHgClient::new(std::env::temp_dir());
+        let mut command = client.hg_command(HgEnv::Parsed);
+        command.args(RESOLVE_PRINT0_ARGS);
+        // hg may be missing or this may not be a repository; only a rejected
+        // argument list is a failure.
+        if let Err(err) = command.output().await {
+            assert_ne!(err.kind(), std::io::ErrorKind::InvalidInput, "{err}");
+        }
+    }
 }
@@ -0,0 +1,222 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Mutex, OwnedMutexGuard, mpsc};
+use tokio::task::AbortHandle;
+
+use crate::app::AppEvent;
+use crate::hg::HgProgress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Read,
+    // Pull/push: they take the write lock, but most of their time is spent
+    // on the transfer, so killing them is not treated like killing a rewrite.
+    Network,
+    Write,
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: u64,
+    pub label: String,
+    pub kind: JobKind,
+    pub queued_at: Instant,
+    pub started_at: Option<Instant>,
+    pub progress: Option<HgProgress>,
+    handle: AbortHandle,
+}
+
+impl Job {
+    pub fn is_queued(&self) -> bool {
+        self.started_at.is_none()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.unwrap_or(self.queued_at).elapsed()
+    }
+
+    // Killing a running write can stop hg inside a transaction.
+    pub fn is_running_write(&self) -> bool {
+        self.kind == JobKind::Write && !self.is_queued()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct JobManager {
+    next_id: u64,
+    jobs: Vec<Job>,
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl JobManager {
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    // Writes hold the shared lock for their whole run so two of them never
+    // race on the repository lock; reads never touch it.
+    pub fn spawn<F>(
+        &mut self,
+        label: impl Into<String>,
+        kind: JobKind,
+        tx: mpsc::UnboundedSender<AppEvent>,
+        task: impl FnOnce(u64) -> F,
+    ) -> u64
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.next_id += 1;
+        let id = self.next_id;
+        let task = task(id);
+        let (guard, queued) = match kind {
+            JobKind::Read => (None, None),
+            JobKind::Network | JobKind::Write => {
+                match Arc::clone(&self.write_lock).try_lock_owned() {
+                    Ok(guard) => (Some(guard), None),
+                    Err(_) => (None, Some(Arc::clone(&self.write_lock))),
+                }
+            }
+        };
+        let started_at = queued.is_none().then(Instant::now);
+        let handle = tokio::spawn(async move {
+            let _guard: Option<OwnedMutexGuard<()>> = match queued {
+                Some(lock) => {
+                    let guard = lock.lock_owned().await;
+                    let _ = tx.send(AppEvent::JobStarted { id });
+                    Some(guard)
+                }
+                None => guard,
+            };
+            task.await;
+            let _ = tx.send(AppEvent::JobFinished { id });
+        });
+        self.jobs.push(Job {
+            id,
+            label: label.into(),
+            kind,
+            queued_at: Instant::now(),
+            started_at,
+            progress: None,
+            handle: handle.abort_handle(),
+        });
+        id
+    }
+
+    pub fn mark_started(&mut self, id: u64) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.started_at.get_or_insert_with(Instant::now);
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn set_progress(&mut self, id: u64, progress: Option<HgProgress>) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.progress = progress;
+        }
+    }
+
+    pub fn finish(&mut self, id: u64) {
+        self.jobs.retain(|job| job.id != id);
+    }
+
+    pub fn latest_active(&self) -> Option<&Job> {
+        self.jobs.iter().rev().find(|job| !job.handle.is_finished())
+    }
+
+    // Aborting the task drops the child process handle, which kills the
+    // process because commands are spawned with kill_on_drop.
+    pub fn cancel(&mut self, id: u64) -> Option<Job> {
+        let idx = self
+            .jobs
+            .iter()
+            .position(|job| job.id == id && !job.handle.is_finished())?;
+        let job = self.jobs.remove(idx);
+        job.handle.abort();
+        Some(job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    fn pending_task() -> (
+        oneshot::Sender<()>,
+        impl Future<Output = ()> + Send + 'static,
+    ) {
+        let (release, wait) = oneshot::channel::<()>();
+        (release, async move {
+            let _ = wait.await;
+        })
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn writes_are_serialised_while_reads_run_concurrently() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut jobs = JobManager::default();
+        let (release_first, first) = pending_task();
+        let (_release_second, second) = pending_task();
+        let (_release_read, read) = pending_task();
+
+        let first_id = jobs.spawn("hg pull", JobKind::Write, tx.clone(), move |_| first);
+        let second_id = jobs.spawn("hg update", JobKind::Write, tx.clone(), move |_| second);
+        jobs.spawn("hg outgoing", JobKind::Read, tx.clone(), move |_| read);
+        assert!(!jobs.jobs()[0].is_queued());
+        assert!(jobs.jobs()[1].is_queued());
+        assert!(!jobs.jobs()[2].is_queued());
+
+        release_first.send(()).expect("release first write");
+        let mut started = None;
+        while started.is_none() {
+            match rx.recv().await.expect("job event") {
+                AppEvent::JobFinished { id } => jobs.finish(id),
+                AppEvent::JobStarted { id } => {
+                    jobs.mark_started(id);
+                    started = Some(id);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(started, Some(second_id));
+        assert!(jobs.jobs().iter().all(|job| job.id != first_id));
+        assert!(jobs.jobs().iter().all(|job| !job.is_queued()));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn cancel_releases_write_lock_for_queued_job() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut jobs = JobManager::default();
+        let (_release_first, first) = pending_task();
+        let (_release_second, second) = pending_task();
+        jobs.spawn("hg pull", JobKind::Network, tx.clone(), move |_| first);
+        jobs.spawn("hg push", JobKind::Network, tx.clone(), move |_| second);
+
+        let cancel_latest = |jobs: &mut JobManager| {
+            let id = jobs.latest_active()?.id;
+            jobs.cancel(id)
+        };
+        let cancelled = cancel_latest(&mut jobs).expect("cancel queued push");
+        assert_eq!(cancelled.label, "hg push");
+        let cancelled = cancel_latest(&mut jobs).expect("cancel running pull");
+        assert_eq!(cancelled.label, "hg pull");
+        assert!(!cancelled.is_running_write());
+        assert!(jobs.is_empty());
+        assert!(cancel_latest(&mut jobs).is_none());
+
+        tokio::task::yield_now().await;
+        let (_release_third, third) = pending_task();
+        jobs.spawn("hg update", JobKind::Write, tx, move |_| third);
+        assert!(jobs.jobs()[0].is_running_write());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,19 +1,25 @@
 mod actions;
 mod app;
+mod backups;
 mod config;
 mod custom_commands;
 mod domain;
 mod hg;
+mod jobs;
+mod protection;
+mod remotes;
 mod ui;
+mod undo;
 
 use anyhow::{Result, bail};
 use async_trait::async_trait;
 use chrono::Utc;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use crate::domain::{HgCapabilities, RepoSnapshot};
-use crate::hg::{CliHgClient, HgClient, SnapshotOptions};
+use crate::hg::{CliHgClient, HgClient, SnapshotOptions, output_affecting_settings};
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -105,6 +111,8 @@ struct CheckConfigOutput {
     ok: bool,
     path: Option<String>,
     issues: Vec<String>,
+    // Effective per-action confirmation policies from `[confirmations]`.
+    confirmations: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -125,6 +133,7 @@ struct DoctorOutput {
     branch: Option<String>,
     probes: Vec<ProbeOutput>,
     capability_probes: Vec<ProbeOutput>,
+    user_config_overrides: Vec<String>,
     error: Option<String>,
 }
 
@@ -139,6 +148,7 @@ struct SnapshotOutput {
 #[async_trait]
 trait CliModeHgClient: Send + Sync {
     async fn run_hg_args(&self, args: &[&str]) -> Result<crate::hg::CommandResult>;
+    async fn run_hg_inherited_args(&self, args: &[&str]) -> Result<crate::hg::CommandResult>;
     async fn detect_capabilities(&self) -> HgCapabilities;
     async fn refresh_snapshot(&self, options: SnapshotOptions) -> Result<RepoSnapshot>;
 }
@@ -149,6 +159,10 @@ impl CliModeHgClient for CliHgClient {
         self.run_hg(args).await
     }
 
+    async fn run_hg_inherited_args(&self, args: &[&str]) -> Result<crate::hg::CommandResult> {
+        self.run_hg_inherited(args).await
+    }
+
     async fn detect_capabilities(&self) -> HgCapabilities {
         CliHgClient::detect_capabilities(self).await
     }
@@ -167,6 +181,10 @@ fn check_config_output(report: config::ConfigLoadReport) -> CheckConfigOutput {
         ok: report.issues.is_empty(),
         path: report.path.map(|p| p.display().to_string()),
         issues: report.issues,
+        confirmations: actions::confirmation_policies(&report.config.confirmations)
+            .into_iter()
+            .map(|(action, policy)| (action.as_str().to_string(), policy.as_str().to_string()))
+            .collect(),
     }
 }
 
@@ -308,6 +326,13 @@ async fn build_doctor_output(
         }
     }
 
+    // easyhg runs its parsing commands under HGPLAIN with pinned config; list
+    // the user settings that would otherwise have changed their output.
+    let user_config_overrides = match hg.run_hg_inherited_args(&["config"]).await {
+        Ok(result) if result.success => output_affecting_settings(&result.stdout),
+        _ => Vec::new(),
+    };
+
     let capabilities = Some(hg.detect_capabilities().await);
     let mut repo_root = None;
     let mut branch = None;
@@ -340,6 +365,7 @@ async fn build_doctor_output(
         branch,
         probes,
         capability_probes,
+        user_config_overrides,
         error,
     }
 }
@@ -390,6 +416,9 @@ mod tests {
                     has_rebase: true,
                     has_histedit: true,
                     has_shelve: true,
+                    has_evolve: true,
+                    has_topic: true,
+                    has_undo: false,
                     supports_json_status: true,
                     supports_json_log: true,
                     supports_json_bookmarks: true,
@@ -410,6 +439,10 @@ mod tests {
             }
         }
 
+        async fn run_hg_inherited_args(&self, args: &[&str]) -> Result<crate::hg::CommandResult> {
+            self.run_hg_args(args).await
+        }
+
         async fn detect_capabilities(&self) -> HgCapabilities {
             self.capabilities.clone()
         }
@@ -466,12 +499,23 @@ mod tests {
 
     #[test]
     fn check_config_output_ok_has_zero_exit_code() {
+        let config = config::AppConfig {
+            confirmations: HashMap::from([
+                ("push".to_string(), "typed".to_string()),
+                ("quit".to_string(), "always".to_string()),
+            ]),
+            ..config::AppConfig::default()
+        };
         let output = check_config_output(config::ConfigLoadReport {
-            config: config::AppConfig::default(),
+            config,
             path: None,
             issues: Vec::new(),
         });
         assert!(output.ok);
+        assert_eq!(
+            output.confirmations,
+            BTreeMap::from([("push".to_string(), "typed".to_string())])
+        );
         assert_eq!(output_exit_code(output.ok), 0);
     }
 
@@ -483,6 +527,7 @@ mod tests {
             Ok(crate::hg::CommandResult {
                 command_preview: "hg root".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: "/repo\n".to_string(),
                 stderr: String::new(),
             }),
@@ -501,6 +546,7 @@ mod tests {
             Ok(crate::hg::CommandResult {
                 command_preview: "hg root".to_string(),
                 success: false,
+                exit_code: Some(1),
                 stdout: String::new(),
                 stderr: "abort: no repository found".to_string(),
             }),
@@ -557,6 +603,7 @@ mod tests {
             Ok(crate::hg::CommandResult {
                 command_preview: "hg --version".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: "Mercurial 6.9".to_string(),
                 stderr: String::new(),
             }),
@@ -566,6 +613,7 @@ mod tests {
             Ok(crate::hg::CommandResult {
                 command_preview: "hg root".to_string(),
                 success: false,
+                exit_code: Some(1),
                 stdout: String::new(),
                 stderr: "abort: no repository found".to_string(),
             }),
@@ -575,6 +623,7 @@ mod tests {
             Ok(crate::hg::CommandResult {
                 command_preview: "hg status -Tjson".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: "[]".to_string(),
                 stderr: String::new(),
             }),
@@ -584,6 +633,7 @@ mod tests {
             Ok(crate::hg::CommandResult {
                 command_preview: "hg log -l 5 -Tjson".to_string(),
                 success: true,
+                exit_code: Some(0),
                 stdout: "[]".to_string(),
                 stderr: String::new(),
             }),
@@ -610,12 +660,23 @@ mod tests {
     #[tokio::test(flavor = "current_thread")]
     async fn doctor_output_success_when_probes_snapshot_and_config_are_ok() {
         let mut run_results = HashMap::new();
+        run_results.insert(
+            "config".to_string(),
+            Ok(crate::hg::CommandResult {
+                command_preview: "hg config".to_string(),
+                success: true,
+                exit_code: Some(0),
+                stdout: "ui.username=jo\nalias.log=log -G\nui.tweakdefaults=true\n".to_string(),
+                stderr: String::new(),
+            }),
+        );
         for key in ["--version", "root", "status -Tjson", "log -l 5 -Tjson"] {
             run_results.insert(
                 key.to_string(),
                 Ok(crate::hg::CommandResult {
                     command_preview: format!("hg {key}"),
                     success: true,
+                    exit_code: Some(0),
                     stdout: String::new(),
                     stderr: String::new(),
                 }),
@@ -644,6 +705,10 @@ mod tests {
         assert_eq!(out.timestamp_unix_secs, 201);
         assert_eq!(out.repo_root, Some("/tmp/repo".to_string()));
         assert_eq!(out.branch, Some("default".to_string()));
+        assert_eq!(
+            out.user_config_overrides,
+            vec!["alias.log=log -G", "ui.tweakdefaults=true"]
+        );
         assert_eq!(output_exit_code(out.ok), 0);
     }
 }
@@ -0,0 +1,340 @@
+use crate::config::ProtectedConfig;
+use crate::domain::{RepoSnapshot, Revision};
+use crate::hg::{HgAction, SyncScope};
+
+pub const PROTECTION_MODES: &[&str] = &["confirm", "refuse"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionMode {
+    Confirm,
+    Refuse,
+}
+
+impl ProtectionMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "confirm" => Some(Self::Confirm),
+            "refuse" => Some(Self::Refuse),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtectionHit {
+    // What the user has to type to go ahead.
+    pub target: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProtectionPolicy {
+    names: Vec<String>,
+    patterns: Vec<String>,
+    public_phase: bool,
+    pub mode: ProtectionMode,
+}
+
+impl ProtectionPolicy {
+    pub fn from_config(config: &ProtectedConfig) -> Self {
+        Self {
+            names: config
+                .names
+                .iter()
+                .map(|name| name.trim().to_string())
+                .collect(),
+            patterns: config
+                .patterns
+                .iter()
+                .map(|pattern| pattern.trim().to_string())
+                .collect(),
+            public_phase: config.public_phase,
+            mode: ProtectionMode::from_name(config.mode.trim()).unwrap_or(ProtectionMode::Confirm),
+        }
+    }
+
+    pub fn protects_name(&self, name: &str) -> bool {
+        self.names.iter().any(|protected| protected == name)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, name))
+    }
+
+    fn protected_ref(&self, revision: &Revision) -> Option<ProtectionHit> {
+        if let Some(name) = revision
+            .bookmarks
+            .iter()
+            .find(|name| self.protects_name(name))
+        {
+            return Some(hit(name, format!("bookmark {name}")));
+        }
+        if self.protects_name(&revision.branch) {
+            return Some(hit(&revision.branch, format!("branch {}", revision.branch)));
+        }
+        if self.public_phase && revision.phase == "public" {
+            let rev = revision.rev.to_string();
+            return Some(hit(&rev, format!("public revision {rev}")));
+        }
+        None
+    }
+
+    fn revision_ref(&self, snapshot: &RepoSnapshot, rev: i64) -> Option<ProtectionHit> {
+        snapshot
+            .revisions
+            .iter()
+            .find(|revision| revision.rev == rev)
+            .and_then(|revision| self.protected_ref(revision))
+    }
+
+    fn working_ref(&self, snapshot: &RepoSnapshot) -> Option<ProtectionHit> {
+        if let Some(bookmark) = snapshot
+            .bookmarks
+            .iter()
+            .find(|bookmark| bookmark.active && self.protects_name(&bookmark.name))
+        {
+            return Some(hit(&bookmark.name, format!("bookmark {}", bookmark.name)));
+        }
+        snapshot
+            .branch
+            .as_deref()
+            .filter(|branch| self.protects_name(branch))
+            .map(|branch| hit(branch, format!("branch {branch}")))
+    }
+
+    pub fn check(&self, action: &HgAction, snapshot: &RepoSnapshot) -> Option<ProtectionHit> {
+        let (operation, found) = match action {
+            HgAction::Push { scope, .. } => {
+                let found = match scope {
+                    // A plain push sends every outgoing head; the push preview
+                    // checks those with `check_outgoing`.
+                    SyncScope::All => None,
+                    SyncScope::Rev(rev) => snapshot
+                        .revisions
+                        .iter()
+                        .find(|revision| revision.rev.to_string() == *rev || revision.node == *rev)
+                        .and_then(|revision| self.protected_ref(revision)),
+                    SyncScope::Bookmark(name) => self
+                        .protects_name(name)
+                        .then(|| hit(name, format!("bookmark {name}"))),
+                    SyncScope::Branch(name) => self
+                        .protects_name(name)
+                        .then(|| hit(name, format!("branch {name}"))),
+                };
+                ("Pushing to", found)
+            }
+            HgAction::RebaseSourceDest { source_rev, .. } => {
+                ("Rebasing", self.revision_ref(snapshot, *source_rev))
+            }
+            HgAction::HisteditPlan { base_rev, .. } => {
+                ("Rewriting", self.revision_ref(snapshot, *base_rev))
+            }
+            HgAction::Prune { rev } => ("Pruning", self.revision_ref(snapshot, *rev)),
+            HgAction::BookmarkMove { name, .. }
+            | HgAction::BookmarkDelete { name }
+            | HgAction::BookmarkRename { old: name, .. } => (
+                "Changing",
+                self.protects_name(name)
+                    .then(|| hit(name, format!("bookmark {name}"))),
+            ),
+            HgAction::Commit { .. } => ("Committing directly on", self.working_ref(snapshot)),
+            _ => return None,
+        };
+        found.map(|found| ProtectionHit {
+            reason: format!("{operation} protected {}", found.reason),
+            target: found.target,
+        })
+    }
+}
+
+impl ProtectionPolicy {
+    // Unscoped push: the changesets `hg outgoing` listed, or the working
+    // directory's bookmark/branch when they could not be listed.
+    pub fn check_outgoing(
+        &self,
+        outgoing: Option<&[Revision]>,
+        snapshot: &RepoSnapshot,
+    ) -> Option<ProtectionHit> {
+        let found = match outgoing {
+            Some(revisions) => revisions
+                .iter()
+                .find_map(|revision| self.protected_ref(revision)),
+            None => self.working_ref(snapshot),
+        };
+        found.map(|found| ProtectionHit {
+            reason: format!("Pushing to protected {}", found.reason),
+            target: found.target,
+        })
+    }
+}
+
+fn hit(target: &str, reason: String) -> ProtectionHit {
+    ProtectionHit {
+        target: target.to_string(),
+        reason,
+    }
+}
+
+// `*` matches any run of characters (including `/`), `?` a single one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(ch) if *ch == '?' || *ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Bookmark;
+
+    fn policy() -> ProtectionPolicy {
+        ProtectionPolicy::from_config(&ProtectedConfig {
+            names: vec!["main".to_string()],
+            patterns: vec!["release/*".to_string()],
+            public_phase: true,
+            mode: "confirm".to_string(),
+        })
+    }
+
+    fn revision(rev: i64, branch: &str, phase: &str, bookmarks: &[&str]) -> Revision {
+        Revision {
+            rev,
+            node: format!("node-{rev}"),
+            desc: String::new(),
+            user: String::new(),
+            branch: branch.to_string(),
+            phase: phase.to_string(),
+            tags: Vec::new(),
+            bookmarks: bookmarks.iter().map(|name| name.to_string()).collect(),
+            date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
+            graph_prefix: None,
+        }
+    }
+
+    #[test]
+    fn glob_patterns_match_names() {
+        assert!(glob_match("release/*", "release/1.0"));
+        assert!(glob_match("release/*", "release/"));
+        assert!(!glob_match("release/*", "releases/1.0"));
+        assert!(glob_match("hot?ix-*", "hotfix-42"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("main", "mainline"));
+    }
+
+    #[test]
+    fn protected_targets_are_detected_per_action() {
+        let policy = policy();
+        let snapshot = RepoSnapshot {
+            branch: Some("default".to_string()),
+            bookmarks: vec![Bookmark {
+                name: "main".to_string(),
+                rev: 2,
+                node: "node-2".to_string(),
+                active: true,
+            }],
+            revisions: vec![
+                revision(2, "default", "draft", &["main"]),
+                revision(1, "release/1.0", "draft", &[]),
+                revision(0, "default", "public", &[]),
+            ],
+            ..RepoSnapshot::default()
+        };
+
+        let push = HgAction::Push {
+            dest: None,
+            scope: SyncScope::Branch("release/1.0".to_string()),
+            new_branch: false,
+        };
+        let found = policy.check(&push, &snapshot).expect("protected branch");
+        assert_eq!(found.target, "release/1.0");
+        assert_eq!(found.reason, "Pushing to protected branch release/1.0");
+
+        let commit = HgAction::Commit {
+            message: "m".to_string(),
+            files: Vec::new(),
+            close_branch: false,
+        };
+        assert_eq!(
+            policy
+                .check(&commit, &snapshot)
+                .expect("active bookmark")
+                .target,
+            "main"
+        );
+
+        let rebase = HgAction::RebaseSourceDest {
+            source_rev: 0,
+            dest_rev: 2,
+        };
+        assert_eq!(
+            policy
+                .check(&rebase, &snapshot)
+                .expect("public source")
+                .reason,
+            "Rebasing protected public revision 0"
+        );
+
+        let push_all = HgAction::Push {
+            dest: None,
+            scope: SyncScope::All,
+            new_branch: false,
+        };
+        assert!(policy.check(&push_all, &snapshot).is_none());
+        let outgoing = [
+            revision(4, "default", "draft", &[]),
+            revision(5, "default", "draft", &["release/2.0"]),
+        ];
+        assert_eq!(
+            policy
+                .check_outgoing(Some(&outgoing), &snapshot)
+                .expect("outgoing protected bookmark")
+                .reason,
+            "Pushing to protected bookmark release/2.0"
+        );
+        assert!(
+            policy
+                .check_outgoing(Some(&outgoing[..1]), &snapshot)
+                .is_none()
+        );
+        assert_eq!(
+            policy
+                .check_outgoing(None, &snapshot)
+                .expect("working directory fallback")
+                .target,
+            "main"
+        );
+
+        let rename = HgAction::BookmarkRename {
+            old: "feature".to_string(),
+            new: "main".to_string(),
+        };
+        assert!(policy.check(&rename, &snapshot).is_none());
+        let update = HgAction::UpdateToRevision { rev: 2 };
+        assert!(policy.check(&update, &snapshot).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteDirection {
+    Pull,
+    Push,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RemoteDefaultsFile {
+    #[serde(default)]
+    repos: BTreeMap<String, RemoteDefaults>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RemoteDefaultsStore {
+    path: Option<PathBuf>,
+    repos: BTreeMap<String, RemoteDefaults>,
+}
+
+impl RemoteDefaultsStore {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let repos = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|raw| toml::from_str::<RemoteDefaultsFile>(&raw).ok())
+            .map(|file| file.repos)
+            .unwrap_or_default();
+        Self { path, repos }
+    }
+
+    pub fn get(&self, repo_root: &str, direction: RemoteDirection) -> Option<&str> {
+        let defaults = self.repos.get(repo_root)?;
+        match direction {
+            RemoteDirection::Pull => defaults.pull.as_deref(),
+            RemoteDirection::Push => defaults.push.as_deref(),
+        }
+    }
+
+    pub fn set(
+        &mut self,
+        repo_root: &str,
+        direction: RemoteDirection,
+        name: &str,
+    ) -> Result<(), String> {
+        let defaults = self.repos.entry(repo_root.to_string()).or_default();
+        let slot = match direction {
+            RemoteDirection::Pull => &mut defaults.pull,
+            RemoteDirection::Push => &mut defaults.push,
+        };
+        if slot.as_deref() == Some(name) {
+            return Ok(());
+        }
+        *slot = Some(name.to_string());
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("failed creating {parent:?}: {err}"))?;
+        }
+        let raw = toml::to_string(&RemoteDefaultsFile {
+            repos: self.repos.clone(),
+        })
+        .map_err(|err| format!("failed serializing remote defaults: {err}"))?;
+        fs::write(path, raw).map_err(|err| format!("failed writing {path:?}: {err}"))
+    }
+}
+
+pub fn default_remote_defaults_path() -> Option<PathBuf> {
+    let mut base = dirs::config_dir()?;
+    base.push("easyhg");
+    base.push("remotes.toml");
+    Some(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn store_round_trips_per_repo_defaults() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("easyhg-remotes-{nanos}"));
+        let path = dir.join("remotes.toml");
+
+        let mut store = RemoteDefaultsStore::load(Some(path.clone()));
+        assert_eq!(store.get("/repo/a", RemoteDirection::Pull), None);
+        store
+            .set("/repo/a", RemoteDirection::Pull, "upstream")
+            .expect("save pull");
+        store
+            .set("/repo/a", RemoteDirection::Push, "default-push")
+            .expect("save push");
+        store
+            .set("/repo/b", RemoteDirection::Push, "mirror")
+            .expect("save other repo");
+
+        let reloaded = RemoteDefaultsStore::load(Some(path));
+        assert_eq!(
+            reloaded.get("/repo/a", RemoteDirection::Pull),
+            Some("upstream")
+        );
+        assert_eq!(
+            reloaded.get("/repo/a", RemoteDirection::Push),
+            Some("default-push")
+        );
+        assert_eq!(reloaded.get("/repo/b", RemoteDirection::Pull), None);
+        assert_eq!(
+            reloaded.get("/repo/b", RemoteDirection::Push),
+            Some("mirror")
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn store_without_path_keeps_defaults_in_memory() {
+        let mut store = RemoteDefaultsStore::default();
+        store
+            .set("/repo", RemoteDirection::Pull, "default")
+            .expect("in-memory set");
+        assert_eq!(store.get("/repo", RemoteDirection::Pull), Some("default"));
+    }
+}
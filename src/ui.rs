@@ -8,8 +8,14 @@ use ratatui::widgets::{
 };
 
 use crate::actions::ActionId;
-use crate::app::{App, FocusPanel};
-use crate::domain::{Bookmark, ConflictEntry, FileChange, Revision, Shelf};
+use crate::app::{App, FocusPanel, LogEntry, remote_direction_label};
+use crate::domain::{
+    Bookmark, Branch, ConflictEntry, FileChange, HisteditAction, Revision, Shelf, Tag,
+};
+use crate::hg::{HgProgress, HisteditPlanStep};
+use crate::remotes::RemoteDirection;
+
+const PROGRESS_BAR_WIDTH: usize = 20;
 
 #[derive(Debug, Clone, Copy)]
 pub struct UiRects {
@@ -19,8 +25,11 @@ pub struct UiRects {
     pub details: Rect,
     pub revisions: Rect,
     pub bookmarks: Rect,
+    pub branches: Rect,
+    pub tags: Rect,
     pub shelves: Rect,
     pub conflicts: Rect,
+    pub remote: Rect,
     pub log: Rect,
 }
 
@@ -33,8 +42,11 @@ impl Default for UiRects {
             details: Rect::new(0, 0, 0, 0),
             revisions: Rect::new(0, 0, 0, 0),
             bookmarks: Rect::new(0, 0, 0, 0),
+            branches: Rect::new(0, 0, 0, 0),
+            tags: Rect::new(0, 0, 0, 0),
             shelves: Rect::new(0, 0, 0, 0),
             conflicts: Rect::new(0, 0, 0, 0),
+            remote: Rect::new(0, 0, 0, 0),
             log: Rect::new(0, 0, 0, 0),
         }
     }
@@ -46,8 +58,11 @@ impl UiRects {
             FocusPanel::Files => self.files,
             FocusPanel::Revisions => self.revisions,
             FocusPanel::Bookmarks => self.bookmarks,
+            FocusPanel::Branches => self.branches,
+            FocusPanel::Tags => self.tags,
             FocusPanel::Shelves => self.shelves,
             FocusPanel::Conflicts => self.conflicts,
+            FocusPanel::Remote => self.remote,
             FocusPanel::Log => self.log,
         }
     }
@@ -83,9 +98,22 @@ pub fn compute_ui_rects(root: Rect) -> UiRects {
         ])
         .split(cols[1]);
 
+    let refs = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(34),
+            Constraint::Percentage(33),
+            Constraint::Percentage(33),
+        ])
+        .split(right[1]);
+
     let shelf_conflict = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .constraints([
+            Constraint::Percentage(34),
+            Constraint::Percentage(33),
+            Constraint::Percentage(33),
+        ])
         .split(right[2]);
 
     UiRects {
@@ -94,9 +122,12 @@ pub fn compute_ui_rects(root: Rect) -> UiRects {
         files: left[0],
         details: left[1],
         revisions: right[0],
-        bookmarks: right[1],
+        bookmarks: refs[0],
+        branches: refs[1],
+        tags: refs[2],
         shelves: shelf_conflict[0],
         conflicts: shelf_conflict[1],
+        remote: shelf_conflict[2],
         log: right[3],
     }
 }
@@ -109,20 +140,50 @@ pub fn render(frame: &mut Frame<'_>, app: &App, rects: &UiRects) {
     render_footer(frame, rects.footer, app);
 
     if let Some(confirm) = &app.confirmation {
-        let area = centered_rect(70, 25, root);
+        let height = if confirm.message.contains('\n') {
+            60
+        } else {
+            25
+        };
+        let area = centered_rect(70, height, root);
         frame.render_widget(Clear, area);
-        let text = Text::from(vec![
-            Line::from(confirm.message.clone()),
+        let mut lines = confirm
+            .message
+            .lines()
+            .map(|line| Line::from(line.to_string()))
+            .collect::<Vec<_>>();
+        lines.extend([
             Line::from(""),
             Line::from(format!("Command: {}", confirm.action.command_preview())),
             Line::from(""),
-            Line::from("Press y/Enter to confirm, n/Esc to cancel."),
         ]);
+        match &confirm.typed {
+            Some(typed) => lines.extend([
+                Line::from(format!("> {}_", typed.value)),
+                Line::from(""),
+                Line::from(format!(
+                    "Type `{}` and press Enter to confirm, Esc to cancel.",
+                    typed.expected
+                )),
+            ]),
+            None => lines.push(Line::from("Press y/Enter to confirm, n/Esc to cancel.")),
+        }
+        let text = Text::from(lines);
         let modal = Paragraph::new(text).block(
             Block::default()
-                .title("Confirm Action")
+                .title(if confirm.typed.is_some() {
+                    "Typed Confirmation"
+                } else if confirm.follow_up.is_some() {
+                    "Confirm Action (1/2)"
+                } else {
+                    "Confirm Action"
+                })
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Yellow)),
+                .border_style(Style::default().fg(if confirm.typed.is_some() {
+                    Color::Red
+                } else {
+                    Color::Yellow
+                })),
         );
         frame.render_widget(modal, area);
     }
@@ -187,6 +248,170 @@ pub fn render(frame: &mut Frame<'_>, app: &App, rects: &UiRects) {
         );
         frame.render_widget(modal, area);
     }
+
+    if let Some(picker) = &app.path_picker {
+        let area = centered_rect(70, 45, root);
+        frame.render_widget(Clear, area);
+        let title = match picker.direction {
+            RemoteDirection::Pull => "Pull From",
+            RemoteDirection::Push => "Push To",
+        };
+        let mut lines = app
+            .snapshot
+            .paths
+            .iter()
+            .enumerate()
+            .map(|(idx, path)| {
+                let marker = if idx == picker.selected { ">" } else { " " };
+                format!("{marker} {} = {}", path.name, path.url)
+            })
+            .collect::<Vec<_>>();
+        lines.push("".to_string());
+        lines.push("Enter to select (remembered for this repo), Esc to cancel.".to_string());
+        let text = Text::from(lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        let modal = Paragraph::new(text).block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Green)),
+        );
+        frame.render_widget(modal, area);
+    }
+
+    if let Some(picker) = &app.backup_picker {
+        let area = centered_rect(80, 50, root);
+        frame.render_widget(Clear, area);
+        let mut lines = picker
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let marker = if idx == picker.selected { ">" } else { " " };
+                let name = entry
+                    .file
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let revset = if entry.revset.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", entry.revset)
+                };
+                format!(
+                    "{marker} {}  {}{revset}  {name}",
+                    entry.created_at, entry.operation
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.push("".to_string());
+        lines.push("Enter to restore (hg unbundle, confirm), Esc to close.".to_string());
+        let text = Text::from(lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        let modal = Paragraph::new(text).block(
+            Block::default()
+                .title("Backups")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Green)),
+        );
+        frame.render_widget(modal, area);
+    }
+
+    if let Some(prompt) = &app.lock_wait {
+        let area = centered_rect(70, 40, root);
+        frame.render_widget(Clear, area);
+        let holder = prompt
+            .wait
+            .holder
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        let lines = vec![
+            format!("{} is waiting for a lock.", prompt.label),
+            "".to_string(),
+            prompt.wait.message.clone(),
+            format!("Holder: {holder}"),
+            "".to_string(),
+            "w/Enter keep waiting | c/Esc cancel the command | b break the lock (hg debuglocks)"
+                .to_string(),
+        ];
+        let text = Text::from(lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        let modal = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title("Repository Locked")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+        frame.render_widget(modal, area);
+    }
+
+    if let Some(prompt) = &app.cancel_prompt {
+        let area = centered_rect(70, 40, root);
+        frame.render_widget(Clear, area);
+        let lines = vec![
+            format!("{} is still changing the repository.", prompt.label),
+            "".to_string(),
+            "Killing it can stop hg in the middle of a transaction: the repository may need `hg recover`, and a rebase or histedit may be left interrupted (`--abort`).".to_string(),
+            "".to_string(),
+            "y/Enter kill it | n/Esc keep it running".to_string(),
+        ];
+        let text = Text::from(lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        let modal = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title("Kill Running Write")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red)),
+        );
+        frame.render_widget(modal, area);
+    }
+
+    if let Some(plan) = &app.histedit_plan {
+        let area = centered_rect(80, 60, root);
+        frame.render_widget(Clear, area);
+        let mut lines = vec![
+            Line::from(format!(
+                "Plan from revision {} to working parent (oldest first):",
+                plan.base_rev
+            )),
+            Line::from(""),
+        ];
+        lines.extend(
+            plan.rows
+                .iter()
+                .enumerate()
+                .map(|(idx, step)| histedit_plan_line(step, idx == plan.selected)),
+        );
+        lines.push(Line::from(""));
+        lines.push(Line::from(
+            "j/k move | J/K reorder | p pick e edit f fold r roll d drop m mess b base",
+        ));
+        lines.push(Line::from("Enter to run histedit, Esc to cancel."));
+        let modal = Paragraph::new(Text::from(lines)).block(
+            Block::default()
+                .title("Histedit Plan")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta)),
+        );
+        frame.render_widget(modal, area);
+    }
+}
+
+fn histedit_plan_line(step: &HisteditPlanStep, selected: bool) -> Line<'static> {
+    let prefix = if selected { "> " } else { "  " };
+    let short = step.node.chars().take(12).collect::<String>();
+    let desc = step.desc.lines().next().unwrap_or("");
+    let text = format!(
+        "{prefix}{:<5} @{} {} {}",
+        step.action.as_str(),
+        step.rev,
+        short,
+        desc
+    );
+    let style = if selected {
+        selected_row_style()
+    } else if step.action == HisteditAction::Drop {
+        Style::default().fg(Color::DarkGray)
+    } else {
+        Style::default()
+    };
+    Line::styled(text, style)
 }
 
 fn render_header(frame: &mut Frame<'_>, area: Rect, app: &App) {
@@ -197,16 +422,33 @@ fn render_header(frame: &mut Frame<'_>, area: Rect, app: &App) {
         .map(short_path)
         .unwrap_or_else(|| "(not in hg repo)".to_string());
     let branch = app.snapshot.branch.as_deref().unwrap_or("unknown branch");
-    let title = format!(
+    let branch = match app.snapshot.active_topic() {
+        Some(topic) => format!("{branch} | topic: {topic}"),
+        None => branch.to_string(),
+    };
+    let mut title = format!(
         "easyHg | {} | branch: {} | {}",
         repo, branch, app.snapshot.capabilities.version
     );
+    if let Some(counts) = ahead_behind(app) {
+        title.push_str(&format!(" | {counts}"));
+    }
 
     let text = Text::from(vec![Line::from(title), Line::from(app.status_line.clone())]);
     let block = Paragraph::new(text).block(Block::default().borders(Borders::ALL));
     frame.render_widget(block, area);
 }
 
+fn ahead_behind(app: &App) -> Option<String> {
+    let ahead = app.outgoing_changes.as_ref().map(Vec::len);
+    let behind = app.incoming_changes.as_ref().map(Vec::len);
+    if ahead.is_none() && behind.is_none() {
+        return None;
+    }
+    let count = |value: Option<usize>| value.map_or("?".to_string(), |n| n.to_string());
+    Some(format!("ahead {} / behind {}", count(ahead), count(behind)))
+}
+
 fn render_body(frame: &mut Frame<'_>, rects: &UiRects, app: &App) {
     render_files(frame, rects.files, app, app.focus == FocusPanel::Files);
     render_details(frame, rects.details, app);
@@ -222,6 +464,13 @@ fn render_body(frame: &mut Frame<'_>, rects: &UiRects, app: &App) {
         app,
         app.focus == FocusPanel::Bookmarks,
     );
+    render_branches(
+        frame,
+        rects.branches,
+        app,
+        app.focus == FocusPanel::Branches,
+    );
+    render_tags(frame, rects.tags, app, app.focus == FocusPanel::Tags);
     render_shelves(frame, rects.shelves, app, app.focus == FocusPanel::Shelves);
     render_conflicts(
         frame,
@@ -229,6 +478,7 @@ fn render_body(frame: &mut Frame<'_>, rects: &UiRects, app: &App) {
         app,
         app.focus == FocusPanel::Conflicts,
     );
+    render_remote(frame, rects.remote, app, app.focus == FocusPanel::Remote);
     render_log(frame, rects.log, app, app.focus == FocusPanel::Log);
 }
 
@@ -252,6 +502,21 @@ fn render_footer(frame: &mut Frame<'_>, area: Rect, app: &App) {
             app.key_for_action(ActionId::CommitInteractive)
         ),
         format!("{} bookmark", app.key_for_action(ActionId::Bookmark)),
+        format!("{} branch", app.key_for_action(ActionId::BranchCreate)),
+        format!(
+            "{}/{}/{} tag/local/remove",
+            app.key_for_action(ActionId::TagCreate),
+            app.key_for_action(ActionId::TagCreateLocal),
+            app.key_for_action(ActionId::TagRemove)
+        ),
+        format!("{} jump", app.key_for_action(ActionId::JumpToRevision)),
+        format!(
+            "{}/{}/{}/{} bm delete/rename/move/deactivate",
+            app.key_for_action(ActionId::BookmarkDelete),
+            app.key_for_action(ActionId::BookmarkRename),
+            app.key_for_action(ActionId::BookmarkMove),
+            app.key_for_action(ActionId::BookmarkDeactivate)
+        ),
         format!("{} update", app.key_for_action(ActionId::UpdateSelected)),
         format!("{} push", app.key_for_action(ActionId::Push)),
         format!("{} pull", app.key_for_action(ActionId::Pull)),
@@ -265,15 +530,37 @@ fn render_footer(frame: &mut Frame<'_>, area: Rect, app: &App) {
             app.key_for_action(ActionId::ResolveMark),
             app.key_for_action(ActionId::ResolveUnmark)
         ),
+        format!("{} backout", app.key_for_action(ActionId::BackoutSelected)),
+        format!(
+            "{}/{}/{} phase",
+            app.key_for_action(ActionId::PhaseSecret),
+            app.key_for_action(ActionId::PhaseDraft),
+            app.key_for_action(ActionId::PhasePublic)
+        ),
         format!("{} refresh", app.key_for_action(ActionId::RefreshSnapshot)),
         format!("{} help->log", app.key_for_action(ActionId::Help)),
     ];
+    if let Some(jobs) = jobs_summary(app) {
+        keys.insert(0, jobs);
+    }
     if !app.config.custom_commands.is_empty() {
         keys.push(format!(
             "{} commands",
             app.key_for_action(ActionId::OpenCustomCommands)
         ));
     }
+    if app.dry_run_next {
+        keys.push(format!(
+            "{} dry-run armed",
+            app.key_for_action(ActionId::DryRunNext)
+        ));
+    }
+    if app.close_branch_on_commit {
+        keys.push(format!(
+            "{} close-branch armed",
+            app.key_for_action(ActionId::ToggleCloseBranch)
+        ));
+    }
     if app.selected_file_commit_count() > 0 {
         keys.push(format!("{} picked", app.selected_file_commit_count()));
     }
@@ -297,10 +584,89 @@ fn render_footer(frame: &mut Frame<'_>, area: Rect, app: &App) {
             app.key_for_action(ActionId::HisteditSelected)
         ));
     }
+    if app.snapshot.capabilities.has_topic {
+        keys.push(format!(
+            "{}/{}/{} topic new/switch/clear",
+            app.key_for_action(ActionId::TopicCreate),
+            app.key_for_action(ActionId::TopicSwitch),
+            app.key_for_action(ActionId::TopicClear)
+        ));
+        keys.push(format!(
+            "{} topics",
+            app.key_for_action(ActionId::TopicsList)
+        ));
+    }
+    if app.snapshot.capabilities.has_evolve {
+        keys.push(format!(
+            "{}/{} prev/next",
+            app.key_for_action(ActionId::EvolvePrev),
+            app.key_for_action(ActionId::EvolveNext)
+        ));
+        keys.push(format!(
+            "{}/{} evolve/all",
+            app.key_for_action(ActionId::Evolve),
+            app.key_for_action(ActionId::EvolveAll)
+        ));
+        keys.push(format!(
+            "{} prune",
+            app.key_for_action(ActionId::PruneSelected)
+        ));
+        keys.push(format!(
+            "{} obslog",
+            app.key_for_action(ActionId::ObslogSelected)
+        ));
+    }
     let line = Paragraph::new(keys.join(" | ")).block(Block::default().borders(Borders::TOP));
     frame.render_widget(line, area);
 }
 
+fn jobs_summary(app: &App) -> Option<String> {
+    if app.jobs.is_empty() {
+        return None;
+    }
+    let jobs = app
+        .jobs
+        .jobs()
+        .iter()
+        .map(|job| {
+            if job.is_queued() {
+                format!("{} (queued)", job.label)
+            } else {
+                match &job.progress {
+                    Some(progress) => format!(
+                        "{} {}s {}",
+                        job.label,
+                        job.elapsed().as_secs(),
+                        progress_bar(progress, PROGRESS_BAR_WIDTH)
+                    ),
+                    None => format!("{} {}s", job.label, job.elapsed().as_secs()),
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "jobs: {jobs} ({} cancel)",
+        app.key_for_action(ActionId::CancelJob)
+    ))
+}
+
+fn progress_bar(progress: &HgProgress, width: usize) -> String {
+    match (progress.fraction(), progress.total) {
+        (Some(fraction), Some(total)) => {
+            let filled = ((fraction * width as f64).round() as usize).min(width);
+            format!(
+                "[{}{}] {} {}/{total}",
+                "#".repeat(filled),
+                "-".repeat(width - filled),
+                progress.topic,
+                progress.pos
+            )
+        }
+        _ => format!("{} {}", progress.topic, progress.pos),
+    }
+}
+
 fn render_files(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool) {
     let items: Vec<ListItem<'_>> = if app.snapshot.files.is_empty() {
         vec![ListItem::new("(clean working directory)")]
@@ -344,8 +710,16 @@ fn render_revisions(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool)
             .revisions
             .iter()
             .enumerate()
-            .map(|(idx, rev)| revision_item(rev, idx == app.rev_idx))
-            .map(ListItem::new)
+            .map(|(idx, rev)| {
+                let item = ListItem::new(revision_item(rev, idx == app.rev_idx));
+                if rev.phase == "secret" {
+                    item.style(secret_revision_style())
+                } else if app.snapshot.branch_heads.contains(&rev.rev) {
+                    item.style(branch_head_style())
+                } else {
+                    item
+                }
+            })
             .collect()
     };
 
@@ -391,6 +765,65 @@ fn render_bookmarks(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool)
     frame.render_stateful_widget(list, area, &mut state);
 }
 
+fn render_branches(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool) {
+    let current = app.snapshot.branch.as_deref();
+    let items: Vec<ListItem<'_>> = if app.snapshot.branches.is_empty() {
+        vec![ListItem::new("(no branches)")]
+    } else {
+        app.snapshot
+            .branches
+            .iter()
+            .enumerate()
+            .map(|(idx, branch)| {
+                let item = ListItem::new(branch_item(
+                    branch,
+                    idx == app.branches_idx,
+                    current == Some(branch.name.as_str()),
+                ));
+                if branch.closed {
+                    item.style(Style::default().fg(Color::DarkGray))
+                } else {
+                    item
+                }
+            })
+            .collect()
+    };
+
+    let mut state = ListState::default();
+    if !app.snapshot.branches.is_empty() {
+        *state.offset_mut() = app.branches_offset;
+        state.select(Some(app.branches_idx));
+    }
+    let list = List::new(items)
+        .block(panel_block("Branches", focused))
+        .highlight_style(selected_row_style());
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_tags(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool) {
+    let items: Vec<ListItem<'_>> = if app.snapshot.tags.is_empty() {
+        vec![ListItem::new("(no tags)")]
+    } else {
+        app.snapshot
+            .tags
+            .iter()
+            .enumerate()
+            .map(|(idx, tag)| tag_item(tag, idx == app.tags_idx))
+            .map(ListItem::new)
+            .collect()
+    };
+
+    let mut state = ListState::default();
+    if !app.snapshot.tags.is_empty() {
+        *state.offset_mut() = app.tags_offset;
+        state.select(Some(app.tags_idx));
+    }
+    let list = List::new(items)
+        .block(panel_block("Tags", focused))
+        .highlight_style(selected_row_style());
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
 fn render_shelves(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool) {
     let items: Vec<ListItem<'_>> = if app.snapshot.shelves.is_empty() {
         vec![ListItem::new("(no shelves)")]
@@ -438,6 +871,41 @@ fn render_conflicts(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool)
     frame.render_stateful_widget(list, area, &mut state);
 }
 
+fn render_remote(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool) {
+    let revisions = app.remote_revisions();
+    let label = remote_direction_label(app.remote_view);
+    let loaded = match app.remote_view {
+        crate::remotes::RemoteDirection::Pull => app.incoming_changes.is_some(),
+        crate::remotes::RemoteDirection::Push => app.outgoing_changes.is_some(),
+    };
+    let items: Vec<ListItem<'_>> = if !loaded {
+        vec![ListItem::new("(run incoming/outgoing)")]
+    } else if revisions.is_empty() {
+        vec![ListItem::new(format!("(no {label} changesets)"))]
+    } else {
+        revisions
+            .iter()
+            .enumerate()
+            .map(|(idx, rev)| remote_item(rev, idx == app.remote_idx))
+            .map(ListItem::new)
+            .collect()
+    };
+    let mut state = ListState::default();
+    if !revisions.is_empty() {
+        *state.offset_mut() = app.remote_offset;
+        state.select(Some(app.remote_idx));
+    }
+    let title = if loaded {
+        format!("Remote: {label} ({})", revisions.len())
+    } else {
+        "Remote".to_string()
+    };
+    let list = List::new(items)
+        .block(panel_block(&title, focused))
+        .highlight_style(selected_row_style());
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
 fn render_details(frame: &mut Frame<'_>, area: Rect, app: &App) {
     let detail_scroll = app.details_scroll.min(app.max_detail_scroll());
     let detail = Paragraph::new(styled_detail_text(app.detail_text.as_str()))
@@ -475,24 +943,56 @@ fn detail_line_style(line: &str) -> Style {
 }
 
 fn render_log(frame: &mut Frame<'_>, area: Rect, app: &App, focused: bool) {
-    let text = if app.log_lines.is_empty() {
-        "(command log is empty)".to_string()
+    let entries = app.visible_log_entries();
+    let items: Vec<ListItem<'_>> = if entries.is_empty() && app.log_failures_only {
+        vec![ListItem::new("(no failed commands)")]
+    } else if entries.is_empty() {
+        vec![ListItem::new("(command log is empty)")]
     } else {
-        app.log_lines.join("\n")
+        entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| log_item(entry, focused && idx == app.log_idx))
+            .map(ListItem::new)
+            .collect()
     };
-    let paragraph = Paragraph::new(text)
-        .block(panel_block("Command Log", focused))
-        .wrap(Wrap { trim: false })
-        .scroll((app.log_idx as u16, 0));
-    frame.render_widget(paragraph, area);
-
-    if !app.log_lines.is_empty() {
-        let mut scrollbar_state = ScrollbarState::new(app.log_lines.len()).position(app.log_idx);
+    let mut state = ListState::default();
+    if focused && !entries.is_empty() {
+        state.select(Some(app.log_idx));
+    } else {
+        *state.offset_mut() = app.log_idx;
+    }
+    let list = List::new(items)
+        .block(panel_block(
+            if app.log_failures_only {
+                "Command Log (failures)"
+            } else {
+                "Command Log"
+            },
+            focused,
+        ))
+        .highlight_style(selected_row_style());
+    frame.render_stateful_widget(list, area, &mut state);
+
+    if !entries.is_empty() {
+        let mut scrollbar_state = ScrollbarState::new(entries.len()).position(app.log_idx);
         let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight);
         frame.render_stateful_widget(scrollbar, area, &mut scrollbar_state);
     }
 }
 
+fn log_item(entry: &LogEntry, selected: bool) -> String {
+    let prefix = if selected { "> " } else { "  " };
+    let first = entry.text.lines().next().unwrap_or("");
+    if entry.is_failure() {
+        format!("{prefix}! {first}")
+    } else if entry.record.is_some() || entry.text.contains('\n') {
+        format!("{prefix}{first} …")
+    } else {
+        format!("{prefix}{first}")
+    }
+}
+
 fn panel_block(title: &str, focused: bool) -> Block<'_> {
     let mut block = Block::default().title(title).borders(Borders::ALL);
     if focused {
@@ -509,9 +1009,13 @@ fn file_item(file: &FileChange, selected: bool, commit_selected: bool) -> String
 
 fn revision_item(rev: &Revision, selected: bool) -> String {
     let short = rev.node.chars().take(10).collect::<String>();
+    let short = match rev.topic.as_deref() {
+        Some(topic) => format!("{short} {{{topic}}}"),
+        None => short,
+    };
     let desc = rev.desc.lines().next().unwrap_or("").to_string();
     let prefix = if selected { "> " } else { "  " };
-    match rev.graph_prefix.as_deref() {
+    let line = match rev.graph_prefix.as_deref() {
         Some(graph) if !graph.is_empty() => {
             format!(
                 "{prefix}{graph} @{} {} {} ({})",
@@ -519,9 +1023,32 @@ fn revision_item(rev: &Revision, selected: bool) -> String {
             )
         }
         _ => format!("{prefix}@{} {} {} ({})", rev.rev, short, desc, rev.user),
+    };
+    let mut flags = Vec::new();
+    if rev.phase == "secret" {
+        flags.push("secret");
+    }
+    if rev.obsolete {
+        flags.push("obsolete");
+    }
+    flags.extend(rev.instabilities.iter().map(String::as_str));
+    if flags.is_empty() {
+        line
+    } else {
+        format!("{line} [{}]", flags.join(", "))
     }
 }
 
+fn branch_head_style() -> Style {
+    Style::default().fg(Color::Green)
+}
+
+fn secret_revision_style() -> Style {
+    Style::default()
+        .fg(Color::Magenta)
+        .add_modifier(Modifier::ITALIC)
+}
+
 fn commit_highlight_style() -> Style {
     selected_row_style()
 }
@@ -545,6 +1072,32 @@ fn bookmark_item(bookmark: &Bookmark, selected: bool) -> String {
     )
 }
 
+fn branch_item(branch: &Branch, selected: bool, current: bool) -> String {
+    let prefix = if selected { "> " } else { "  " };
+    let marker = if current { "*" } else { " " };
+    let state = if branch.closed {
+        " (closed)"
+    } else if !branch.active {
+        " (inactive)"
+    } else {
+        ""
+    };
+    format!("{prefix}{marker} {} @{}{state}", branch.name, branch.rev)
+}
+
+fn remote_item(rev: &Revision, selected: bool) -> String {
+    let prefix = if selected { "> " } else { "  " };
+    let short = rev.node.chars().take(10).collect::<String>();
+    let desc = rev.desc.lines().next().unwrap_or("");
+    format!("{prefix}{short} {desc} ({})", rev.user)
+}
+
+fn tag_item(tag: &Tag, selected: bool) -> String {
+    let prefix = if selected { "> " } else { "  " };
+    let local = if tag.local { " (local)" } else { "" };
+    format!("{prefix}{} @{}{local}", tag.name, tag.rev)
+}
+
 fn shelf_item(shelf: &Shelf, selected: bool) -> String {
     let prefix = if selected { "> " } else { "  " };
     if shelf.description.is_empty() {
@@ -599,12 +1152,13 @@ fn short_path(path: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::CommandRecord;
     use ratatui::style::Modifier;
 
     #[test]
     fn file_item_selected_prefix() {
         let file = FileChange {
-            path: "src/main.rs".to_string(),
+            path: "src/main.rs".into(),
             status: crate::domain::FileStatus::Modified,
         };
         assert!(file_item(&file, true, true).starts_with("> "));
@@ -626,6 +1180,97 @@ mod tests {
         assert!(line.contains("main @3"));
     }
 
+    #[test]
+    fn branch_item_marks_current_and_state() {
+        let branch = Branch {
+            name: "stable".to_string(),
+            rev: 12,
+            node: "abc".to_string(),
+            active: false,
+            closed: true,
+        };
+        assert_eq!(branch_item(&branch, true, false), ">   stable @12 (closed)");
+        let active = Branch {
+            closed: false,
+            active: true,
+            ..branch
+        };
+        assert_eq!(branch_item(&active, false, true), "  * stable @12");
+    }
+
+    #[test]
+    fn remote_item_uses_node_instead_of_local_rev() {
+        let revision = Revision {
+            rev: 12,
+            node: "abcdef0123456789".to_string(),
+            desc: "upstream change\nbody".to_string(),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "draft".to_string(),
+            tags: Vec::new(),
+            bookmarks: Vec::new(),
+            date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
+            graph_prefix: None,
+        };
+        assert_eq!(
+            remote_item(&revision, true),
+            "> abcdef0123 upstream change (u)"
+        );
+    }
+
+    #[test]
+    fn progress_bar_renders_fraction_or_bare_count() {
+        let progress = HgProgress {
+            topic: "changesets".to_string(),
+            pos: 5,
+            total: Some(10),
+        };
+        assert_eq!(progress_bar(&progress, 10), "[#####-----] changesets 5/10");
+        let unbounded = HgProgress {
+            topic: "searching".to_string(),
+            pos: 3,
+            total: None,
+        };
+        assert_eq!(progress_bar(&unbounded, 10), "searching 3");
+    }
+
+    #[test]
+    fn log_item_marks_records_and_failures() {
+        let entry = LogEntry {
+            text: "[10:00:00] OK: hg pull".to_string(),
+            record: None,
+        };
+        assert_eq!(log_item(&entry, true), "> [10:00:00] OK: hg pull");
+        let started_at = chrono::Local::now();
+        let failed = LogEntry {
+            text: "[10:00:01] FAILED: hg push".to_string(),
+            record: Some(CommandRecord {
+                command: "hg push".to_string(),
+                started_at,
+                finished_at: started_at,
+                exit_code: Some(1),
+                success: false,
+                stdout: String::new(),
+                stderr: "abort".to_string(),
+            }),
+        };
+        assert_eq!(log_item(&failed, false), "  ! [10:00:01] FAILED: hg push");
+    }
+
+    #[test]
+    fn tag_item_marks_local_tags() {
+        let tag = Tag {
+            name: "wip".to_string(),
+            rev: 4,
+            node: "abc".to_string(),
+            local: true,
+        };
+        assert_eq!(tag_item(&tag, true), "> wip @4 (local)");
+    }
+
     #[test]
     fn shelf_item_prefix_and_text() {
         let shelf = Shelf {
@@ -641,7 +1286,7 @@ mod tests {
     fn conflict_item_keeps_status_marker() {
         let conflict = ConflictEntry {
             resolved: false,
-            path: "src/lib.rs".to_string(),
+            path: "src/lib.rs".into(),
         };
         assert_eq!(conflict_item(&conflict, true), "> U src/lib.rs");
     }
@@ -658,11 +1303,101 @@ mod tests {
             tags: Vec::new(),
             bookmarks: Vec::new(),
             date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
             graph_prefix: Some("| o".to_string()),
         };
         assert!(revision_item(&revision, true).starts_with("> | o @9 "));
     }
 
+    #[test]
+    fn revision_item_appends_evolution_flags() {
+        let revision = Revision {
+            rev: 9,
+            node: "abcdef0123456789".to_string(),
+            desc: "message".to_string(),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "draft".to_string(),
+            tags: Vec::new(),
+            bookmarks: Vec::new(),
+            date_unix_secs: 0,
+            obsolete: true,
+            instabilities: vec!["orphan".to_string()],
+            topic: None,
+            graph_prefix: None,
+        };
+        assert_eq!(
+            revision_item(&revision, false),
+            "  @9 abcdef0123 message (u) [obsolete, orphan]"
+        );
+    }
+
+    #[test]
+    fn revision_item_flags_secret_phase() {
+        let revision = Revision {
+            rev: 9,
+            node: "abcdef0123456789".to_string(),
+            desc: "message".to_string(),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "secret".to_string(),
+            tags: Vec::new(),
+            bookmarks: Vec::new(),
+            date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: None,
+            graph_prefix: None,
+        };
+        assert_eq!(
+            revision_item(&revision, false),
+            "  @9 abcdef0123 message (u) [secret]"
+        );
+    }
+
+    #[test]
+    fn revision_item_shows_topic_after_node() {
+        let revision = Revision {
+            rev: 9,
+            node: "abcdef0123456789".to_string(),
+            desc: "message".to_string(),
+            user: "u".to_string(),
+            branch: "default".to_string(),
+            phase: "draft".to_string(),
+            tags: Vec::new(),
+            bookmarks: Vec::new(),
+            date_unix_secs: 0,
+            obsolete: false,
+            instabilities: Vec::new(),
+            topic: Some("feature-x".to_string()),
+            graph_prefix: None,
+        };
+        assert_eq!(
+            revision_item(&revision, false),
+            "  @9 abcdef0123 {feature-x} message (u)"
+        );
+    }
+
+    #[test]
+    fn histedit_plan_line_pads_verb_and_dims_dropped_rows() {
+        let step = HisteditPlanStep {
+            action: HisteditAction::Drop,
+            rev: 4,
+            node: "abcdef0123456789".to_string(),
+            desc: "wip\nbody".to_string(),
+        };
+        let line = histedit_plan_line(&step, false);
+        assert_eq!(line.to_string(), "  drop  @4 abcdef012345 wip");
+        assert_eq!(line.style.fg, Some(Color::DarkGray));
+        assert!(
+            histedit_plan_line(&step, true)
+                .to_string()
+                .starts_with("> ")
+        );
+    }
+
     #[test]
     fn selected_row_style_has_high_contrast_defaults() {
         let style = selected_row_style();
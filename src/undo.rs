@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::domain::RepoSnapshot;
+use crate::hg::{HgAction, UndoStep};
+
+pub const UNDO_STACK_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoKind {
+    Commit,
+    Shelve(String),
+    Update,
+    Bookmarks,
+    Rewrite,
+}
+
+impl UndoKind {
+    pub fn for_action(action: &HgAction) -> Option<Self> {
+        match action {
+            HgAction::Commit { .. } => Some(Self::Commit),
+            HgAction::ShelveCreate { name, .. } => Some(Self::Shelve(name.clone())),
+            HgAction::UpdateToRevision { .. }
+            | HgAction::UpdateToBookmark { .. }
+            | HgAction::UpdateToBranch { .. }
+            | HgAction::UpdateToTopic { .. }
+            | HgAction::Prev
+            | HgAction::Next => Some(Self::Update),
+            HgAction::BookmarkCreate { .. }
+            | HgAction::BookmarkDelete { .. }
+            | HgAction::BookmarkRename { .. }
+            | HgAction::BookmarkMove { .. }
+            | HgAction::BookmarkDeactivate => Some(Self::Bookmarks),
+            HgAction::Pull { .. }
+            | HgAction::RebaseSourceDest { .. }
+            | HgAction::RebaseContinue
+            | HgAction::HisteditPlan { .. }
+            | HgAction::HisteditContinue
+            | HgAction::Backout { .. }
+            | HgAction::Evolve { .. }
+            | HgAction::Prune { .. } => Some(Self::Rewrite),
+            _ => None,
+        }
+    }
+}
+
+// The repository state undo cares about: working parent, bookmark positions
+// and the visible changesets (node -> rev).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoState {
+    pub parent: Option<String>,
+    pub active_bookmark: Option<String>,
+    pub bookmarks: BTreeMap<String, String>,
+    pub revisions: BTreeMap<String, i64>,
+}
+
+impl RepoState {
+    pub fn capture(snapshot: &RepoSnapshot) -> Self {
+        let parent = snapshot.working_parent_rev.and_then(|parent| {
+            snapshot
+                .revisions
+                .iter()
+                .find(|rev| rev.rev == parent)
+                .map(|rev| rev.node.clone())
+        });
+        Self {
+            parent,
+            active_bookmark: snapshot
+                .bookmarks
+                .iter()
+                .find(|bookmark| bookmark.active)
+                .map(|bookmark| bookmark.name.clone()),
+            bookmarks: snapshot
+                .bookmarks
+                .iter()
+                .map(|bookmark| (bookmark.name.clone(), bookmark.node.clone()))
+                .collect(),
+            revisions: snapshot
+                .revisions
+                .iter()
+                .map(|rev| (rev.node.clone(), rev.rev))
+                .collect(),
+        }
+    }
+
+    // Revision numbers are ignored: restoring a bundle renumbers changesets
+    // without changing what is in the repository.
+    fn same_changesets(&self, other: &RepoState) -> bool {
+        self.parent == other.parent
+            && self.active_bookmark == other.active_bookmark
+            && self.bookmarks == other.bookmarks
+            && self.revisions.keys().eq(other.revisions.keys())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UndoCapture {
+    pub label: String,
+    pub kind: UndoKind,
+    pub before: RepoState,
+    pub backups: BTreeSet<PathBuf>,
+}
+
+impl UndoCapture {
+    pub fn from_state(label: String, kind: UndoKind, repo_root: &Path, before: RepoState) -> Self {
+        Self {
+            label,
+            kind,
+            before,
+            backups: backup_bundles(repo_root),
+        }
+    }
+
+    pub fn finish(self, snapshot: &RepoSnapshot) -> Option<UndoEntry> {
+        let backups = snapshot
+            .repo_root
+            .as_deref()
+            .map(|root| backup_bundles(Path::new(root)))
+            .unwrap_or_default();
+        self.finish_at(RepoState::capture(snapshot), &backups)
+    }
+
+    // Ends the capture at the state the next action started from.
+    pub fn finish_before(self, next: &UndoCapture) -> Option<UndoEntry> {
+        self.finish_at(next.before.clone(), &next.backups)
+    }
+
+    fn finish_at(self, after: RepoState, backups: &BTreeSet<PathBuf>) -> Option<UndoEntry> {
+        let new_backups = backups
+            .iter()
+            .filter(|bundle| !self.backups.contains(*bundle))
+            .cloned()
+            .collect::<Vec<_>>();
+        let unchanged = after == self.before && new_backups.is_empty();
+        if unchanged && !matches!(self.kind, UndoKind::Shelve(_)) {
+            return None;
+        }
+        Some(UndoEntry {
+            label: self.label,
+            kind: self.kind,
+            before: self.before,
+            after,
+            new_backups,
+            recorded_at: Local::now(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    pub label: String,
+    pub kind: UndoKind,
+    pub before: RepoState,
+    pub after: RepoState,
+    pub new_backups: Vec<PathBuf>,
+    pub recorded_at: DateTime<Local>,
+}
+
+impl UndoEntry {
+    // Without the undo extension the plan is rebuilt from the recorded
+    // states: backups written by the action are unbundled, changesets it
+    // created are stripped (strip keeps its own backup), then bookmarks and
+    // the working parent are moved back. Both plans assume the repository is
+    // still where the action left it; a shelf is restored by name, so it
+    // can be unshelved later.
+    pub fn plan(
+        &self,
+        current: &RepoState,
+        has_undo_extension: bool,
+    ) -> Result<Vec<UndoStep>, String> {
+        if let UndoKind::Shelve(name) = &self.kind {
+            return Ok(vec![step(
+                ["unshelve", "--name", name.as_str()],
+                format!("Unshelve '{name}' back into the working directory"),
+            )]);
+        }
+        if !self.after.same_changesets(current) {
+            return Err(
+                "the repository changed since it ran (changesets, bookmarks or the working parent differ), so undoing it could revert later work".to_string(),
+            );
+        }
+        if has_undo_extension {
+            return Ok(vec![step(
+                ["undo"],
+                "Run hg undo (undo extension) to revert the last repository change",
+            )]);
+        }
+
+        let before_max = self.before.revisions.values().copied().max().unwrap_or(-1);
+        let after_min = self.after.revisions.values().copied().min();
+        let created = self
+            .after
+            .revisions
+            .iter()
+            .filter(|(node, rev)| **rev > before_max && !self.before.revisions.contains_key(*node))
+            .map(|(node, _)| node.as_str())
+            .collect::<Vec<_>>();
+        let removed = self
+            .before
+            .revisions
+            .iter()
+            .filter(|(node, rev)| {
+                after_min.is_none_or(|min| **rev >= min)
+                    && !self.after.revisions.contains_key(*node)
+            })
+            .count();
+        if removed > 0 && self.new_backups.is_empty() {
+            return Err(format!(
+                "{removed} changeset(s) were hidden by the action without a backup bundle (obsolescence markers). Enable the undo extension to revert it with hg undo."
+            ));
+        }
+
+        let mut steps = Vec::new();
+        for bundle in &self.new_backups {
+            let bundle = bundle.display().to_string();
+            let summary = format!("Restore the changesets saved in {bundle}");
+            steps.push(step(["unbundle", bundle.as_str()], summary));
+        }
+        if !created.is_empty() {
+            let commit = self.kind == UndoKind::Commit;
+            let mut args = vec!["--config", "extensions.strip=", "strip"];
+            if commit {
+                args.push("--keep");
+            }
+            for node in &created {
+                args.extend(["-r", node]);
+            }
+            let nodes = created
+                .iter()
+                .map(|node| short(node))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let summary = if commit {
+                format!(
+                    "Strip the new commit {nodes}, keeping its changes in the working directory"
+                )
+            } else {
+                format!(
+                    "Strip {} changeset(s) created by the action: {nodes} (hg keeps a backup bundle)",
+                    created.len()
+                )
+            };
+            steps.push(step(args, summary));
+        }
+        for (name, node) in &self.before.bookmarks {
+            if self.after.bookmarks.get(name) != Some(node) {
+                steps.push(step(
+                    ["bookmark", "-f", "-r", node.as_str(), name.as_str()],
+                    format!("Move bookmark {name} back to {}", short(node)),
+                ));
+            }
+        }
+        for name in self.after.bookmarks.keys() {
+            if !self.before.bookmarks.contains_key(name) {
+                steps.push(step(
+                    ["bookmark", "-d", name.as_str()],
+                    format!("Delete bookmark {name}"),
+                ));
+            }
+        }
+        let parent_changed =
+            self.kind != UndoKind::Commit && self.before.parent != self.after.parent;
+        let active_changed = self.before.active_bookmark != self.after.active_bookmark;
+        match (&self.before.active_bookmark, &self.before.parent) {
+            (Some(name), _) if parent_changed || active_changed => steps.push(step(
+                ["update", name.as_str()],
+                format!("Update back to bookmark {name} and reactivate it"),
+            )),
+            (None, Some(parent)) if parent_changed => steps.push(step(
+                ["update", "-r", parent.as_str()],
+                format!("Update the working directory back to {}", short(parent)),
+            )),
+            (None, _) if active_changed => {
+                steps.push(step(["bookmark", "-i"], "Deactivate the active bookmark"))
+            }
+            _ => {}
+        }
+
+        if steps.is_empty() {
+            return Err("the recorded state shows nothing to restore".to_string());
+        }
+        Ok(steps)
+    }
+
+    pub fn preview(&self, steps: &[UndoStep]) -> String {
+        let mut lines = vec![format!(
+            "Undo '{}' (ran {}):",
+            self.label,
+            self.recorded_at.format("%H:%M:%S")
+        )];
+        lines.extend(steps.iter().map(|step| format!("- {}", step.summary)));
+        lines.join("\n")
+    }
+}
+
+pub fn backup_bundles(repo_root: &Path) -> BTreeSet<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(repo_root.join(".hg").join("strip-backup")) else {
+        return BTreeSet::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hg"))
+        .collect()
+}
+
+fn step<S: Into<String>>(
+    args: impl IntoIterator<Item = S>,
+    summary: impl Into<String>,
+) -> UndoStep {
+    UndoStep {
+        args: args.into_iter().map(Into::into).collect(),
+        summary: summary.into(),
+    }
+}
+
+fn short(node: &str) -> &str {
+    node.get(..12).unwrap_or(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(
+        parent: &str,
+        active: Option<&str>,
+        bookmarks: &[(&str, &str)],
+        revs: &[(&str, i64)],
+    ) -> RepoState {
+        RepoState {
+            parent: Some(parent.to_string()),
+            active_bookmark: active.map(str::to_string),
+            bookmarks: bookmarks
+                .iter()
+                .map(|(name, node)| (name.to_string(), node.to_string()))
+                .collect(),
+            revisions: revs
+                .iter()
+                .map(|(node, rev)| (node.to_string(), *rev))
+                .collect(),
+        }
+    }
+
+    fn entry(kind: UndoKind, before: RepoState, after: RepoState, backups: &[&str]) -> UndoEntry {
+        UndoEntry {
+            label: "hg test".to_string(),
+            kind,
+            before,
+            after,
+            new_backups: backups.iter().map(PathBuf::from).collect(),
+            recorded_at: Local::now(),
+        }
+    }
+
+    fn args(steps: &[UndoStep]) -> Vec<String> {
+        steps.iter().map(|step| step.args.join(" ")).collect()
+    }
+
+    #[test]
+    fn commit_undo_strips_new_commit_and_keeps_changes() {
+        let before = state("aaa", Some("main"), &[("main", "aaa")], &[("aaa", 0)]);
+        let after = state(
+            "bbb",
+            Some("main"),
+            &[("main", "bbb")],
+            &[("aaa", 0), ("bbb", 1)],
+        );
+        let steps = entry(UndoKind::Commit, before, after.clone(), &[])
+            .plan(&after, false)
+            .expect("plan");
+        assert_eq!(
+            args(&steps),
+            vec![
+                "--config extensions.strip= strip --keep -r bbb",
+                "bookmark -f -r aaa main",
+            ]
+        );
+    }
+
+    #[test]
+    fn rebase_undo_unbundles_backup_and_restores_bookmarks_and_parent() {
+        let before = state(
+            "bbb",
+            None,
+            &[("feature", "bbb")],
+            &[("aaa", 0), ("bbb", 1), ("ccc", 2)],
+        );
+        let after = state(
+            "ddd",
+            None,
+            &[("feature", "ddd")],
+            &[("aaa", 0), ("ccc", 2), ("ddd", 3)],
+        );
+        let steps = entry(
+            UndoKind::Rewrite,
+            before,
+            after.clone(),
+            &["/repo/.hg/strip-backup/bbb-rebase.hg"],
+        )
+        .plan(&after, false)
+        .expect("plan");
+        assert_eq!(
+            args(&steps),
+            vec![
+                "unbundle /repo/.hg/strip-backup/bbb-rebase.hg",
+                "--config extensions.strip= strip -r ddd",
+                "bookmark -f -r bbb feature",
+                "update -r bbb",
+            ]
+        );
+    }
+
+    #[test]
+    fn bookmark_rename_undo_recreates_old_name_and_reactivates() {
+        let before = state("aaa", Some("old"), &[("old", "aaa")], &[("aaa", 0)]);
+        let after = state("aaa", Some("new"), &[("new", "aaa")], &[("aaa", 0)]);
+        let steps = entry(UndoKind::Bookmarks, before, after.clone(), &[])
+            .plan(&after, false)
+            .expect("plan");
+        assert_eq!(
+            args(&steps),
+            vec!["bookmark -f -r aaa old", "bookmark -d new", "update old"]
+        );
+    }
+
+    #[test]
+    fn hidden_changesets_without_backup_need_undo_extension() {
+        let before = state("bbb", None, &[], &[("aaa", 0), ("bbb", 1)]);
+        let after = state("ccc", None, &[], &[("aaa", 0), ("ccc", 2)]);
+        let undo = entry(UndoKind::Rewrite, before, after.clone(), &[]);
+        assert!(
+            undo.plan(&after, false)
+                .unwrap_err()
+                .contains("undo extension")
+        );
+        assert_eq!(args(&undo.plan(&after, true).expect("plan")), vec!["undo"]);
+    }
+
+    #[test]
+    fn undo_refuses_once_the_repository_moved_on() {
+        let before = state("aaa", None, &[], &[("aaa", 0)]);
+        let after = state("bbb", None, &[], &[("aaa", 0), ("bbb", 1)]);
+        let undo = entry(UndoKind::Commit, before, after.clone(), &[]);
+
+        let committed_again = state("ccc", None, &[], &[("aaa", 0), ("bbb", 1), ("ccc", 2)]);
+        for has_undo_extension in [false, true] {
+            let err = undo.plan(&committed_again, has_undo_extension).unwrap_err();
+            assert!(err.contains("repository changed"));
+        }
+        let moved_bookmark = state("bbb", None, &[("main", "bbb")], &[("aaa", 0), ("bbb", 1)]);
+        assert!(undo.plan(&moved_bookmark, true).is_err());
+
+        let renumbered = state("bbb", None, &[], &[("aaa", 0), ("bbb", 5)]);
+        assert!(undo.plan(&renumbered, true).is_ok());
+    }
+
+    #[test]
+    fn shelve_undo_unshelves_by_name() {
+        let state = state("aaa", None, &[], &[("aaa", 0)]);
+        let undo = entry(
+            UndoKind::Shelve("wip".to_string()),
+            state.clone(),
+            state,
+            &[],
+        );
+        let steps = undo.plan(&RepoState::default(), true).expect("plan");
+        assert_eq!(args(&steps), vec!["unshelve --name wip"]);
+        assert!(undo.preview(&steps).contains("- Unshelve 'wip'"));
+    }
+}